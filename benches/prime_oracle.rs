@@ -0,0 +1,55 @@
+//! `cargo bench --bench prime_oracle` compares the oracle with computing every answer.
+//! A plain timing loop, good enough to see whether the sieve and the cache pay off.
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use num_bigint::{BigInt, BigUint};
+use protohackers::problems::prime_time::oracle::{PrimeOracle, DEFAULT_SIEVE_LIMIT};
+use protohackers::problems::prime_time::primality::{self, next_prime};
+
+const ROUNDS: usize = 200_000;
+
+fn bench(name: &str, numbers: &[BigUint], check: impl Fn(&BigUint) -> bool) {
+    let started = Instant::now();
+    let mut primes = 0;
+    for i in 0..ROUNDS {
+        primes += check(black_box(&numbers[i % numbers.len()])) as usize;
+    }
+    let elapsed = started.elapsed();
+    println!(
+        "{name:<24} {:>8.0} ns/lookup ({primes} primes)",
+        elapsed.as_nanos() as f64 / ROUNDS as f64
+    );
+}
+
+fn main() {
+    let started = Instant::now();
+    let oracle = PrimeOracle::default();
+    println!(
+        "sieve up to {DEFAULT_SIEVE_LIMIT} built in {:?}",
+        started.elapsed()
+    );
+
+    let small: Vec<BigUint> = (0..10_000_u64)
+        .map(|n| BigUint::from(n * 1_597 % DEFAULT_SIEVE_LIMIT))
+        .collect();
+    // The same 100 large primes over and over, like a load test does
+    let mut hot = vec![next_prime(&BigInt::from(10_u32).pow(30))];
+    while hot.len() < 100 {
+        let last = BigInt::from(hot.last().unwrap().clone());
+        hot.push(next_prime(&last));
+    }
+    // Never the same number twice, every other one is odd
+    let cold: Vec<BigUint> = (0..ROUNDS as u64)
+        .map(|n| BigUint::from(10_u32).pow(30) + n)
+        .collect();
+
+    bench("small, computed", &small, primality::is_prime);
+    bench("small, oracle", &small, |n| oracle.is_prime(n));
+    bench("hot large, computed", &hot, primality::is_prime);
+    bench("hot large, oracle", &hot, |n| oracle.is_prime(n));
+    bench("cold large, computed", &cold, primality::is_prime);
+    bench("cold large, oracle", &cold, |n| oracle.is_prime(n));
+    println!("{:?}", oracle.stats());
+}
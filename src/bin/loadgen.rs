@@ -0,0 +1,60 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use clap::Parser;
+use protohackers::loadgen::{self, LoadOptions};
+use protohackers::problems::Problem;
+use protohackers::{logging, Config, Error};
+
+/// Opens many concurrent clients against a problem server and reports the latency of its
+/// answers and its throughput
+#[derive(Debug, Parser)]
+#[command(version)]
+struct Cli {
+    /// Number or name of the problem: 1 prime-time, 2 means-to-an-end, 3 budget-chat,
+    /// 4 unusual-database-program or 6 speed-daemon
+    problem: Problem,
+
+    /// Address of the server
+    #[arg(long, default_value = "127.0.0.1:5555")]
+    target: SocketAddr,
+
+    /// Number of concurrent clients
+    #[arg(long, default_value_t = 100)]
+    clients: usize,
+
+    /// How long the clients keep sending, in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 10)]
+    duration: u64,
+
+    /// How long every client waits between two requests, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pause: u64,
+
+    /// Connecting or waiting for an answer for longer counts as an error, in seconds
+    #[arg(long, value_name = "SECONDS", default_value_t = 5)]
+    timeout: u64,
+
+    /// Makes the test data repeatable, logged at startup if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    let cli = Cli::parse();
+    logging::init(&Config::default())?;
+
+    let options = LoadOptions {
+        target: cli.target,
+        clients: cli.clients,
+        duration: Duration::from_secs(cli.duration),
+        pause: Duration::from_millis(cli.pause),
+        timeout: Duration::from_secs(cli.timeout),
+        seed: cli.seed,
+    };
+    let report = loadgen::run(cli.problem, &options).await?;
+    println!("{report}");
+
+    Ok(())
+}
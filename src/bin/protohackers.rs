@@ -0,0 +1,243 @@
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::{Parser, Subcommand};
+use protohackers::chaos::{self, ChaosOptions};
+use protohackers::config::Args;
+use protohackers::problems::Problem;
+use protohackers::{logging, shutdown, Config, Error};
+use tokio::net::{TcpListener, UdpSocket};
+use tokio::select;
+use tokio::task::JoinSet;
+use tracing::{info, info_span, Instrument};
+
+#[derive(Debug, Parser)]
+#[command(version, about = "Servers for the protohackers.com problems")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Serves one or more problems in this process
+    Serve {
+        /// Number or name of the problem, e.g. `6` or `speed-daemon`, optionally followed
+        /// by `:<port>`. Problems without a port get consecutive ports starting at `--port`.
+        #[arg(required = true, value_name = "PROBLEM[:PORT]")]
+        problems: Vec<Listen>,
+
+        #[command(flatten)]
+        args: Box<Args>,
+    },
+    /// Forwards traffic to a server while fragmenting, delaying, reordering and dropping it
+    Chaos(Box<ChaosArgs>),
+    /// Lists the problems with their numbers
+    List,
+}
+
+#[derive(Debug, clap::Args)]
+struct ChaosArgs {
+    /// Address the proxy listens on
+    #[arg(long)]
+    listen: SocketAddr,
+
+    /// Address of the server behind the proxy
+    #[arg(long)]
+    target: SocketAddr,
+
+    /// Forward UDP datagrams instead of TCP connections
+    #[arg(long)]
+    udp: bool,
+
+    /// TCP: split writes into chunks of 1 up to this many bytes
+    #[arg(long, value_name = "BYTES")]
+    chunk: Option<usize>,
+
+    /// TCP: merge everything read within this many milliseconds into one write
+    #[arg(long, value_name = "MS")]
+    coalesce: Option<u64>,
+
+    /// Delay every chunk or datagram by this many milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    latency: u64,
+
+    /// Add a random delay of up to this many milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    jitter: u64,
+
+    /// UDP: percentage of datagrams to drop
+    #[arg(long, value_name = "PERCENT", default_value_t = 0.0)]
+    drop: f64,
+
+    /// UDP: percentage of datagrams to hold back, so later ones overtake them
+    #[arg(long, value_name = "PERCENT", default_value_t = 0.0)]
+    reorder: f64,
+
+    /// UDP: how long held back datagrams wait, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 50)]
+    reorder_delay: u64,
+
+    /// Makes the random decisions repeatable, logged at startup if not given
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+impl ChaosArgs {
+    fn options(&self) -> ChaosOptions {
+        ChaosOptions {
+            max_chunk: self.chunk,
+            coalesce: self.coalesce.map(Duration::from_millis),
+            latency: Duration::from_millis(self.latency),
+            jitter: Duration::from_millis(self.jitter),
+            drop_percent: self.drop,
+            reorder_percent: self.reorder,
+            reorder_delay: Duration::from_millis(self.reorder_delay),
+            seed: self.seed,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Listen {
+    problem: Problem,
+    port: Option<u16>,
+}
+
+impl FromStr for Listen {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (problem, port) = match s.split_once(':') {
+            Some((problem, port)) => {
+                let port = port
+                    .parse()
+                    .map_err(|err| format!("invalid port {port:?}: {err}"))?;
+                (problem, Some(port))
+            }
+            None => (s, None),
+        };
+        Ok(Self {
+            problem: problem.parse()?,
+            port,
+        })
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    match Cli::parse().command {
+        Command::Serve { problems, args } => serve(problems, Config::from_args(*args)?).await,
+        Command::Chaos(args) => proxy(*args).await,
+        Command::List => {
+            for problem in Problem::ALL {
+                println!("{:>2}  {problem}", problem.number());
+            }
+            Ok(())
+        }
+    }
+}
+
+async fn serve(problems: Vec<Listen>, config: Config) -> Result<(), Error> {
+    logging::init(&config)?;
+
+    let mut servers = JoinSet::new();
+    for (problem, port) in assign_ports(problems, config.port) {
+        let config = Config {
+            port,
+            ..config.clone()
+        };
+        let span = info_span!("problem", number = problem.number(), name = %problem);
+        servers.spawn(async move { problem.run(&config).await }.instrument(span));
+    }
+
+    // A server that fails to start takes the others down with it
+    while let Some(result) = servers.join_next().await {
+        result??;
+    }
+    info!("all servers stopped");
+
+    Ok(())
+}
+
+/// Problems without a port get consecutive ports starting at `first_port`
+fn assign_ports(problems: Vec<Listen>, first_port: u16) -> Vec<(Problem, u16)> {
+    let mut next_port = first_port;
+    problems
+        .into_iter()
+        .map(|Listen { problem, port }| {
+            let port = port.unwrap_or_else(|| {
+                let port = next_port;
+                next_port = next_port.wrapping_add(1);
+                port
+            });
+            (problem, port)
+        })
+        .collect()
+}
+
+async fn proxy(args: ChaosArgs) -> Result<(), Error> {
+    logging::init(&Config::default())?;
+
+    let options = args.options();
+    let proxy = async {
+        match args.udp {
+            true => chaos::udp(UdpSocket::bind(args.listen).await?, args.target, options).await,
+            false => chaos::tcp(TcpListener::bind(args.listen).await?, args.target, options).await,
+        }
+    };
+    select! {
+        result = proxy => result,
+        _ = shutdown::signal() => {
+            info!("chaos proxy stopped");
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listen(s: &str) -> Result<(Problem, Option<u16>), String> {
+        s.parse::<Listen>()
+            .map(|Listen { problem, port }| (problem, port))
+    }
+
+    #[test]
+    fn parses_problems_with_an_optional_port() {
+        assert_eq!(listen("6"), Ok((Problem::SpeedDaemon, None)));
+        assert_eq!(
+            listen("speed-daemon:7000"),
+            Ok((Problem::SpeedDaemon, Some(7000)))
+        );
+        assert_eq!(listen("0:1"), Ok((Problem::SmokeTest, Some(1))));
+
+        for invalid in ["6:", "6:65536", "6:-1", "6:port", "speed-daemon:7000:1"] {
+            let err = listen(invalid).unwrap_err();
+            assert!(err.starts_with("invalid port"), "{invalid}: {err}");
+        }
+        assert!(listen("speed-demon:7000")
+            .unwrap_err()
+            .contains("unknown problem"));
+        assert!(listen("12").unwrap_err().contains("there is no problem 12"));
+    }
+
+    #[test]
+    fn assigns_consecutive_ports_to_problems_without_one() {
+        let problems = ["0", "1:9000", "prime-time", "4:5555", "11"]
+            .map(|s| s.parse().unwrap())
+            .into();
+        assert_eq!(
+            assign_ports(problems, 5555),
+            [
+                (Problem::SmokeTest, 5555),
+                (Problem::PrimeTime, 9000),
+                (Problem::PrimeTime, 5556),
+                (Problem::UnusualDatabaseProgram, 5555),
+                (Problem::PestControl, 5557),
+            ]
+        );
+    }
+}
@@ -0,0 +1,266 @@
+//! A proxy that sits in front of a server and treats its traffic the way the Protohackers
+//! checkers and a bad network do: writes are split at arbitrary byte boundaries or merged,
+//! delivery is delayed, and datagrams arrive out of order or not at all.
+//!
+//! Like the proxy of problem 5 every TCP connection gets a connection to the target and both
+//! directions are forwarded on their own until either side closes. Every UDP peer gets its own
+//! socket towards the target, so the answers can be sent back to the right peer.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::time::Instant;
+use tracing::{debug, info, info_span, warn, Instrument};
+
+use crate::rng::Rng;
+use crate::udp::MAX_DATAGRAM_SIZE;
+use crate::Error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChaosOptions {
+    /// TCP: split every write into chunks of 1 up to this many bytes, `Some(1)` forwards
+    /// byte by byte. `None` forwards writes the way they were read.
+    pub max_chunk: Option<usize>,
+    /// TCP: after a read, keep reading for this long and forward everything as one write
+    pub coalesce: Option<Duration>,
+    /// Added before every chunk or datagram is forwarded
+    pub latency: Duration,
+    /// A random delay between zero and this is added on top of `latency`
+    pub jitter: Duration,
+    /// UDP: percentage of datagrams that are dropped, in both directions
+    pub drop_percent: f64,
+    /// UDP: percentage of datagrams that are held back by `reorder_delay`,
+    /// so the ones after them overtake them
+    pub reorder_percent: f64,
+    pub reorder_delay: Duration,
+    /// The same seed makes the same decisions, as long as the traffic is the same.
+    /// `None` picks one from the clock.
+    pub seed: Option<u64>,
+}
+
+impl Default for ChaosOptions {
+    fn default() -> Self {
+        Self {
+            max_chunk: None,
+            coalesce: None,
+            latency: Duration::ZERO,
+            jitter: Duration::ZERO,
+            drop_percent: 0.0,
+            reorder_percent: 0.0,
+            reorder_delay: Duration::from_millis(50),
+            seed: None,
+        }
+    }
+}
+
+struct Chaos {
+    options: ChaosOptions,
+    rng: Rng,
+}
+
+impl Chaos {
+    fn delay(&self) -> Duration {
+        self.options.latency + self.rng.up_to(self.options.jitter)
+    }
+}
+
+/// Forwards every connection accepted by `listener` to `target` until it fails
+pub async fn tcp(
+    listener: TcpListener,
+    target: SocketAddr,
+    options: ChaosOptions,
+) -> Result<(), Error> {
+    info!(addr = %listener.local_addr()?, %target, "chaos proxy listening");
+    let chaos = Arc::new(Chaos {
+        rng: Rng::new(options.seed, "chaos"),
+        options,
+    });
+
+    let mut id = 0_usize;
+    loop {
+        let (inbound, addr) = listener.accept().await?;
+        let chaos = chaos.clone();
+        tokio::spawn(
+            async move {
+                info!("connect");
+                if let Err(err) = forward(inbound, target, &chaos).await {
+                    warn!(error = %err, "forwarding failed");
+                }
+                info!("disconnect");
+            }
+            .instrument(info_span!("connection", id, %addr)),
+        );
+        id = id.wrapping_add(1);
+    }
+}
+
+async fn forward(mut inbound: TcpStream, target: SocketAddr, chaos: &Chaos) -> Result<(), Error> {
+    let mut outbound = TcpStream::connect(target).await?;
+    // Otherwise the kernel merges the chunks again
+    inbound.set_nodelay(true)?;
+    outbound.set_nodelay(true)?;
+
+    let (mut inbound_r, mut inbound_w) = inbound.split();
+    let (mut outbound_r, mut outbound_w) = outbound.split();
+
+    let original_to_target =
+        pipe(&mut inbound_r, &mut outbound_w, chaos).instrument(info_span!("o2t"));
+    let target_to_original =
+        pipe(&mut outbound_r, &mut inbound_w, chaos).instrument(info_span!("t2o"));
+
+    // Each side closes its half once the other one is done, a client that stops sending
+    // still gets the rest of the answers
+    tokio::try_join!(original_to_target, target_to_original)?;
+    Ok(())
+}
+
+/// Forwards one direction until the reader closes
+async fn pipe<R, W>(reader: &mut R, writer: &mut W, chaos: &Chaos) -> Result<(), Error>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buffer = vec![0_u8; 64 * 1024];
+    let mut pending = Vec::new();
+
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            writer.shutdown().await?;
+            return Ok(());
+        }
+        pending.extend_from_slice(&buffer[..read]);
+
+        if let Some(coalesce) = chaos.options.coalesce {
+            let deadline = Instant::now() + coalesce;
+            while let Ok(read) = tokio::time::timeout_at(deadline, reader.read(&mut buffer)).await {
+                match read? {
+                    0 => break,
+                    read => pending.extend_from_slice(&buffer[..read]),
+                }
+            }
+        }
+
+        let mut rest = &pending[..];
+        while !rest.is_empty() {
+            let len = match chaos.options.max_chunk {
+                Some(max_chunk) => chaos.rng.between_one_and(max_chunk).min(rest.len()),
+                None => rest.len(),
+            };
+            let (chunk, after) = rest.split_at(len);
+            tokio::time::sleep(chaos.delay()).await;
+            writer.write_all(chunk).await?;
+            writer.flush().await?;
+            rest = after;
+        }
+        debug!(bytes = pending.len(), "forwarded");
+        pending.clear();
+    }
+}
+
+/// Forwards every datagram received on `socket` to `target` and the answers back,
+/// until the socket fails
+pub async fn udp(
+    socket: UdpSocket,
+    target: SocketAddr,
+    options: ChaosOptions,
+) -> Result<(), Error> {
+    info!(addr = %socket.local_addr()?, %target, "chaos proxy listening (UDP)");
+    let socket = Arc::new(socket);
+    let chaos = Arc::new(Chaos {
+        rng: Rng::new(options.seed, "chaos"),
+        options,
+    });
+    let bind_addr: SocketAddr = match target {
+        SocketAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+        SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
+    };
+
+    let mut peers = HashMap::<SocketAddr, Arc<UdpSocket>>::new();
+    let mut buffer = vec![0_u8; MAX_DATAGRAM_SIZE];
+    loop {
+        let (len, addr) = socket.recv_from(&mut buffer).await?;
+
+        let upstream = match peers.get(&addr) {
+            Some(upstream) => upstream.clone(),
+            None => {
+                let upstream = Arc::new(UdpSocket::bind(bind_addr).await?);
+                upstream.connect(target).await?;
+                info!(%addr, "new peer");
+                tokio::spawn(answer(
+                    upstream.clone(),
+                    socket.clone(),
+                    addr,
+                    chaos.clone(),
+                ));
+                peers.insert(addr, upstream.clone());
+                upstream
+            }
+        };
+
+        let datagram = buffer[..len].to_vec();
+        send_later(&chaos, datagram, move |datagram| async move {
+            upstream.send(&datagram).await
+        })
+        .await;
+    }
+}
+
+/// Forwards what the target sends to a single peer
+async fn answer(
+    upstream: Arc<UdpSocket>,
+    socket: Arc<UdpSocket>,
+    addr: SocketAddr,
+    chaos: Arc<Chaos>,
+) {
+    let mut buffer = vec![0_u8; MAX_DATAGRAM_SIZE];
+    loop {
+        let len = match upstream.recv(&mut buffer).await {
+            Ok(len) => len,
+            Err(err) => {
+                warn!(%addr, error = %err, "could not receive from the target");
+                return;
+            }
+        };
+        let socket = socket.clone();
+        send_later(&chaos, buffer[..len].to_vec(), move |datagram| async move {
+            socket.send_to(&datagram, addr).await
+        })
+        .await;
+    }
+}
+
+/// Drops, delays or holds back a datagram before `send` gets it.
+/// Datagrams without a delay are sent right away, in order.
+async fn send_later<F, Fut>(chaos: &Chaos, datagram: Vec<u8>, send: F)
+where
+    F: FnOnce(Vec<u8>) -> Fut + Send + 'static,
+    Fut: std::future::Future<Output = std::io::Result<usize>> + Send,
+{
+    if chaos.rng.percent(chaos.options.drop_percent) {
+        debug!(bytes = datagram.len(), "dropped");
+        return;
+    }
+    let mut delay = chaos.delay();
+    if chaos.rng.percent(chaos.options.reorder_percent) {
+        debug!(bytes = datagram.len(), "held back");
+        delay += chaos.options.reorder_delay;
+    }
+
+    let delayed = async move {
+        tokio::time::sleep(delay).await;
+        if let Err(err) = send(datagram).await {
+            warn!(error = %err, "could not forward datagram");
+        }
+    };
+    match delay.is_zero() {
+        true => delayed.await,
+        false => {
+            tokio::spawn(delayed);
+        }
+    }
+}
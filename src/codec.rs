@@ -0,0 +1,331 @@
+//! Frames on top of byte streams: newline terminated lines, fixed size records and
+//! length prefixed messages.
+//!
+//! ```no_run
+//! # async fn example(mut connection: protohackers::Connection) -> Result<(), protohackers::Error> {
+//! use protohackers::codec::{FrameReader, FrameWriter, Framing};
+//!
+//! let (reader, writer) = connection.stream.split();
+//! let framing = Framing::Line { max_len: 1024 };
+//! let (mut reader, mut writer) = (FrameReader::new(reader, framing), FrameWriter::new(writer, framing));
+//!
+//! while let Some(line) = reader.read_frame().await? {
+//!     writer.write_frame(&line).await?;
+//!     writer.flush().await?;
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::fmt;
+use std::io;
+use std::num::NonZeroUsize;
+use std::string::FromUtf8Error;
+
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader, BufWriter};
+
+/// Width of the length prefix. Lengths are big endian, like every other number on the wire.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrefixWidth {
+    U8,
+    U16,
+    U32,
+}
+
+impl PrefixWidth {
+    fn len(self) -> usize {
+        match self {
+            Self::U8 => 1,
+            Self::U16 => 2,
+            Self::U32 => 4,
+        }
+    }
+
+    fn max(self) -> usize {
+        match self {
+            Self::U8 => u8::MAX as usize,
+            Self::U16 => u16::MAX as usize,
+            Self::U32 => u32::MAX as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Terminated by `\n`, which is not part of the frame. `max_len` does not include it.
+    /// When reading, a `\r` right before the `\n` is dropped as well.
+    Line { max_len: usize },
+    /// Every frame has exactly this many bytes. Never 0, reading empty frames
+    /// would never get anywhere in the stream.
+    Fixed(NonZeroUsize),
+    /// A length followed by that many bytes. The length does not include the prefix itself.
+    Prefixed { width: PrefixWidth, max_len: usize },
+}
+
+#[derive(Debug)]
+pub enum FrameError {
+    Io(io::Error),
+    /// The peer closed the stream in the middle of a frame
+    Truncated {
+        received: usize,
+    },
+    /// The frame is longer than the framing allows. When reading lines `len` is
+    /// how much was read before giving up, the line may be even longer.
+    TooLong {
+        len: usize,
+        max: usize,
+    },
+    /// A fixed size record with the wrong number of bytes was written
+    WrongSize {
+        len: usize,
+        expected: usize,
+    },
+    /// A line that was written contains a `\n`
+    NewlineInLine,
+    InvalidUtf8(FromUtf8Error),
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Truncated { received } => {
+                write!(f, "stream ended after {received} bytes of a frame")
+            }
+            Self::TooLong { len, max } => {
+                write!(f, "frame of {len} bytes exceeds the maximum of {max}")
+            }
+            Self::WrongSize { len, expected } => {
+                write!(f, "record of {len} bytes, expected {expected}")
+            }
+            Self::NewlineInLine => write!(f, "line contains a newline"),
+            Self::InvalidUtf8(err) => write!(f, "frame is not valid UTF-8: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for FrameError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            Self::InvalidUtf8(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FrameError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+/// Reads frames from a buffered stream
+pub struct FrameReader<R> {
+    reader: BufReader<R>,
+    framing: Framing,
+    /// The start of a line whose read was cancelled
+    partial: Vec<u8>,
+}
+
+impl<R: AsyncRead + Unpin> FrameReader<R> {
+    pub fn new(reader: R, framing: Framing) -> Self {
+        Self {
+            reader: BufReader::new(reader),
+            framing,
+            partial: Vec::new(),
+        }
+    }
+
+    /// Returns `None` if the stream ends between two frames.
+    ///
+    /// Reading lines is cancel safe, e.g. in a `select!`: a line that was cancelled
+    /// halfway is continued by the next call. Fixed and prefixed frames are not.
+    ///
+    /// After an error the position in the stream is unknown,
+    /// the only sensible thing left to do is to close the connection.
+    pub async fn read_frame(&mut self) -> Result<Option<Vec<u8>>, FrameError> {
+        match self.framing {
+            Framing::Line { max_len } => self.read_line(max_len).await,
+            Framing::Fixed(len) => self.read_exact(len.get()).await,
+            Framing::Prefixed { width, max_len } => {
+                let Some(prefix) = self.read_exact(width.len()).await? else {
+                    return Ok(None);
+                };
+                let len = prefix
+                    .iter()
+                    .fold(0_usize, |len, &byte| (len << 8) | byte as usize);
+                if len > max_len {
+                    return Err(FrameError::TooLong { len, max: max_len });
+                }
+
+                match self.read_exact(len).await {
+                    Ok(Some(frame)) => Ok(Some(frame)),
+                    Ok(None) => Err(FrameError::Truncated {
+                        received: width.len(),
+                    }),
+                    Err(FrameError::Truncated { received }) => Err(FrameError::Truncated {
+                        received: width.len() + received,
+                    }),
+                    Err(err) => Err(err),
+                }
+            }
+        }
+    }
+
+    /// Like [`read_frame`](Self::read_frame), for frames that have to be UTF-8
+    pub async fn read_string(&mut self) -> Result<Option<String>, FrameError> {
+        match self.read_frame().await? {
+            Some(frame) => Ok(Some(
+                String::from_utf8(frame).map_err(FrameError::InvalidUtf8)?,
+            )),
+            None => Ok(None),
+        }
+    }
+
+    /// Buffers at most one read more than `max_len`, however long the line is
+    async fn read_line(&mut self, max_len: usize) -> Result<Option<Vec<u8>>, FrameError> {
+        loop {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                return match self.partial.len() {
+                    0 => Ok(None),
+                    received => Err(FrameError::Truncated { received }),
+                };
+            }
+
+            let (taken, done) = match available.iter().position(|&byte| byte == b'\n') {
+                Some(newline) => (newline, true),
+                None => (available.len(), false),
+            };
+            self.partial.extend_from_slice(&available[..taken]);
+            self.reader.consume(taken + done as usize);
+
+            if done && self.partial.ends_with(b"\r") {
+                self.partial.pop();
+            }
+            // Until the newline shows up the last byte may still be a `\r` that is dropped
+            let max = match done {
+                true => max_len,
+                false => max_len.saturating_add(1),
+            };
+            if self.partial.len() > max {
+                return Err(FrameError::TooLong {
+                    len: std::mem::take(&mut self.partial).len(),
+                    max: max_len,
+                });
+            }
+            if done {
+                return Ok(Some(std::mem::take(&mut self.partial)));
+            }
+        }
+    }
+
+    /// Reads exactly `len` bytes whatever the framing, e.g. a body whose length was sent in
+    /// the frame before. All of them are allocated up front, check `len` before.
+    pub async fn read_bytes(&mut self, len: usize) -> Result<Vec<u8>, FrameError> {
+        self.read_exact(len)
+            .await?
+            .ok_or(FrameError::Truncated { received: 0 })
+    }
+
+    /// Returns `None` if the stream ends before the first byte
+    async fn read_exact(&mut self, len: usize) -> Result<Option<Vec<u8>>, FrameError> {
+        let mut frame = Vec::with_capacity(len);
+
+        while frame.len() < len {
+            let available = self.reader.fill_buf().await?;
+            if available.is_empty() {
+                return match frame.len() {
+                    0 => Ok(None),
+                    received => Err(FrameError::Truncated { received }),
+                };
+            }
+
+            let taken = available.len().min(len - frame.len());
+            frame.extend_from_slice(&available[..taken]);
+            self.reader.consume(taken);
+        }
+
+        Ok(Some(frame))
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Anything that was buffered but not read yet is lost
+    pub fn into_inner(self) -> R {
+        self.reader.into_inner()
+    }
+}
+
+/// Writes frames into a buffered stream. Nothing is sent until [`flush`](Self::flush).
+pub struct FrameWriter<W> {
+    writer: BufWriter<W>,
+    framing: Framing,
+}
+
+impl<W: AsyncWrite + Unpin> FrameWriter<W> {
+    pub fn new(writer: W, framing: Framing) -> Self {
+        Self {
+            writer: BufWriter::new(writer),
+            framing,
+        }
+    }
+
+    /// Checks the frame against the framing before writing anything
+    pub async fn write_frame(&mut self, frame: &[u8]) -> Result<(), FrameError> {
+        match self.framing {
+            Framing::Line { max_len } => {
+                if frame.len() > max_len {
+                    return Err(FrameError::TooLong {
+                        len: frame.len(),
+                        max: max_len,
+                    });
+                }
+                if frame.contains(&b'\n') {
+                    return Err(FrameError::NewlineInLine);
+                }
+                self.writer.write_all(frame).await?;
+                self.writer.write_u8(b'\n').await?;
+            }
+            Framing::Fixed(expected) => {
+                if frame.len() != expected.get() {
+                    return Err(FrameError::WrongSize {
+                        len: frame.len(),
+                        expected: expected.get(),
+                    });
+                }
+                self.writer.write_all(frame).await?;
+            }
+            Framing::Prefixed { width, max_len } => {
+                let max = max_len.min(width.max());
+                if frame.len() > max {
+                    return Err(FrameError::TooLong {
+                        len: frame.len(),
+                        max,
+                    });
+                }
+                let prefix = (frame.len() as u32).to_be_bytes();
+                self.writer.write_all(&prefix[4 - width.len()..]).await?;
+                self.writer.write_all(frame).await?;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<(), FrameError> {
+        self.writer.flush().await?;
+        Ok(())
+    }
+
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// Anything that was not flushed yet is lost
+    pub fn into_inner(self) -> W {
+        self.writer.into_inner()
+    }
+}
@@ -0,0 +1,413 @@
+//! Settings shared by every server binary.
+//!
+//! Values are taken from, in order of precedence: command line arguments,
+//! environment variables, an optional TOML config file and finally the defaults.
+//!
+//! ```toml
+//! bind = "::"
+//! port = 5555
+//! max_connections = 512
+//! max_connections_per_ip = 16
+//! # pause, reject or queue
+//! overload_policy = "queue"
+//! queue_timeout = 5
+//! shutdown_timeout = 10
+//! # per connection, leave out to disable
+//! idle_timeout = 60
+//! first_byte_timeout = 10
+//! session_timeout = 3600
+//! log_level = "info"
+//! # compact or json
+//! log_format = "compact"
+//! # serves GET /metrics on `bind`, leave out to disable
+//! metrics_port = 9100
+//! # problem 1 only: strict, extended or json-rpc
+//! prime_time_protocol = "strict"
+//! # requests per connection that are evaluated at the same time
+//! prime_time_window = 32
+//! # problem 1 only: larger integers are malformed
+//! prime_time_max_digits = 1000
+//! # problems 1, 3, 5 and 10: longest line in bytes, longer ones end the connection
+//! max_line_length = 1048576
+//! # problem 5: the Budget Chat server behind the proxy
+//! mob_in_the_middle_upstream = "[2a03:b0c0:1:d0::116a:8001]:16963"
+//! # problem 11: the authority server, host name and port
+//! pest_control_authority = "pestcontrol.protohackers.com:20547"
+//! ```
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use clap::Parser;
+use serde::Deserialize;
+use tokio::net::lookup_host;
+
+use crate::limit::{ConnectionLimit, OverloadPolicy};
+use crate::logging::LogFormat;
+use crate::problems::mob_in_the_middle;
+use crate::problems::pest_control;
+use crate::problems::prime_time::{self, Protocol as PrimeTimeProtocol};
+use crate::stream::Timeouts;
+use crate::Error;
+
+pub const DEFAULT_BIND: &str = "::";
+pub const DEFAULT_PORT: u16 = 5555;
+pub const DEFAULT_SHUTDOWN_TIMEOUT: u64 = 10;
+pub const DEFAULT_QUEUE_TIMEOUT: u64 = 5;
+pub const DEFAULT_MAX_LINE_LENGTH: usize = 1024 * 1024;
+
+#[derive(Debug, Clone, Default, Parser)]
+#[command(version, about)]
+pub struct Args {
+    /// Address or host name to listen on
+    #[arg(short, long, env = "PROTOHACKERS_BIND")]
+    pub bind: Option<String>,
+
+    /// Port to listen on
+    #[arg(short, long, env = "PROTOHACKERS_PORT")]
+    pub port: Option<u16>,
+
+    /// Maximum number of connections handled at the same time
+    #[arg(long, env = "PROTOHACKERS_MAX_CONNECTIONS")]
+    pub max_connections: Option<usize>,
+
+    /// Maximum number of connections from a single IP address handled at the same time
+    #[arg(long, env = "PROTOHACKERS_MAX_CONNECTIONS_PER_IP")]
+    pub max_connections_per_ip: Option<usize>,
+
+    /// What to do with new connections while a connection limit is reached
+    #[arg(long, env = "PROTOHACKERS_OVERLOAD_POLICY")]
+    pub overload_policy: Option<OverloadPolicy>,
+
+    /// Seconds a connection waits for a free slot with the `queue` overload policy,
+    /// and for a slot of its IP with `pause`
+    #[arg(long, env = "PROTOHACKERS_QUEUE_TIMEOUT")]
+    pub queue_timeout: Option<u64>,
+
+    /// Seconds to wait for running connections when shutting down
+    #[arg(long, env = "PROTOHACKERS_SHUTDOWN_TIMEOUT")]
+    pub shutdown_timeout: Option<u64>,
+
+    /// Seconds without reading or writing anything before a connection times out
+    #[arg(long, env = "PROTOHACKERS_IDLE_TIMEOUT")]
+    pub idle_timeout: Option<u64>,
+
+    /// Seconds a new connection has to send its first byte
+    #[arg(long, env = "PROTOHACKERS_FIRST_BYTE_TIMEOUT")]
+    pub first_byte_timeout: Option<u64>,
+
+    /// Seconds after which every connection times out
+    #[arg(long, env = "PROTOHACKERS_SESSION_TIMEOUT")]
+    pub session_timeout: Option<u64>,
+
+    /// Log level or filter directive, e.g. `debug` or
+    /// `protohackers::problems::speed_daemon=trace`.
+    /// Falls back to `RUST_LOG`.
+    #[arg(short, long, env = "PROTOHACKERS_LOG")]
+    pub log_level: Option<String>,
+
+    /// How log lines are formatted
+    #[arg(long, env = "PROTOHACKERS_LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+
+    /// Port for the Prometheus metrics endpoint, on the same address as the servers
+    #[arg(long, env = "PROTOHACKERS_METRICS_PORT")]
+    pub metrics_port: Option<u16>,
+
+    /// Problem 1 only: `extended` adds more methods, `json-rpc` serves them as JSON-RPC 2.0
+    #[arg(long, env = "PROTOHACKERS_PRIME_TIME_PROTOCOL")]
+    pub prime_time_protocol: Option<PrimeTimeProtocol>,
+
+    /// Problem 1 only: requests per connection that are evaluated at the same time,
+    /// reading waits while this many are not answered yet
+    #[arg(long, env = "PROTOHACKERS_PRIME_TIME_WINDOW")]
+    pub prime_time_window: Option<usize>,
+
+    /// Problem 1 only: integers with more digits are answered like a malformed request,
+    /// checking them would take too long
+    #[arg(long, env = "PROTOHACKERS_PRIME_TIME_MAX_DIGITS")]
+    pub prime_time_max_digits: Option<usize>,
+
+    /// Problems 1, 3, 5 and 10: longest line in bytes, without the newline.
+    /// A client that sends a longer one is disconnected.
+    #[arg(long, env = "PROTOHACKERS_MAX_LINE_LENGTH")]
+    pub max_line_length: Option<usize>,
+
+    /// Problem 5 only: address of the Budget Chat server behind the proxy
+    #[arg(long, env = "PROTOHACKERS_MOB_IN_THE_MIDDLE_UPSTREAM")]
+    pub mob_in_the_middle_upstream: Option<SocketAddr>,
+
+    /// Problem 11 only: host name and port of the authority server
+    #[arg(long, env = "PROTOHACKERS_PEST_CONTROL_AUTHORITY")]
+    pub pest_control_authority: Option<String>,
+
+    /// TOML file with any of the settings above
+    #[arg(short, long, env = "PROTOHACKERS_CONFIG")]
+    pub config: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub bind: String,
+    pub port: u16,
+    /// `None` means no limit
+    pub max_connections: Option<usize>,
+    /// `None` means no limit
+    pub max_connections_per_ip: Option<usize>,
+    pub overload_policy: OverloadPolicy,
+    /// In seconds
+    pub queue_timeout: u64,
+    /// In seconds
+    pub shutdown_timeout: u64,
+    /// In seconds, `None` means no timeout
+    pub idle_timeout: Option<u64>,
+    /// In seconds, `None` means no timeout
+    pub first_byte_timeout: Option<u64>,
+    /// In seconds, `None` means no timeout
+    pub session_timeout: Option<u64>,
+    pub log_level: Option<String>,
+    pub log_format: LogFormat,
+    /// `None` disables the metrics endpoint
+    pub metrics_port: Option<u16>,
+    pub prime_time_protocol: PrimeTimeProtocol,
+    /// 0 counts as 1
+    pub prime_time_window: usize,
+    /// Only positive integers count, everything else is never a prime
+    pub prime_time_max_digits: usize,
+    /// In bytes, without the newline
+    pub max_line_length: usize,
+    pub mob_in_the_middle_upstream: SocketAddr,
+    /// Host name and port, resolved whenever a site is dialled
+    pub pest_control_authority: String,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            bind: DEFAULT_BIND.to_string(),
+            port: DEFAULT_PORT,
+            max_connections: None,
+            max_connections_per_ip: None,
+            overload_policy: OverloadPolicy::default(),
+            queue_timeout: DEFAULT_QUEUE_TIMEOUT,
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            idle_timeout: None,
+            first_byte_timeout: None,
+            session_timeout: None,
+            log_level: None,
+            log_format: LogFormat::default(),
+            metrics_port: None,
+            prime_time_protocol: PrimeTimeProtocol::default(),
+            prime_time_window: prime_time::DEFAULT_WINDOW,
+            prime_time_max_digits: prime_time::DEFAULT_MAX_DIGITS,
+            max_line_length: DEFAULT_MAX_LINE_LENGTH,
+            mob_in_the_middle_upstream: mob_in_the_middle::DEFAULT_UPSTREAM.parse().unwrap(),
+            pest_control_authority: pest_control::DEFAULT_AUTHORITY.to_string(),
+        }
+    }
+}
+
+impl Config {
+    /// Reads the command line arguments, environment variables and config file.
+    /// Exits the process with a usage message if the arguments are invalid.
+    pub fn load() -> Result<Self, Error> {
+        Self::from_args(Args::parse())
+    }
+
+    pub fn from_args(args: Args) -> Result<Self, Error> {
+        let mut config = match &args.config {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|err| format!("could not read config file {path:?}: {err}"))?;
+                toml::from_str(&contents)
+                    .map_err(|err| format!("invalid config file {path:?}: {err}"))?
+            }
+            None => Self::default(),
+        };
+
+        if let Some(bind) = args.bind {
+            config.bind = bind;
+        }
+        if let Some(port) = args.port {
+            config.port = port;
+        }
+        if args.max_connections.is_some() {
+            config.max_connections = args.max_connections;
+        }
+        if args.max_connections_per_ip.is_some() {
+            config.max_connections_per_ip = args.max_connections_per_ip;
+        }
+        if let Some(overload_policy) = args.overload_policy {
+            config.overload_policy = overload_policy;
+        }
+        if let Some(queue_timeout) = args.queue_timeout {
+            config.queue_timeout = queue_timeout;
+        }
+        if let Some(shutdown_timeout) = args.shutdown_timeout {
+            config.shutdown_timeout = shutdown_timeout;
+        }
+        if args.idle_timeout.is_some() {
+            config.idle_timeout = args.idle_timeout;
+        }
+        if args.first_byte_timeout.is_some() {
+            config.first_byte_timeout = args.first_byte_timeout;
+        }
+        if args.session_timeout.is_some() {
+            config.session_timeout = args.session_timeout;
+        }
+        if args.log_level.is_some() {
+            config.log_level = args.log_level;
+        }
+        if let Some(log_format) = args.log_format {
+            config.log_format = log_format;
+        }
+        if args.metrics_port.is_some() {
+            config.metrics_port = args.metrics_port;
+        }
+        if let Some(prime_time_protocol) = args.prime_time_protocol {
+            config.prime_time_protocol = prime_time_protocol;
+        }
+        if let Some(prime_time_window) = args.prime_time_window {
+            config.prime_time_window = prime_time_window;
+        }
+        if let Some(prime_time_max_digits) = args.prime_time_max_digits {
+            config.prime_time_max_digits = prime_time_max_digits;
+        }
+        if let Some(max_line_length) = args.max_line_length {
+            config.max_line_length = max_line_length;
+        }
+        if let Some(upstream) = args.mob_in_the_middle_upstream {
+            config.mob_in_the_middle_upstream = upstream;
+        }
+        if let Some(authority) = args.pest_control_authority {
+            config.pest_control_authority = authority;
+        }
+
+        Ok(config)
+    }
+
+    /// Resolves `bind` and `port`. Fails if the address does not resolve to anything.
+    pub async fn listen_addr(&self) -> Result<SocketAddr, Error> {
+        let addr = (self.bind.as_str(), self.port);
+        lookup_host(addr)
+            .await
+            .map_err(|err| format!("could not resolve {}:{}: {err}", self.bind, self.port))?
+            .next()
+            .ok_or_else(|| {
+                format!("{}:{} did not resolve to any address", self.bind, self.port).into()
+            })
+    }
+
+    /// The filter directive for `tracing_subscriber::EnvFilter`:
+    /// the configured log level, then `RUST_LOG`, then the given default.
+    pub fn log_filter(&self, default: impl Into<String>) -> String {
+        self.log_level
+            .clone()
+            .or_else(|| std::env::var("RUST_LOG").ok())
+            .unwrap_or_else(|| default.into())
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        Timeouts {
+            idle: self.idle_timeout.map(Duration::from_secs),
+            first_byte: self.first_byte_timeout.map(Duration::from_secs),
+            session: self.session_timeout.map(Duration::from_secs),
+        }
+    }
+
+    pub fn connection_limit(&self) -> ConnectionLimit {
+        ConnectionLimit::new(
+            self.max_connections,
+            self.max_connections_per_ip,
+            self.overload_policy,
+            Duration::from_secs(self.queue_timeout),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a config file that is removed again when the test is done
+    struct TomlFile(PathBuf);
+
+    impl TomlFile {
+        fn new(name: &str, contents: &str) -> Self {
+            let path = std::env::temp_dir()
+                .join(format!("protohackers-{name}-{}.toml", std::process::id()));
+            std::fs::write(&path, contents).unwrap();
+            Self(path)
+        }
+    }
+
+    impl Drop for TomlFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
+    fn load(file: &TomlFile, args: &[&str]) -> Result<Config, Error> {
+        let path = file.0.to_str().unwrap();
+        let args = ["protohackers", "--config", path]
+            .into_iter()
+            .chain(args.iter().copied());
+        Config::from_args(Args::try_parse_from(args)?)
+    }
+
+    #[test]
+    fn prefers_arguments_over_the_environment_over_the_config_file() {
+        let file = TomlFile::new(
+            "precedence",
+            "bind = \"toml\"\nport = 1\nqueue_timeout = 7\nshutdown_timeout = 8\n",
+        );
+        // Only this test sets these
+        std::env::set_var("PROTOHACKERS_PORT", "2");
+        std::env::set_var("PROTOHACKERS_QUEUE_TIMEOUT", "9");
+
+        let config = load(&file, &["--port", "3"]).unwrap();
+        std::env::remove_var("PROTOHACKERS_PORT");
+        std::env::remove_var("PROTOHACKERS_QUEUE_TIMEOUT");
+
+        assert_eq!(config.port, 3);
+        assert_eq!(config.queue_timeout, 9);
+        assert_eq!(config.shutdown_timeout, 8);
+        assert_eq!(config.bind, "toml");
+        assert_eq!(config.max_line_length, DEFAULT_MAX_LINE_LENGTH);
+    }
+
+    #[test]
+    fn rejects_invalid_config_files_and_ports() {
+        let file = TomlFile::new("invalid-port", "port = 70000\n");
+        let err = load(&file, &[]).unwrap_err().to_string();
+        assert!(err.starts_with("invalid config file"), "{err}");
+
+        let file = TomlFile::new("unknown-field", "prot = 5555\n");
+        let err = load(&file, &[]).unwrap_err().to_string();
+        assert!(err.contains("unknown field"), "{err}");
+
+        assert!(Args::try_parse_from(["protohackers", "--port", "70000"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn fails_to_listen_on_addresses_that_do_not_resolve() {
+        let config = Config {
+            bind: "[::1]".to_string(),
+            ..Config::default()
+        };
+        let err = config.listen_addr().await.unwrap_err().to_string();
+        assert!(err.starts_with("could not resolve [::1]:5555"), "{err}");
+
+        let config = Config {
+            bind: "::1".to_string(),
+            port: 1234,
+            ..Config::default()
+        };
+        assert_eq!(
+            config.listen_addr().await.unwrap(),
+            "[::1]:1234".parse().unwrap()
+        );
+    }
+}
@@ -0,0 +1,262 @@
+//! Insecure Sockets Layer: an obfuscation layer that sits between a [`TcpStream`](tokio::net::TcpStream)
+//! (or anything else that is [`AsyncRead`] + [`AsyncWrite`]) and the application.
+//!
+//! The client sends a cipher spec first, every byte after that is encoded with the spec.
+//! Wrap the stream with [`CipherStream::accept`] and use the result like the original stream.
+
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, ReadBuf};
+
+use crate::Error;
+
+/// Cipher specs are at most this long, including the terminating zero byte
+pub const MAX_SPEC_LEN: usize = 80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    /// Reverse the order of bits in the byte
+    ReverseBits,
+    /// XOR the byte by the value N
+    Xor(u8),
+    /// XOR the byte by its position in the stream
+    XorPos,
+    /// Add N to the byte, modulo 256
+    Add(u8),
+    /// Add the position in the stream to the byte, modulo 256
+    AddPos,
+}
+
+impl Operation {
+    fn encode(self, byte: u8, pos: u64) -> u8 {
+        match self {
+            Self::ReverseBits => byte.reverse_bits(),
+            Self::Xor(n) => byte ^ n,
+            Self::XorPos => byte ^ pos as u8,
+            Self::Add(n) => byte.wrapping_add(n),
+            Self::AddPos => byte.wrapping_add(pos as u8),
+        }
+    }
+
+    fn decode(self, byte: u8, pos: u64) -> u8 {
+        match self {
+            Self::ReverseBits => byte.reverse_bits(),
+            Self::Xor(n) => byte ^ n,
+            Self::XorPos => byte ^ pos as u8,
+            Self::Add(n) => byte.wrapping_sub(n),
+            Self::AddPos => byte.wrapping_sub(pos as u8),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CipherSpec(Vec<Operation>);
+
+impl CipherSpec {
+    /// Parses a complete cipher spec, including the terminating zero byte.
+    /// Cipher specs that leave every byte unchanged are rejected.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let mut bytes = bytes.iter().copied();
+        let mut operations = Vec::new();
+
+        loop {
+            let operation = match bytes.next().ok_or("cipher spec is not terminated")? {
+                0x00 => break,
+                0x01 => Operation::ReverseBits,
+                0x02 => Operation::Xor(bytes.next().ok_or("xor without operand")?),
+                0x03 => Operation::XorPos,
+                0x04 => Operation::Add(bytes.next().ok_or("add without operand")?),
+                0x05 => Operation::AddPos,
+                other => return Err(format!("unknown cipher operation {other:#04x}").into()),
+            };
+            operations.push(operation);
+        }
+
+        if bytes.next().is_some() {
+            return Err("trailing bytes after cipher spec".into());
+        }
+
+        Self::new(operations)
+    }
+
+    /// Reads a cipher spec from the start of a stream.
+    /// Never reads more than [`MAX_SPEC_LEN`] bytes.
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Self, Error> {
+        let mut spec = Vec::with_capacity(MAX_SPEC_LEN);
+
+        loop {
+            match read_spec_byte(reader, &mut spec).await? {
+                0x00 => break,
+                // these take an operand which may be zero itself
+                0x02 | 0x04 => {
+                    read_spec_byte(reader, &mut spec).await?;
+                }
+                _ => {}
+            }
+        }
+
+        Self::from_bytes(&spec)
+    }
+
+    pub fn new(operations: Vec<Operation>) -> Result<Self, Error> {
+        let spec = Self(operations);
+        if spec.is_noop() {
+            return Err("cipher spec does not change anything".into());
+        }
+        Ok(spec)
+    }
+
+    pub fn operations(&self) -> &[Operation] {
+        &self.0
+    }
+
+    pub fn encode(&self, byte: u8, pos: u64) -> u8 {
+        self.0
+            .iter()
+            .fold(byte, |byte, operation| operation.encode(byte, pos))
+    }
+
+    pub fn decode(&self, byte: u8, pos: u64) -> u8 {
+        self.0
+            .iter()
+            .rev()
+            .fold(byte, |byte, operation| operation.decode(byte, pos))
+    }
+
+    /// Positions only matter modulo 256, so checking every byte at every
+    /// position in `0..256` covers the whole stream.
+    fn is_noop(&self) -> bool {
+        (0..=u8::MAX as u64).all(|pos| (0..=u8::MAX).all(|byte| self.encode(byte, pos) == byte))
+    }
+}
+
+async fn read_spec_byte<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    spec: &mut Vec<u8>,
+) -> Result<u8, Error> {
+    if spec.len() >= MAX_SPEC_LEN {
+        return Err("cipher spec too long".into());
+    }
+    let byte = reader.read_u8().await?;
+    spec.push(byte);
+    Ok(byte)
+}
+
+/// Encodes everything written to and decodes everything read from the inner stream.
+///
+/// Positions are tracked separately for each direction, both start at 0 right after the cipher spec.
+pub struct CipherStream<S> {
+    inner: S,
+    spec: CipherSpec,
+    read_pos: u64,
+    write_pos: u64,
+    /// Encoded bytes that the inner stream has not accepted yet
+    write_buffer: Vec<u8>,
+}
+
+impl<S> CipherStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    /// Reads the cipher spec from the client and wraps the stream
+    pub async fn accept(mut inner: S) -> Result<Self, Error> {
+        let spec = CipherSpec::read_from(&mut inner).await?;
+        Ok(Self::new(inner, spec))
+    }
+
+    pub fn new(inner: S, spec: CipherSpec) -> Self {
+        Self {
+            inner,
+            spec,
+            read_pos: 0,
+            write_pos: 0,
+            write_buffer: Vec::new(),
+        }
+    }
+
+    pub fn spec(&self) -> &CipherSpec {
+        &self.spec
+    }
+
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+
+    fn poll_flush_buffer(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        while !self.write_buffer.is_empty() {
+            let written = ready!(Pin::new(&mut self.inner).poll_write(cx, &self.write_buffer))?;
+            if written == 0 {
+                return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+            }
+            self.write_buffer.drain(..written);
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncRead for CipherStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let already_filled = buf.filled().len();
+
+        ready!(Pin::new(&mut this.inner).poll_read(cx, buf))?;
+
+        for byte in &mut buf.filled_mut()[already_filled..] {
+            *byte = this.spec.decode(*byte, this.read_pos);
+            this.read_pos += 1;
+        }
+
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl<S> AsyncWrite for CipherStream<S>
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        // Bytes that were encoded once must be written exactly like that,
+        // so get rid of them before accepting new ones.
+        ready!(this.poll_flush_buffer(cx))?;
+
+        for &byte in buf {
+            this.write_buffer
+                .push(this.spec.encode(byte, this.write_pos));
+            this.write_pos += 1;
+        }
+
+        // Try to get it out right away, whatever is left gets written on the next call.
+        if let Poll::Ready(Err(err)) = this.poll_flush_buffer(cx) {
+            return Poll::Ready(Err(err));
+        }
+
+        Poll::Ready(Ok(buf.len()))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_flush_buffer(cx))?;
+        Pin::new(&mut this.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        ready!(this.poll_flush_buffer(cx))?;
+        Pin::new(&mut this.inner).poll_shutdown(cx)
+    }
+}
@@ -1,59 +1,190 @@
-use std::{
-    future::Future,
-    net::{Ipv6Addr, SocketAddr, SocketAddrV6},
-    sync::{
-        atomic::{AtomicUsize, Ordering},
-        Arc,
-    },
-};
+use std::{future::Future, net::SocketAddr, sync::Arc, time::Duration};
 
-use tokio::net::{lookup_host, TcpListener, TcpStream, ToSocketAddrs};
+use tokio::net::TcpListener;
+use tokio::select;
+use tracing::{error, info, info_span, warn, Instrument};
+
+pub mod chaos;
+pub mod codec;
+pub mod config;
+pub mod isl;
+pub mod limit;
+pub mod loadgen;
+pub mod logging;
+pub mod lrcp;
+pub mod metrics;
+pub mod problems;
+mod rng;
+pub mod shutdown;
+pub mod stats;
+pub mod stream;
+pub mod udp;
+
+pub use config::Config;
+pub use shutdown::Summary as ShutdownSummary;
+pub use stream::Stream;
+
+use stream::TimedOut;
+pub use udp::serve as serve_udp;
+
+use shutdown::Handlers;
+use stats::{CloseReason, ConnectionStats, ServerStats};
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 
-pub async fn serve<A, H, Fut>(addr: A, mut handler: H) -> Result<(), Error>
+/// How long a handler gets to say goodbye after the session timeout before it is stopped
+pub const GOODBYE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Serves connections until the process receives SIGINT or SIGTERM,
+/// see [`serve_until`].
+///
+/// Every connection is handled by its own clone of `handler`, so state shared between
+/// connections is captured as an `Arc`, a channel sender or anything else that is cheap
+/// to clone: `move |connection| handle(connection, state)`.
+pub async fn serve<H, Fut>(config: &Config, handler: H) -> Result<ShutdownSummary, Error>
+where
+    H: FnOnce(Connection) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<(), Error>> + Send,
+{
+    serve_until(config, handler, shutdown::signal()).await
+}
+
+/// Serves connections until `shutdown` completes. Then it stops accepting and gives the
+/// running handlers `config.shutdown_timeout` seconds to finish before aborting them.
+///
+/// The timeouts from the config apply to the [`Stream`] of every connection. Handlers that
+/// are still running [`GOODBYE_TIMEOUT`] after the session timeout are stopped.
+///
+/// The numbers of every connection are added up in a [`ServerStats`],
+/// see [`stats::snapshot`].
+pub async fn serve_until<H, Fut, S>(
+    config: &Config,
+    handler: H,
+    shutdown: S,
+) -> Result<ShutdownSummary, Error>
 where
-    A: ToSocketAddrs,
-    H: FnMut(Connection) -> Fut + Send + 'static + Copy,
+    H: FnOnce(Connection) -> Fut + Clone + Send + 'static,
     Fut: Future<Output = Result<(), Error>> + Send,
+    S: Future<Output = ()>,
 {
-    let addr = lookup_host(addr)
-        .await?
-        .next()
-        .unwrap_or_else(|| SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 5555, 0, 0)));
+    let addr = config.listen_addr().await?;
 
     let listener = TcpListener::bind(addr).await?;
-    println!("Listening on {addr}");
+    info!(%addr, "listening");
+    metrics::start(config).await?;
+    let server_stats = ServerStats::register(format!("tcp://{addr}"));
 
     let mut id = 0_usize;
-    let running = Arc::new(AtomicUsize::new(0_usize));
-
-    while let Ok((stream, addr)) = listener.accept().await {
-        let connection = Connection::new(stream, addr, id);
-        let running = running.clone();
-        tokio::spawn(async move {
-            let currently_running = running.fetch_add(1, Ordering::SeqCst) + 1;
-            println!(">> [{id:>3}/{currently_running:>3}] {addr}");
-            if let Err(err) = handler(connection).await {
-                eprintln!("ERROR: {:?}", err);
-            };
-            println!("<< [{id:>3}/___] {addr}");
-            running.fetch_sub(1, Ordering::SeqCst);
-        });
+    let mut handlers = Handlers::new();
+    let limit = config.connection_limit();
+    let timeouts = config.timeouts();
+    tokio::pin!(shutdown);
+
+    loop {
+        let reservation = select! {
+            reservation = limit.reserve() => reservation,
+            _ = &mut shutdown => break,
+        };
+        let (stream, addr) = select! {
+            accepted = listener.accept() => match accepted {
+                Ok(accepted) => accepted,
+                Err(_) => break,
+            },
+            _ = &mut shutdown => break,
+        };
+
+        let stats = server_stats.connection();
+        let connection = Connection::new(Stream::new(stream, timeouts, stats.clone()), addr, id);
+        let limit = limit.clone();
+        let handler = handler.clone();
+        let span = info_span!("connection", id, %addr);
+        let currently_running = handlers.spawn(
+            async move {
+                // Rejected connections are closed by dropping them
+                let _permit = match limit.admit(reservation, addr.ip()).await {
+                    Ok(permit) => permit,
+                    Err(rejected) => {
+                        warn!(%rejected, "rejected");
+                        stats.close(CloseReason::Rejected);
+                        return;
+                    }
+                };
+                let hard_stop = connection
+                    .stream
+                    .session_deadline()
+                    .map(|deadline| deadline + GOODBYE_TIMEOUT);
+                let handled = async {
+                    match hard_stop {
+                        Some(hard_stop) => tokio::time::timeout_at(hard_stop, handler(connection))
+                            .await
+                            .unwrap_or_else(|_| Err(TimedOut::Session.into())),
+                        None => handler(connection).await,
+                    }
+                };
+                let reason = match handled.await {
+                    Ok(()) => CloseReason::Closed,
+                    Err(err) => match TimedOut::find(err.as_ref()) {
+                        Some(timed_out) => CloseReason::TimedOut(timed_out),
+                        None => {
+                            error!(error = %err, "handler failed");
+                            CloseReason::Failed
+                        }
+                    },
+                };
+                stats.close(reason);
+            }
+            .instrument(span.clone()),
+        );
+        span.in_scope(|| info!(running = currently_running, "connect"));
         id = id.wrapping_add(1);
     }
 
-    Ok(())
+    drop(listener);
+    info!(%addr, "stopped listening");
+
+    let summary = handlers
+        .drain(Duration::from_secs(config.shutdown_timeout))
+        .await;
+    info!(
+        drained = summary.drained,
+        aborted = summary.aborted,
+        "shutdown"
+    );
+    log_totals(&server_stats);
+
+    Ok(summary)
+}
+
+pub(crate) fn log_totals(stats: &ServerStats) {
+    let totals = stats.snapshot();
+    info!(
+        accepted = totals.accepted,
+        closed = totals.closed,
+        rejected = totals.rejected,
+        timed_out = totals.timed_out,
+        failed = totals.failed,
+        aborted = totals.aborted,
+        bytes_in = totals.bytes_in,
+        bytes_out = totals.bytes_out,
+        messages_in = totals.messages_in,
+        messages_out = totals.messages_out,
+        "totals"
+    );
 }
 
 pub struct Connection {
-    pub stream: TcpStream,
+    pub stream: Stream,
     pub addr: SocketAddr,
     pub id: usize,
 }
 
 impl Connection {
-    pub fn new(stream: TcpStream, addr: SocketAddr, id: usize) -> Self {
+    pub fn new(stream: Stream, addr: SocketAddr, id: usize) -> Self {
         Self { stream, addr, id }
     }
+
+    /// Grab this before splitting the stream to count messages
+    pub fn stats(&self) -> Arc<ConnectionStats> {
+        self.stream.stats().clone()
+    }
 }
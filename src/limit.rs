@@ -0,0 +1,205 @@
+//! Caps on the number of connections handled at the same time, in total and per IP address.
+//!
+//! Admitting a connection is done in two steps: [`ConnectionLimit::reserve`] before accepting,
+//! which is where the [`OverloadPolicy::Pause`] policy stops the accept loop, and
+//! [`ConnectionLimit::admit`] once the peer address is known.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tokio::time::Instant;
+
+/// What to do with new connections while the server is at its limit
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum OverloadPolicy {
+    /// Stop accepting until a connection slot is free.
+    /// Connections over the per IP limit wait up to the queue timeout for a slot of their IP,
+    /// without holding on to a total slot, then they are closed.
+    #[default]
+    Pause,
+    /// Accept and close right away
+    Reject,
+    /// Accept and wait for a slot for up to the queue timeout, then close
+    Queue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Rejected {
+    TooManyConnections,
+    TooManyConnectionsFrom(IpAddr),
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooManyConnections => write!(f, "connection limit reached"),
+            Self::TooManyConnectionsFrom(ip) => write!(f, "connection limit for {ip} reached"),
+        }
+    }
+}
+
+impl std::error::Error for Rejected {}
+
+#[derive(Debug, Clone)]
+pub struct ConnectionLimit {
+    total: Option<Arc<Semaphore>>,
+    per_ip: Option<usize>,
+    ips: Arc<Mutex<HashMap<IpAddr, Arc<Semaphore>>>>,
+    policy: OverloadPolicy,
+    queue_timeout: Duration,
+}
+
+/// A connection slot taken before accepting
+#[derive(Debug, Default)]
+pub struct Reservation(Option<OwnedSemaphorePermit>);
+
+/// Hold on to this for as long as the connection is being handled
+#[derive(Debug)]
+pub struct Permit {
+    _total: Option<OwnedSemaphorePermit>,
+    ip: Option<(IpAddr, OwnedSemaphorePermit)>,
+    ips: Arc<Mutex<HashMap<IpAddr, Arc<Semaphore>>>>,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let Some((ip, permit)) = self.ip.take() else {
+            return;
+        };
+        drop(permit);
+        forget_if_unused(&self.ips, ip);
+    }
+}
+
+/// Forgets about IPs without connections. Nobody can get hold of
+/// the semaphore while the map is locked.
+fn forget_if_unused(ips: &Mutex<HashMap<IpAddr, Arc<Semaphore>>>, ip: IpAddr) {
+    let mut ips = ips.lock().unwrap();
+    if ips
+        .get(&ip)
+        .is_some_and(|semaphore| Arc::strong_count(semaphore) == 1)
+    {
+        ips.remove(&ip);
+    }
+}
+
+impl ConnectionLimit {
+    pub fn new(
+        max_connections: Option<usize>,
+        max_connections_per_ip: Option<usize>,
+        policy: OverloadPolicy,
+        queue_timeout: Duration,
+    ) -> Self {
+        Self {
+            total: max_connections.map(|max| Arc::new(Semaphore::new(max))),
+            per_ip: max_connections_per_ip,
+            ips: Arc::default(),
+            policy,
+            queue_timeout,
+        }
+    }
+
+    /// Call this before accepting. With [`OverloadPolicy::Pause`] it waits for a free slot,
+    /// with every other policy it returns right away.
+    pub async fn reserve(&self) -> Reservation {
+        match (&self.total, self.policy) {
+            (Some(total), OverloadPolicy::Pause) => {
+                Reservation(total.clone().acquire_owned().await.ok())
+            }
+            _ => Reservation(None),
+        }
+    }
+
+    /// Takes a slot for a connection from `ip`, waiting or failing depending on the policy.
+    /// The slot of the IP is taken first and nothing waits for it while holding a total slot,
+    /// otherwise a single busy IP could keep everybody else out.
+    pub async fn admit(&self, reservation: Reservation, ip: IpAddr) -> Result<Permit, Rejected> {
+        let deadline = Instant::now() + self.queue_timeout;
+        let mut reserved = reservation.0;
+
+        let ip = match self.per_ip {
+            Some(per_ip) => {
+                let semaphore = self
+                    .ips
+                    .lock()
+                    .unwrap()
+                    .entry(ip)
+                    .or_insert_with(|| Arc::new(Semaphore::new(per_ip)))
+                    .clone();
+                let permit = match semaphore.clone().try_acquire_owned() {
+                    Ok(permit) => Some(permit),
+                    Err(_) => {
+                        // Give the total slot back while waiting, another IP can use it
+                        reserved = None;
+                        self.queue(semaphore, deadline).await
+                    }
+                };
+                let Some(permit) = permit else {
+                    // The semaphore might have been created just for this connection
+                    forget_if_unused(&self.ips, ip);
+                    return Err(Rejected::TooManyConnectionsFrom(ip));
+                };
+                Some((ip, permit))
+            }
+            None => None,
+        };
+
+        // Dropping it on rejection gives the IP slot back
+        let mut permit = Permit {
+            _total: None,
+            ip,
+            ips: self.ips.clone(),
+        };
+        permit._total = match (reserved, &self.total) {
+            (Some(reserved), _) => Some(reserved),
+            (None, Some(total)) => Some(
+                self.take(total.clone(), deadline)
+                    .await
+                    .ok_or(Rejected::TooManyConnections)?,
+            ),
+            (None, None) => None,
+        };
+
+        Ok(permit)
+    }
+
+    /// Waits for a slot of an IP. Unlike the total slots this never waits
+    /// longer than the queue timeout, not even with [`OverloadPolicy::Pause`].
+    async fn queue(
+        &self,
+        semaphore: Arc<Semaphore>,
+        deadline: Instant,
+    ) -> Option<OwnedSemaphorePermit> {
+        match self.policy {
+            OverloadPolicy::Reject => None,
+            OverloadPolicy::Pause | OverloadPolicy::Queue => {
+                tokio::time::timeout_at(deadline, semaphore.acquire_owned())
+                    .await
+                    .ok()?
+                    .ok()
+            }
+        }
+    }
+
+    async fn take(
+        &self,
+        semaphore: Arc<Semaphore>,
+        deadline: Instant,
+    ) -> Option<OwnedSemaphorePermit> {
+        match self.policy {
+            OverloadPolicy::Pause => semaphore.acquire_owned().await.ok(),
+            OverloadPolicy::Reject => semaphore.try_acquire_owned().ok(),
+            OverloadPolicy::Queue => tokio::time::timeout_at(deadline, semaphore.acquire_owned())
+                .await
+                .ok()?
+                .ok(),
+        }
+    }
+}
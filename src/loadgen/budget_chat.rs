@@ -0,0 +1,59 @@
+//! Every client joins the room and sends messages containing the time they were sent at.
+//! A sample is the time it took a message to reach one of the other clients, so every
+//! message is measured once per receiver.
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::time::{timeout_at, Duration};
+
+use super::{Load, Samples};
+use crate::Error;
+
+pub(super) async fn run(load: Arc<Load>) -> Result<Samples, Error> {
+    load.clients(load.options.clients, client).await
+}
+
+async fn client(id: usize, load: Arc<Load>) -> Samples {
+    let mut samples = Samples::default();
+    let result = async {
+        let (reader, mut writer) = load.connect().await?.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        // The welcome message, then the list of users in the room
+        load.within(lines.next_line()).await?;
+        writer.write_all(format!("user{id}\n").as_bytes()).await?;
+        load.within(lines.next_line()).await?;
+
+        let receive = async {
+            // Messages still on their way at the deadline are not measured
+            while let Ok(line) = timeout_at(load.deadline, lines.next_line()).await {
+                let line = line?.ok_or("connection closed")?;
+                // Joins and parts start with `*`
+                let Some(message) = line.strip_prefix('[') else {
+                    continue;
+                };
+                let sent = message
+                    .rsplit_once(' ')
+                    .and_then(|(_, micros)| micros.parse().ok())
+                    .ok_or_else(|| format!("unexpected message {line:?}"))?;
+                let sent = load.started + Duration::from_micros(sent);
+                samples.record(sent);
+            }
+            Ok::<_, Error>(())
+        };
+        let send = async {
+            while load.running() {
+                let micros = load.started.elapsed().as_micros();
+                writer.write_all(format!("{micros}\n").as_bytes()).await?;
+                load.pause().await;
+            }
+            Ok::<_, Error>(())
+        };
+
+        tokio::try_join!(receive, send)?;
+        Ok(())
+    }
+    .await;
+    samples.finish(result)
+}
@@ -0,0 +1,45 @@
+//! Every client inserts a random price with the next timestamp and queries the mean of all
+//! its prices. A sample is the time from sending both messages to reading the mean.
+
+use std::sync::Arc;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::time::Instant;
+
+use super::{Load, Samples};
+use crate::Error;
+
+pub(super) async fn run(load: Arc<Load>) -> Result<Samples, Error> {
+    load.clients(load.options.clients, client).await
+}
+
+fn message(kind: u8, first: i32, second: i32) -> [u8; 9] {
+    let mut message = [kind; 9];
+    message[1..5].copy_from_slice(&first.to_be_bytes());
+    message[5..].copy_from_slice(&second.to_be_bytes());
+    message
+}
+
+async fn client(_id: usize, load: Arc<Load>) -> Samples {
+    let mut samples = Samples::default();
+    let result = async {
+        let mut stream = load.connect().await?;
+        let mut timestamp = 0;
+
+        while load.running() {
+            timestamp += 1;
+            let price = (load.rng.next() % 10_000) as i32;
+            let mut request = message(b'I', timestamp, price).to_vec();
+            request.extend(message(b'Q', 1, timestamp));
+
+            let sent = Instant::now();
+            stream.write_all(&request).await?;
+            load.within(stream.read_i32()).await?;
+            samples.record(sent);
+            load.pause().await;
+        }
+        Ok(())
+    }
+    .await;
+    samples.finish(result)
+}
@@ -0,0 +1,254 @@
+//! Opens many concurrent clients against a problem server and measures how long it takes
+//! to answer them, see [`run`].
+//!
+//! Every client talks to the server in a loop until the configured duration is over. What a
+//! latency sample is depends on the protocol, each scenario module describes its own.
+
+use std::fmt;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tracing::{debug, info};
+
+use crate::config::DEFAULT_PORT;
+use crate::problems::Problem;
+use crate::rng::Rng;
+use crate::Error;
+
+mod budget_chat;
+mod means_to_an_end;
+mod prime_time;
+mod speed_daemon;
+mod unusual_database_program;
+
+/// The problems [`run`] has a scenario for
+pub const PROBLEMS: [Problem; 5] = [
+    Problem::PrimeTime,
+    Problem::MeansToAnEnd,
+    Problem::BudgetChat,
+    Problem::UnusualDatabaseProgram,
+    Problem::SpeedDaemon,
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoadOptions {
+    pub target: SocketAddr,
+    /// Number of concurrent clients. Problem 6 opens two cameras per client and one
+    /// dispatcher per road on top.
+    pub clients: usize,
+    /// How long the clients keep sending
+    pub duration: Duration,
+    /// How long every client waits between two requests
+    pub pause: Duration,
+    /// Connecting or waiting for an answer for longer counts as an error
+    pub timeout: Duration,
+    /// The same seed picks the same test data. `None` picks one from the clock.
+    pub seed: Option<u64>,
+}
+
+impl Default for LoadOptions {
+    fn default() -> Self {
+        Self {
+            target: SocketAddr::from(([127, 0, 0, 1], DEFAULT_PORT)),
+            clients: 100,
+            duration: Duration::from_secs(10),
+            pause: Duration::ZERO,
+            timeout: Duration::from_secs(5),
+            seed: None,
+        }
+    }
+}
+
+/// Runs the scenario for `problem` against `options.target` and waits for all clients
+pub async fn run(problem: Problem, options: &LoadOptions) -> Result<Report, Error> {
+    if !PROBLEMS.contains(&problem) {
+        return Err(format!("there is no load scenario for {problem}").into());
+    }
+
+    info!(%problem, target = %options.target, clients = options.clients, "generating load");
+    let started = Instant::now();
+    let load = Arc::new(Load {
+        options: options.clone(),
+        rng: Rng::new(options.seed, "loadgen"),
+        started,
+        deadline: started + options.duration,
+    });
+
+    let samples = match problem {
+        Problem::PrimeTime => prime_time::run(load).await?,
+        Problem::MeansToAnEnd => means_to_an_end::run(load).await?,
+        Problem::BudgetChat => budget_chat::run(load).await?,
+        Problem::UnusualDatabaseProgram => unusual_database_program::run(load).await?,
+        Problem::SpeedDaemon => speed_daemon::run(load).await?,
+        _ => unreachable!("checked above"),
+    };
+
+    let mut latencies = samples.latencies;
+    latencies.sort_unstable();
+    Ok(Report {
+        problem,
+        target: options.target,
+        clients: options.clients,
+        elapsed: started.elapsed(),
+        latencies,
+        errors: samples.errors,
+    })
+}
+
+/// What the clients of a run measured
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub problem: Problem,
+    pub target: SocketAddr,
+    pub clients: usize,
+    /// Until the last client finished, including the answers that were still on their way
+    pub elapsed: Duration,
+    /// One per answer, sorted
+    pub latencies: Vec<Duration>,
+    /// Failed connections, timeouts and wrong answers
+    pub errors: u64,
+}
+
+impl Report {
+    pub fn answers(&self) -> usize {
+        self.latencies.len()
+    }
+
+    /// Answers per second
+    pub fn throughput(&self) -> f64 {
+        self.answers() as f64 / self.elapsed.as_secs_f64()
+    }
+
+    /// Nearest rank, `percentile(50.0)` is the median. `None` without answers.
+    pub fn percentile(&self, percentile: f64) -> Option<Duration> {
+        let rank = (percentile / 100.0 * self.latencies.len() as f64).ceil() as usize;
+        self.latencies.get(rank.saturating_sub(1)).copied()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} at {}: {} clients for {:.1}s",
+            self.problem,
+            self.target,
+            self.clients,
+            self.elapsed.as_secs_f64()
+        )?;
+        writeln!(
+            f,
+            "  answers  {} ({:.1}/s)",
+            self.answers(),
+            self.throughput()
+        )?;
+        writeln!(f, "  errors   {}", self.errors)?;
+        write!(f, "  latency ")?;
+        for (name, percentile) in [("p50", 50.0), ("p90", 90.0), ("p99", 99.0), ("p99.9", 99.9)] {
+            match self.percentile(percentile) {
+                Some(latency) => write!(f, " {name} {}", millis(latency))?,
+                None => write!(f, " {name} -")?,
+            }
+        }
+        match self.latencies.last() {
+            Some(&latency) => write!(f, " max {}", millis(latency)),
+            None => write!(f, " max -"),
+        }
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.3}ms", duration.as_secs_f64() * 1000.0)
+}
+
+/// Latencies and errors, collected by every client on its own and merged at the end
+#[derive(Debug, Default)]
+struct Samples {
+    latencies: Vec<Duration>,
+    errors: u64,
+}
+
+impl Samples {
+    fn record(&mut self, sent: Instant) {
+        self.latencies.push(sent.elapsed());
+    }
+
+    fn merge(&mut self, other: Samples) {
+        self.latencies.extend(other.latencies);
+        self.errors += other.errors;
+    }
+
+    /// Counts the error a client stopped with
+    fn finish(mut self, result: Result<(), Error>) -> Self {
+        self.count_failure(result);
+        self
+    }
+
+    fn count_failure(&mut self, result: Result<(), Error>) {
+        if let Err(err) = result {
+            debug!(error = %err, "client failed");
+            self.errors += 1;
+        }
+    }
+}
+
+/// Shared by all clients of a run
+struct Load {
+    options: LoadOptions,
+    rng: Rng,
+    started: Instant,
+    deadline: Instant,
+}
+
+impl Load {
+    /// `false` once the clients should stop sending
+    fn running(&self) -> bool {
+        Instant::now() < self.deadline
+    }
+
+    async fn pause(&self) {
+        if !self.options.pause.is_zero() {
+            tokio::time::sleep(self.options.pause).await;
+        }
+    }
+
+    async fn connect(&self) -> Result<TcpStream, Error> {
+        let stream = self.within(TcpStream::connect(self.options.target)).await?;
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    }
+
+    /// Fails if `future` takes longer than the timeout
+    async fn within<T, E>(&self, future: impl Future<Output = Result<T, E>>) -> Result<T, Error>
+    where
+        E: Into<Error>,
+    {
+        match tokio::time::timeout(self.options.timeout, future).await {
+            Ok(result) => result.map_err(Into::into),
+            Err(_) => Err("timed out".into()),
+        }
+    }
+
+    /// Runs `count` clients, each with its id, and merges what they measured
+    async fn clients<F, Fut>(self: &Arc<Self>, count: usize, client: F) -> Result<Samples, Error>
+    where
+        F: Fn(usize, Arc<Load>) -> Fut,
+        Fut: Future<Output = Samples> + Send + 'static,
+    {
+        let mut clients = JoinSet::new();
+        for id in 0..count {
+            clients.spawn(client(id, self.clone()));
+        }
+
+        let mut samples = Samples::default();
+        while let Some(result) = clients.join_next().await {
+            samples.merge(result?);
+        }
+        Ok(samples)
+    }
+}
@@ -0,0 +1,44 @@
+//! Every client sends `isPrime` requests for random numbers below a million, one at a time.
+//! A sample is the time from sending a request to reading its response.
+
+use std::sync::Arc;
+
+use serde_json::Value;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::time::Instant;
+
+use super::{Load, Samples};
+use crate::Error;
+
+pub(super) async fn run(load: Arc<Load>) -> Result<Samples, Error> {
+    load.clients(load.options.clients, client).await
+}
+
+async fn client(_id: usize, load: Arc<Load>) -> Samples {
+    let mut samples = Samples::default();
+    let result = async {
+        let (reader, mut writer) = load.connect().await?.into_split();
+        let mut lines = BufReader::new(reader).lines();
+
+        while load.running() {
+            let number = load.rng.next() % 1_000_000;
+            let sent = Instant::now();
+            writer
+                .write_all(format!("{{\"method\":\"isPrime\",\"number\":{number}}}\n").as_bytes())
+                .await?;
+            let line = load
+                .within(lines.next_line())
+                .await?
+                .ok_or("connection closed")?;
+            let response: Value = serde_json::from_str(&line)?;
+            if response["method"] != "isPrime" || !response["prime"].is_boolean() {
+                return Err(format!("unexpected response {line:?}").into());
+            }
+            samples.record(sent);
+            load.pause().await;
+        }
+        Ok(())
+    }
+    .await;
+    samples.finish(result)
+}
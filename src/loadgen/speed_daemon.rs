@@ -0,0 +1,142 @@
+//! Every client is a pair of cameras ten miles apart on one of up to 16 roads, every road has
+//! a dispatcher. The clients report cars with new plates that pass both cameras far too fast.
+//! A sample is the time from reporting the second observation to the dispatcher receiving
+//! the ticket. Tickets that do not arrive within the timeout after the run count as errors.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::task::JoinSet;
+use tokio::time::{Duration, Instant};
+
+use super::{Load, Samples};
+use crate::Error;
+
+const MAX_ROADS: usize = 16;
+const SPEED_LIMIT: u16 = 60;
+
+/// Tickets that are expected but have not arrived yet, and the ones that have
+#[derive(Default)]
+struct Tickets {
+    /// plate -> when the second observation was sent
+    outstanding: Mutex<HashMap<String, Instant>>,
+    samples: Mutex<Samples>,
+}
+
+pub(super) async fn run(load: Arc<Load>) -> Result<Samples, Error> {
+    let roads = load.options.clients.clamp(1, MAX_ROADS) as u16;
+    let tickets = Arc::new(Tickets::default());
+
+    // Tickets for roads without a dispatcher would only be queued
+    let mut dispatchers = JoinSet::new();
+    for road in 0..roads {
+        let stream = load.connect().await?;
+        dispatchers.spawn(dispatcher(stream, road, tickets.clone()));
+    }
+
+    let mut samples = load
+        .clients(load.options.clients, {
+            let tickets = tickets.clone();
+            move |id, load| cameras(id, road_of(id, roads), load, tickets.clone())
+        })
+        .await?;
+
+    let grace = Instant::now() + load.options.timeout;
+    while !tickets.outstanding.lock().unwrap().is_empty() && Instant::now() < grace {
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    dispatchers.abort_all();
+    while let Some(result) = dispatchers.join_next().await {
+        // Aborted dispatchers did not fail
+        if let Ok(result) = result {
+            tickets.samples.lock().unwrap().count_failure(result);
+        }
+    }
+
+    samples.merge(std::mem::take(&mut *tickets.samples.lock().unwrap()));
+    samples.errors += tickets.outstanding.lock().unwrap().len() as u64;
+    Ok(samples)
+}
+
+fn road_of(id: usize, roads: u16) -> u16 {
+    (id % roads as usize) as u16
+}
+
+fn camera(road: u16, mile: u16) -> Vec<u8> {
+    let mut message = vec![0x80];
+    for field in [road, mile, SPEED_LIMIT] {
+        message.extend(field.to_be_bytes());
+    }
+    message
+}
+
+fn plate(plate: &str, timestamp: u32) -> Vec<u8> {
+    let mut message = vec![0x20, plate.len() as u8];
+    message.extend(plate.as_bytes());
+    message.extend(timestamp.to_be_bytes());
+    message
+}
+
+async fn cameras(id: usize, road: u16, load: Arc<Load>, tickets: Arc<Tickets>) -> Samples {
+    let samples = Samples::default();
+    let result = async {
+        let mut first = load.connect().await?;
+        let mut second = load.connect().await?;
+        first.write_all(&camera(road, 0)).await?;
+        second.write_all(&camera(road, 10)).await?;
+
+        let mut car = 0_u32;
+        while load.running() {
+            car += 1;
+            // Ten miles in a minute, and a new plate every time so every car gets a ticket
+            let name = format!("C{id}N{car}");
+            let timestamp = car.wrapping_mul(1000);
+            first.write_all(&plate(&name, timestamp)).await?;
+            tickets
+                .outstanding
+                .lock()
+                .unwrap()
+                .insert(name.clone(), Instant::now());
+            second.write_all(&plate(&name, timestamp + 60)).await?;
+            load.pause().await;
+        }
+        Ok(())
+    }
+    .await;
+    samples.finish(result)
+}
+
+/// Reads tickets until it is aborted
+async fn dispatcher(mut stream: TcpStream, road: u16, tickets: Arc<Tickets>) -> Result<(), Error> {
+    let mut message = vec![0x81, 1];
+    message.extend(road.to_be_bytes());
+    stream.write_all(&message).await?;
+
+    let mut reader = BufReader::new(stream);
+    loop {
+        match reader.read_u8().await? {
+            0x21 => {
+                let plate = read_str(&mut reader).await?;
+                // road, mile1, timestamp1, mile2, timestamp2 and speed
+                reader.read_exact(&mut [0_u8; 16]).await?;
+
+                let sent = tickets.outstanding.lock().unwrap().remove(&plate);
+                match sent {
+                    Some(sent) => tickets.samples.lock().unwrap().record(sent),
+                    None => return Err(format!("unexpected ticket for {plate}").into()),
+                }
+            }
+            0x10 => return Err(format!("error: {}", read_str(&mut reader).await?).into()),
+            other => return Err(format!("unexpected message type {other:#x}").into()),
+        }
+    }
+}
+
+async fn read_str<R: AsyncRead + Unpin>(reader: &mut R) -> Result<String, Error> {
+    let len = reader.read_u8().await?;
+    let mut bytes = vec![0_u8; len as usize];
+    reader.read_exact(&mut bytes).await?;
+    Ok(String::from_utf8(bytes)?)
+}
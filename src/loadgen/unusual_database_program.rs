@@ -0,0 +1,58 @@
+//! Every client inserts the next value for its own key and retrieves it again.
+//! A sample is the time from sending the retrieve request to receiving the value.
+//! A value that does not arrive within the timeout counts as an error, the client goes on.
+
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::net::UdpSocket;
+use tokio::time::Instant;
+use tracing::debug;
+
+use super::{Load, Samples};
+use crate::udp::MAX_DATAGRAM_SIZE;
+use crate::Error;
+
+pub(super) async fn run(load: Arc<Load>) -> Result<Samples, Error> {
+    load.clients(load.options.clients, client).await
+}
+
+async fn client(id: usize, load: Arc<Load>) -> Samples {
+    let mut samples = Samples::default();
+    let result = async {
+        let bind_addr: SocketAddr = match load.options.target {
+            SocketAddr::V4(_) => "0.0.0.0:0".parse().unwrap(),
+            SocketAddr::V6(_) => "[::]:0".parse().unwrap(),
+        };
+        let socket = UdpSocket::bind(bind_addr).await?;
+        socket.connect(load.options.target).await?;
+        let mut buffer = vec![0_u8; MAX_DATAGRAM_SIZE];
+
+        let key = format!("key{id}");
+        let mut value = 0_u64;
+        while load.running() {
+            value += 1;
+            let expected = format!("{key}={value}");
+            socket.send(expected.as_bytes()).await?;
+
+            let sent = Instant::now();
+            socket.send(key.as_bytes()).await?;
+            // The answer to a retrieve that timed out may still arrive, it does not match
+            match load.within(socket.recv(&mut buffer)).await {
+                Ok(len) if buffer[..len] == *expected.as_bytes() => samples.record(sent),
+                Ok(len) => {
+                    debug!(answer = %String::from_utf8_lossy(&buffer[..len]), %expected, "wrong answer");
+                    samples.errors += 1;
+                }
+                Err(err) => {
+                    debug!(error = %err, "no answer");
+                    samples.errors += 1;
+                }
+            }
+            load.pause().await;
+        }
+        Ok(())
+    }
+    .await;
+    samples.finish(result)
+}
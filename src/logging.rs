@@ -0,0 +1,49 @@
+//! Logging setup shared by every server binary.
+//!
+//! Connections handled by [`serve`](crate::serve) get a `connection` span with their `id` and
+//! `addr`, and every server emits the same events: `connect`, `rejected`, `handler failed` and
+//! `disconnect`.
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use tracing_subscriber::prelude::__tracing_subscriber_SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+use crate::{Config, Error};
+
+/// Used if neither the config nor `RUST_LOG` set a filter
+pub const DEFAULT_FILTER: &str = "info";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable, one line per event
+    #[default]
+    Compact,
+    /// One JSON object per line, including the fields of all active spans
+    Json,
+}
+
+/// Installs the global subscriber. Fails if one is installed already.
+pub fn init(config: &Config) -> Result<(), Error> {
+    let filter = EnvFilter::try_new(config.log_filter(DEFAULT_FILTER))?;
+
+    match config.log_format {
+        LogFormat::Compact => tracing_subscriber::registry()
+            .with(filter)
+            .with(fmt::layer().compact())
+            .try_init()?,
+        LogFormat::Json => tracing_subscriber::registry()
+            .with(filter)
+            .with(
+                fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true),
+            )
+            .try_init()?,
+    }
+
+    Ok(())
+}
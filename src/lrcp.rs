@@ -0,0 +1,600 @@
+//! Line Reversal Control Protocol: reliable, ordered byte streams over UDP.
+//!
+//! A [`Listener`] owns a single [`UdpSocket`] and demultiplexes incoming packets
+//! into sessions. Every session is driven by its own task that handles acks,
+//! retransmissions and expiry. Application code gets a [`Session`] whose `stream`
+//! behaves like the `stream` of a [`Connection`](crate::Connection).
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt, DuplexStream, ReadHalf, WriteHalf};
+use tokio::net::{ToSocketAddrs, UdpSocket};
+use tokio::select;
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tokio::time::Instant;
+use tracing::{debug, error, info, info_span, warn, Instrument};
+
+use crate::limit::Reservation;
+use crate::shutdown::{self, Handlers};
+use crate::stats::{CloseReason, ServerStats};
+use crate::{log_totals, metrics, Config, Error, ShutdownSummary};
+
+/// Packets have to be smaller than this
+pub const MAX_PACKET_SIZE: usize = 1000;
+/// Numeric fields have to be smaller than this
+pub const MAX_NUMBER: u32 = 2_147_483_648;
+
+pub const RETRANSMISSION_TIMEOUT: Duration = Duration::from_secs(3);
+pub const SESSION_EXPIRY_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Upper bound for the escaped payload of a single `/data/` message,
+/// leaving enough room for the header.
+pub const MAX_DATA_SIZE: usize = MAX_PACKET_SIZE - 64;
+/// Size of the in-memory pipe between a session task and the application
+const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+/// Received data the application has not taken yet. New data is not acknowledged
+/// beyond this, the peer has to send it again later.
+const MAX_UNREAD: usize = 64 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Connect {
+        session: u32,
+    },
+    Data {
+        session: u32,
+        pos: u32,
+        data: Vec<u8>,
+    },
+    Ack {
+        session: u32,
+        length: u32,
+    },
+    Close {
+        session: u32,
+    },
+}
+
+impl Message {
+    /// Parses a packet. Returns `None` for anything that is not a valid LRCP message,
+    /// such packets must be silently ignored.
+    pub fn from_bytes(packet: &[u8]) -> Option<Self> {
+        if packet.len() >= MAX_PACKET_SIZE {
+            return None;
+        }
+
+        let fields = split_fields(packet)?;
+        let number = |field: &[u8]| -> Option<u32> {
+            if field.is_empty() || !field.iter().all(u8::is_ascii_digit) {
+                return None;
+            }
+            std::str::from_utf8(field)
+                .ok()?
+                .parse::<u32>()
+                .ok()
+                .filter(|&number| number < MAX_NUMBER)
+        };
+
+        let message = match fields.as_slice() {
+            [b"connect", session] => Self::Connect {
+                session: number(session)?,
+            },
+            [b"data", session, pos, data] => Self::Data {
+                session: number(session)?,
+                pos: number(pos)?,
+                data: unescape(data)?,
+            },
+            [b"ack", session, length] => Self::Ack {
+                session: number(session)?,
+                length: number(length)?,
+            },
+            [b"close", session] => Self::Close {
+                session: number(session)?,
+            },
+            _ => return None,
+        };
+
+        Some(message)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::Connect { session } => format!("/connect/{session}/").into_bytes(),
+            Self::Data { session, pos, data } => {
+                let mut bytes = format!("/data/{session}/{pos}/").into_bytes();
+                bytes.extend(escape(data));
+                bytes.push(b'/');
+                bytes
+            }
+            Self::Ack { session, length } => format!("/ack/{session}/{length}/").into_bytes(),
+            Self::Close { session } => format!("/close/{session}/").into_bytes(),
+        }
+    }
+
+    pub fn session(&self) -> u32 {
+        match self {
+            Self::Connect { session }
+            | Self::Data { session, .. }
+            | Self::Ack { session, .. }
+            | Self::Close { session } => *session,
+        }
+    }
+}
+
+/// Splits a packet at every unescaped `/`. The packet has to start and end with a `/`.
+/// Escape sequences are kept as they are.
+fn split_fields(packet: &[u8]) -> Option<Vec<&[u8]>> {
+    let inner = packet.strip_prefix(b"/")?.strip_suffix(b"/")?;
+
+    let mut fields = Vec::with_capacity(4);
+    let mut start = 0;
+    let mut escaped = false;
+
+    for (idx, &byte) in inner.iter().enumerate() {
+        match (escaped, byte) {
+            (true, _) => escaped = false,
+            (false, b'\\') => escaped = true,
+            (false, b'/') => {
+                fields.push(&inner[start..idx]);
+                start = idx + 1;
+            }
+            _ => {}
+        }
+    }
+
+    // A trailing backslash would have escaped the closing slash
+    if escaped {
+        return None;
+    }
+    fields.push(&inner[start..]);
+
+    Some(fields)
+}
+
+pub fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for &byte in data {
+        if byte == b'\\' || byte == b'/' {
+            escaped.push(b'\\');
+        }
+        escaped.push(byte);
+    }
+    escaped
+}
+
+/// Returns `None` if the data contains an invalid escape sequence
+pub fn unescape(data: &[u8]) -> Option<Vec<u8>> {
+    let mut unescaped = Vec::with_capacity(data.len());
+    let mut bytes = data.iter();
+
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'\\' => match bytes.next() {
+                Some(&escaped @ (b'\\' | b'/')) => unescaped.push(escaped),
+                _ => return None,
+            },
+            b'/' => return None,
+            _ => unescaped.push(byte),
+        }
+    }
+
+    Some(unescaped)
+}
+
+/// Splits data into chunks whose escaped form fits into a single `/data/` message
+pub fn chunks(data: &[u8]) -> impl Iterator<Item = &[u8]> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+
+        let mut escaped_len = 0;
+        let mut len = 0;
+        for &byte in rest {
+            let byte_len = if byte == b'\\' || byte == b'/' { 2 } else { 1 };
+            if escaped_len + byte_len > MAX_DATA_SIZE {
+                break;
+            }
+            escaped_len += byte_len;
+            len += 1;
+        }
+
+        let (chunk, remaining) = rest.split_at(len);
+        rest = remaining;
+        Some(chunk)
+    })
+}
+
+/// An open LRCP session
+pub struct Session {
+    /// Ordered byte stream. Reading yields the data sent by the peer,
+    /// writing sends data to the peer. Dropping it closes the session
+    /// once everything has been acknowledged.
+    pub stream: DuplexStream,
+    pub addr: SocketAddr,
+    pub id: u32,
+}
+
+pub struct Listener {
+    local_addr: SocketAddr,
+    incoming: mpsc::Receiver<Session>,
+}
+
+impl Listener {
+    pub async fn bind<A: ToSocketAddrs>(addr: A) -> Result<Self, Error> {
+        let socket = Arc::new(UdpSocket::bind(addr).await?);
+        let local_addr = socket.local_addr()?;
+        let (incoming_tx, incoming) = mpsc::channel(128);
+
+        tokio::spawn(async move {
+            if let Err(err) = demultiplex(socket, incoming_tx).await {
+                error!("LRCP socket failed: {err}");
+            }
+        });
+
+        Ok(Self {
+            local_addr,
+            incoming,
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Waits for a peer to open a new session
+    pub async fn accept(&mut self) -> Option<Session> {
+        self.incoming.recv().await
+    }
+}
+
+/// The LRCP counterpart to [`serve`](crate::serve)
+pub async fn serve<H, Fut>(config: &Config, handler: H) -> Result<ShutdownSummary, Error>
+where
+    H: FnOnce(Session) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<(), Error>> + Send,
+{
+    serve_until(config, handler, shutdown::signal()).await
+}
+
+/// The LRCP counterpart to [`serve_until`](crate::serve_until)
+pub async fn serve_until<H, Fut, S>(
+    config: &Config,
+    handler: H,
+    shutdown: S,
+) -> Result<ShutdownSummary, Error>
+where
+    H: FnOnce(Session) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<(), Error>> + Send,
+    S: Future<Output = ()>,
+{
+    let mut listener = Listener::bind(config.listen_addr().await?).await?;
+    info!(addr = %listener.local_addr(), "listening (LRCP)");
+    metrics::start(config).await?;
+    // Only counts sessions, the bytes in a session are not counted
+    let server_stats = ServerStats::register(format!("lrcp://{}", listener.local_addr()));
+
+    let mut handlers = Handlers::new();
+    let limit = config.connection_limit();
+    tokio::pin!(shutdown);
+
+    loop {
+        let session = select! {
+            session = listener.accept() => match session {
+                Some(session) => session,
+                None => break,
+            },
+            _ = &mut shutdown => break,
+        };
+
+        // There is nothing to pause on a shared socket, sessions always
+        // wait for their slot after being opened.
+        let (id, addr) = (session.id, session.addr);
+        let limit = limit.clone();
+        let handler = handler.clone();
+        let stats = server_stats.connection();
+        let span = info_span!("connection", id, %addr);
+        let currently_running = handlers.spawn(
+            async move {
+                // Rejected sessions are closed by dropping their stream
+                let _permit = match limit.admit(Reservation::default(), addr.ip()).await {
+                    Ok(permit) => permit,
+                    Err(rejected) => {
+                        warn!(%rejected, "rejected");
+                        stats.close(CloseReason::Rejected);
+                        return;
+                    }
+                };
+                let reason = match handler(session).await {
+                    Ok(()) => CloseReason::Closed,
+                    Err(err) => {
+                        error!(error = %err, "handler failed");
+                        CloseReason::Failed
+                    }
+                };
+                stats.close(reason);
+            }
+            .instrument(span.clone()),
+        );
+        span.in_scope(|| info!(running = currently_running, "connect"));
+    }
+
+    info!("stopped accepting LRCP sessions");
+    let summary = handlers
+        .drain(Duration::from_secs(config.shutdown_timeout))
+        .await;
+    info!(
+        drained = summary.drained,
+        aborted = summary.aborted,
+        "shutdown"
+    );
+    log_totals(&server_stats);
+
+    Ok(summary)
+}
+
+/// Reads packets from the socket and hands them to the session they belong to.
+/// Sessions are told apart by their id and the peer address, so nobody can send
+/// packets into someone else's session by guessing its id.
+async fn demultiplex(socket: Arc<UdpSocket>, incoming: mpsc::Sender<Session>) -> Result<(), Error> {
+    let mut sessions = HashMap::<(u32, SocketAddr), mpsc::Sender<Message>>::new();
+    let (closed_tx, mut closed_rx) = mpsc::unbounded_channel::<(u32, SocketAddr)>();
+    let mut buffer = [0_u8; MAX_PACKET_SIZE];
+
+    loop {
+        let (bytes_read, addr) = select! {
+            received = socket.recv_from(&mut buffer) => received?,
+            Some(key) = closed_rx.recv() => {
+                // The session may have been re-opened in the meantime
+                if sessions.get(&key).is_some_and(|tx| tx.is_closed()) {
+                    sessions.remove(&key);
+                }
+                continue;
+            }
+        };
+
+        let Some(message) = Message::from_bytes(&buffer[..bytes_read]) else {
+            debug!(
+                "Ignoring invalid packet from {addr}: {:?}",
+                String::from_utf8_lossy(&buffer[..bytes_read])
+            );
+            continue;
+        };
+        debug!("<-- {addr} {message:?}");
+
+        let session = message.session();
+        if let Some(tx) = sessions.get(&(session, addr)) {
+            // A session that does not keep up must not hold up the others,
+            // the peer sends the packet again if it matters
+            match tx.try_send(message) {
+                Ok(()) => continue,
+                Err(TrySendError::Full(message)) => {
+                    debug!("Session {session} is busy, dropping {message:?}");
+                    continue;
+                }
+                Err(TrySendError::Closed(_)) => {}
+            }
+            sessions.remove(&(session, addr));
+            socket
+                .send_to(&Message::Close { session }.to_bytes(), addr)
+                .await?;
+            continue;
+        }
+
+        match message {
+            Message::Connect { session } => {
+                let (app_stream, session_stream) = tokio::io::duplex(STREAM_BUFFER_SIZE);
+                let (tx, rx) = mpsc::channel(128);
+                sessions.insert((session, addr), tx);
+
+                let (reader, writer) = tokio::io::split(session_stream);
+                let task = SessionTask {
+                    id: session,
+                    addr,
+                    socket: socket.clone(),
+                    reader,
+                    writer,
+                    unread: Vec::new(),
+                    received: 0,
+                    sent: Vec::new(),
+                    acked: 0,
+                    last_progress: Instant::now(),
+                    last_heard: Instant::now(),
+                };
+                let closed_tx = closed_tx.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = task.run(rx).await {
+                        warn!("Session {session} failed: {err}");
+                    }
+                    let _ = closed_tx.send((session, addr));
+                });
+
+                socket
+                    .send_to(&Message::Ack { session, length: 0 }.to_bytes(), addr)
+                    .await?;
+
+                let session = Session {
+                    stream: app_stream,
+                    addr,
+                    id: session,
+                };
+                if incoming.send(session).await.is_err() {
+                    // Nobody is accepting sessions anymore
+                    return Ok(());
+                }
+            }
+            // Anything but a connect for an unknown session gets closed
+            _ => {
+                socket
+                    .send_to(&Message::Close { session }.to_bytes(), addr)
+                    .await?;
+            }
+        }
+    }
+}
+
+struct SessionTask {
+    id: u32,
+    addr: SocketAddr,
+    socket: Arc<UdpSocket>,
+    /// Our end of the pipe to the application
+    reader: ReadHalf<DuplexStream>,
+    writer: WriteHalf<DuplexStream>,
+    /// Received data that still has to go through the pipe
+    unread: Vec<u8>,
+    /// Number of bytes received from the peer, in order
+    received: u32,
+    /// Everything the application has written so far
+    sent: Vec<u8>,
+    /// Number of bytes the peer has acknowledged
+    acked: u32,
+    /// Last time the peer acknowledged new data
+    last_progress: Instant,
+    /// Last time the peer sent anything
+    last_heard: Instant,
+}
+
+impl SessionTask {
+    async fn run(mut self, mut packets: mpsc::Receiver<Message>) -> Result<(), Error> {
+        // The first tick of `interval` completes right away and would resend the first data
+        let mut retransmission = tokio::time::interval_at(
+            Instant::now() + RETRANSMISSION_TIMEOUT,
+            RETRANSMISSION_TIMEOUT,
+        );
+        let mut buffer = vec![0_u8; MAX_DATA_SIZE];
+        let mut application_done = false;
+
+        loop {
+            select! {
+                packet = packets.recv() => {
+                    let Some(packet) = packet else { break };
+                    self.last_heard = Instant::now();
+                    if !self.handle(packet).await? {
+                        break;
+                    }
+                }
+                bytes_read = self.reader.read(&mut buffer), if !application_done => {
+                    let bytes_read = bytes_read?;
+                    if bytes_read == 0 {
+                        application_done = true;
+                    } else {
+                        self.transmit(&buffer[..bytes_read]).await?;
+                    }
+                }
+                // An application that does not read must not hold up acks and retransmissions
+                bytes_written = self.writer.write(&self.unread), if !self.unread.is_empty() => {
+                    self.unread.drain(..bytes_written?);
+                }
+                _ = retransmission.tick() => {
+                    // Either the peer is gone or it ignores what we send
+                    let stalled = self.outstanding()
+                        && self.last_progress.elapsed() >= SESSION_EXPIRY_TIMEOUT;
+                    if stalled || self.last_heard.elapsed() >= SESSION_EXPIRY_TIMEOUT {
+                        info!("Session {} expired", self.id);
+                        break;
+                    }
+                    if self.outstanding() {
+                        self.retransmit().await?;
+                    }
+                }
+            }
+
+            if application_done && !self.outstanding() {
+                break;
+            }
+        }
+
+        self.send(Message::Close { session: self.id }).await
+    }
+
+    /// Returns `false` once the session should be closed
+    async fn handle(&mut self, packet: Message) -> Result<bool, Error> {
+        match packet {
+            Message::Connect { session } => {
+                // The first ack may have been lost
+                self.send(Message::Ack { session, length: 0 }).await?;
+            }
+            Message::Data { session, pos, data } => {
+                // Retransmissions may overlap with what we already have, only the tail is new
+                let end = pos as usize + data.len();
+                if pos <= self.received && end > self.received as usize {
+                    if end >= MAX_NUMBER as usize {
+                        warn!("Session {session} exceeded the maximum stream length");
+                        return Ok(false);
+                    }
+                    if self.unread.len() < MAX_UNREAD {
+                        let new = &data[(self.received - pos) as usize..];
+                        self.unread.extend_from_slice(new);
+                        self.received += new.len() as u32;
+                    }
+                }
+                self.send(Message::Ack {
+                    session,
+                    length: self.received,
+                })
+                .await?;
+            }
+            Message::Ack { session, length } => {
+                if length <= self.acked {
+                    return Ok(true);
+                }
+                if length as usize > self.sent.len() {
+                    warn!("Session {session} acknowledged data we never sent");
+                    return Ok(false);
+                }
+
+                self.acked = length;
+                self.last_progress = Instant::now();
+                if self.outstanding() {
+                    self.retransmit().await?;
+                }
+            }
+            Message::Close { .. } => return Ok(false),
+        }
+
+        Ok(true)
+    }
+
+    fn outstanding(&self) -> bool {
+        (self.acked as usize) < self.sent.len()
+    }
+
+    async fn transmit(&mut self, data: &[u8]) -> Result<(), Error> {
+        if !self.outstanding() {
+            self.last_progress = Instant::now();
+        }
+
+        let start = self.sent.len();
+        self.sent.extend_from_slice(data);
+        self.send_from(start).await
+    }
+
+    async fn retransmit(&self) -> Result<(), Error> {
+        self.send_from(self.acked as usize).await
+    }
+
+    async fn send_from(&self, start: usize) -> Result<(), Error> {
+        let mut pos = start;
+        for chunk in chunks(&self.sent[start..]) {
+            self.send(Message::Data {
+                session: self.id,
+                pos: pos as u32,
+                data: chunk.to_vec(),
+            })
+            .await?;
+            pos += chunk.len();
+        }
+        Ok(())
+    }
+
+    async fn send(&self, message: Message) -> Result<(), Error> {
+        debug!("--> {} {message:?}", self.addr);
+        self.socket.send_to(&message.to_bytes(), self.addr).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,222 @@
+//! An HTTP listener that serves the [`stats`](crate::stats) of every server in this process
+//! in the Prometheus text format.
+//!
+//! It is started by the first server of the process if `metrics_port` is configured and
+//! answers `GET /metrics` on the same address the servers bind to:
+//!
+//! ```text
+//! # HELP protohackers_connections_active Connections that are currently open
+//! # TYPE protohackers_connections_active gauge
+//! protohackers_connections_active{server="tcp://[::]:5555"} 3
+//! ```
+//!
+//! Protocol specific counters, see [`ConnectionStats::count`](crate::stats::ConnectionStats::count),
+//! are exposed as `protohackers_<name>_total`.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+use std::sync::Mutex;
+
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tracing::{debug, info, warn};
+
+use crate::stats::{self, Snapshot};
+use crate::{Config, Error};
+
+/// Requests with a longer head are refused
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+static STARTED: Mutex<bool> = Mutex::new(false);
+
+/// Binds the metrics listener if `metrics_port` is configured and it is not running yet
+pub async fn start(config: &Config) -> Result<(), Error> {
+    let Some(port) = config.metrics_port else {
+        return Ok(());
+    };
+    if *STARTED.lock().unwrap() {
+        return Ok(());
+    }
+
+    let addr = Config {
+        port,
+        ..config.clone()
+    }
+    .listen_addr()
+    .await?;
+    let listener = TcpListener::bind(addr)
+        .await
+        .map_err(|err| format!("could not bind the metrics listener to {addr}: {err}"))?;
+
+    // Another server may have been faster while this one was binding
+    let mut started = STARTED.lock().unwrap();
+    if *started {
+        return Ok(());
+    }
+    *started = true;
+    info!(%addr, "serving metrics");
+
+    tokio::spawn(async move {
+        loop {
+            let (stream, addr) = match listener.accept().await {
+                Ok(accepted) => accepted,
+                Err(err) => {
+                    warn!(error = %err, "metrics listener failed");
+                    break;
+                }
+            };
+            tokio::spawn(async move {
+                if let Err(err) = respond(stream).await {
+                    debug!(%addr, error = %err, "metrics request failed");
+                }
+            });
+        }
+    });
+
+    Ok(())
+}
+
+async fn respond(mut stream: TcpStream) -> Result<(), Error> {
+    let mut request = Vec::with_capacity(1024);
+    let mut buffer = [0_u8; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        if request.len() > MAX_REQUEST_SIZE {
+            return Err("request head is too long".into());
+        }
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            return Err("connection closed before the end of the request".into());
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request_line = request.split(|&byte| byte == b'\r').next().unwrap_or(&[]);
+    let mut parts = request_line.split(|&byte| byte == b' ');
+    let (status, body) = match (parts.next(), parts.next()) {
+        (Some(b"GET"), Some(b"/metrics")) => ("200 OK", render(&stats::snapshot())),
+        (Some(b"GET"), _) => ("404 Not Found", "not found\n".to_string()),
+        _ => (
+            "405 Method Not Allowed",
+            "only GET is supported\n".to_string(),
+        ),
+    };
+
+    let response = format!(
+        "HTTP/1.1 {status}\r\n\
+         Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n\
+         Content-Length: {}\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {body}",
+        body.len()
+    );
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Formats the snapshots in the Prometheus text format
+pub fn render(snapshots: &[Snapshot]) -> String {
+    let mut out = String::new();
+
+    let mut metric = |name: &str, kind: &str, help: &str, value: &dyn Fn(&Snapshot) -> u64| {
+        writeln!(out, "# HELP protohackers_{name} {help}").unwrap();
+        writeln!(out, "# TYPE protohackers_{name} {kind}").unwrap();
+        for snapshot in snapshots {
+            let server = escape(&snapshot.name);
+            let value = value(snapshot);
+            writeln!(out, "protohackers_{name}{{server=\"{server}\"}} {value}").unwrap();
+        }
+    };
+
+    metric(
+        "uptime_seconds",
+        "gauge",
+        "Seconds since the server started",
+        &|snapshot| snapshot.uptime.as_secs(),
+    );
+    metric(
+        "connections_active",
+        "gauge",
+        "Connections that are currently open",
+        &|snapshot| snapshot.active,
+    );
+    metric(
+        "connections_accepted_total",
+        "counter",
+        "Connections accepted since the server started",
+        &|snapshot| snapshot.accepted,
+    );
+    metric(
+        "bytes_received_total",
+        "counter",
+        "Bytes read from all connections",
+        &|snapshot| snapshot.bytes_in,
+    );
+    metric(
+        "bytes_sent_total",
+        "counter",
+        "Bytes written to all connections",
+        &|snapshot| snapshot.bytes_out,
+    );
+    metric(
+        "messages_received_total",
+        "counter",
+        "Protocol messages received from all connections",
+        &|snapshot| snapshot.messages_in,
+    );
+    metric(
+        "messages_sent_total",
+        "counter",
+        "Protocol messages sent to all connections",
+        &|snapshot| snapshot.messages_out,
+    );
+
+    let name = "protohackers_connections_closed_total";
+    writeln!(
+        out,
+        "# HELP {name} Connections that ended, by reason. `failed` means the handler returned an error."
+    )
+    .unwrap();
+    writeln!(out, "# TYPE {name} counter").unwrap();
+    for snapshot in snapshots {
+        let server = escape(&snapshot.name);
+        for (reason, value) in [
+            ("closed", snapshot.closed),
+            ("rejected", snapshot.rejected),
+            ("timed_out", snapshot.timed_out),
+            ("failed", snapshot.failed),
+            ("aborted", snapshot.aborted),
+        ] {
+            writeln!(
+                out,
+                "{name}{{server=\"{server}\",reason=\"{reason}\"}} {value}"
+            )
+            .unwrap();
+        }
+    }
+
+    let counters: BTreeSet<&str> = snapshots
+        .iter()
+        .flat_map(|snapshot| snapshot.counters.keys().copied())
+        .collect();
+    for counter in counters {
+        let name = format!("protohackers_{counter}_total");
+        writeln!(out, "# TYPE {name} counter").unwrap();
+        for snapshot in snapshots {
+            if let Some(value) = snapshot.counters.get(counter) {
+                let server = escape(&snapshot.name);
+                writeln!(out, "{name}{{server=\"{server}\"}} {value}").unwrap();
+            }
+        }
+    }
+
+    out
+}
+
+fn escape(label: &str) -> String {
+    label
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
@@ -0,0 +1,224 @@
+//! [Problem 3: Budget Chat](https://protohackers.com/problem/3), a line based chat room.
+
+use std::collections::HashSet;
+use std::future::Future;
+
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::select;
+use tokio::sync::{broadcast, mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::warn;
+
+use crate::codec::{FrameError, FrameReader, Framing};
+use crate::{serve_until, Config, Connection, Error, ShutdownSummary};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Username(String);
+impl Username {
+    pub fn get(&self) -> &str {
+        self.0.as_str()
+    }
+    pub fn new(name: &str) -> Result<Self, &'static str> {
+        if name.is_empty() {
+            return Err("name empty");
+        }
+        if name.chars().any(|ch| !ch.is_alphanumeric()) {
+            return Err("name contains non-alphanumeric characters");
+        }
+
+        Ok(Username(name.to_string()))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Content(String);
+impl Content {
+    pub fn get(&self) -> &str {
+        self.0.as_str()
+    }
+    pub fn new(content: &str) -> Self {
+        Content(content.to_string())
+    }
+}
+
+#[derive(Debug)]
+enum IncomingEvent {
+    Join(Username, oneshot::Sender<String>),
+    Part(Username),
+    Message(Username, Content),
+}
+
+#[derive(Debug, Clone)]
+enum OutgoingEvent {
+    Join(Username),
+    Part(Username),
+    Message(Username, Content),
+}
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    let (incoming_event_tx, mut incoming_event_rx) = mpsc::channel::<IncomingEvent>(128);
+    let (outgoing_event_tx, _) = broadcast::channel::<OutgoingEvent>(128);
+    let outgoing_event_tx_manager = outgoing_event_tx.clone();
+
+    let _manager: JoinHandle<Result<(), Error>> = tokio::spawn(async move {
+        let mut usernames = HashSet::<String>::new();
+
+        while let Some(event) = incoming_event_rx.recv().await {
+            match event {
+                IncomingEvent::Join(username, reply) => {
+                    let current_users: Vec<String> = usernames.iter().cloned().collect();
+                    let current_users = current_users.join(", ");
+                    reply.send(current_users).unwrap();
+
+                    usernames.insert(username.get().to_string());
+                    let _ = outgoing_event_tx_manager.send(OutgoingEvent::Join(username));
+                }
+                IncomingEvent::Part(username) => {
+                    usernames.remove(username.get());
+                    let _ = outgoing_event_tx_manager.send(OutgoingEvent::Part(username));
+                }
+                IncomingEvent::Message(username, content) => {
+                    let _ =
+                        outgoing_event_tx_manager.send(OutgoingEvent::Message(username, content));
+                }
+            };
+        }
+        Ok(())
+    });
+
+    let framing = Framing::Line {
+        max_len: config.max_line_length,
+    };
+    serve_until(
+        config,
+        move |connection| {
+            let outgoing_event_rx = outgoing_event_tx.subscribe();
+            handle_client(connection, framing, incoming_event_tx, outgoing_event_rx)
+        },
+        shutdown,
+    )
+    .await
+}
+
+async fn handle_client(
+    mut connection: Connection,
+    framing: Framing,
+    incoming_event_tx: mpsc::Sender<IncomingEvent>,
+    mut outgoing_event_rx: broadcast::Receiver<OutgoingEvent>,
+) -> Result<(), Error> {
+    let stats = connection.stats();
+    let (reader, writer) = connection.stream.split();
+    let (mut reader, mut writer) = (FrameReader::new(reader, framing), BufWriter::new(writer));
+
+    let username = {
+        writer.write_all(b"name?\n").await.unwrap();
+        writer.flush().await.unwrap();
+        stats.message_out();
+
+        let username = match reader.read_string().await {
+            Ok(Some(username)) => username,
+            Ok(None) | Err(FrameError::Truncated { .. }) => return Ok(()),
+            Err(FrameError::TooLong { max, .. }) => {
+                stats.count("lines_too_long", 1);
+                let message = format!("* name longer than {max} bytes, disconnecting\n");
+                writer.write_all(message.as_bytes()).await?;
+                writer.flush().await?;
+                stats.message_out();
+                return Ok(());
+            }
+            Err(err) => return Err(err.into()),
+        };
+        stats.message_in();
+        let username = match Username::new(username.trim()) {
+            Ok(username) => username,
+            Err(err) => {
+                warn!(error = err, "invalid name");
+                return Ok(());
+            }
+        };
+
+        let (user_list_sender, user_list_reply) = oneshot::channel();
+
+        incoming_event_tx
+            .send(IncomingEvent::Join(username.clone(), user_list_sender))
+            .await?;
+        let user_list = user_list_reply.await?;
+
+        let message = format!("* LIST: {user_list}\n");
+        let _ = writer.write_all(message.as_bytes()).await;
+        let _ = writer.flush().await;
+        stats.message_out();
+
+        username
+    };
+
+    outgoing_event_rx = outgoing_event_rx.resubscribe();
+
+    loop {
+        select! {
+            maybe_incoming = reader.read_string() => match maybe_incoming {
+                Ok(Some(incoming)) => {
+                    stats.message_in();
+                    let event = IncomingEvent::Message(username.clone(), Content::new(&incoming));
+                    incoming_event_tx.send(event).await?;
+                    stats.count("chat_messages_broadcast", 1);
+                }
+                Ok(None) | Err(FrameError::Truncated { .. }) => break,
+                // Everyone else sees the user leave
+                Err(FrameError::TooLong { max, .. }) => {
+                    stats.count("lines_too_long", 1);
+                    let message = format!("* message longer than {max} bytes, disconnecting\n");
+                    let _ = writer.write_all(message.as_bytes()).await;
+                    let _ = writer.flush().await;
+                    stats.message_out();
+                    break;
+                }
+                Err(err) => {
+                    warn!(error = %err, "could not read message");
+                    break;
+                }
+            },
+
+            Ok(outgoing_event) = outgoing_event_rx.recv() => {
+                match outgoing_event {
+                    OutgoingEvent::Join(author) => {
+                        if author == username {
+                            continue
+                        }
+                        let message = format!("* JOIN: {}\n", author.get());
+                        let _ = writer.write_all(message.as_bytes()).await;
+                        let _ = writer.flush().await;
+                        stats.message_out();
+                    },
+                    OutgoingEvent::Part(author) => {
+                        if author == username {
+                            continue
+                        }
+                        let message = format!("* PART: {}\n", author.get());
+                        let _ = writer.write_all(message.as_bytes()).await;
+                        let _ = writer.flush().await;
+                        stats.message_out();
+                    },
+                    OutgoingEvent::Message(author, content) => {
+                        if author == username {
+                            continue
+                        }
+                        let message = format!("[{}] {}\n", author.get(), content.get());
+                        let _ = writer.write_all(message.as_bytes()).await;
+                        let _ = writer.flush().await;
+                        stats.message_out();
+                    },
+                };
+            }
+            else => {
+                break
+            }
+        }
+    }
+    let _ = incoming_event_tx.send(IncomingEvent::Part(username)).await;
+
+    Ok(())
+}
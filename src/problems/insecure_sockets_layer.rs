@@ -0,0 +1,60 @@
+//! [Problem 8: Insecure Sockets Layer](https://protohackers.com/problem/8), toy orders over an [obfuscated stream](crate::isl).
+
+use std::future::Future;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::{info, warn};
+
+use crate::{isl::CipherStream, serve_until, Config, Error, ShutdownSummary};
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    serve_until(
+        config,
+        |connection| async move {
+            let stats = connection.stats();
+            let stream = match CipherStream::accept(connection.stream).await {
+                Ok(stream) => stream,
+                Err(err) => {
+                    // Invalid or no-op cipher specs get disconnected right away
+                    warn!("{}: {err}", connection.addr);
+                    return Ok(());
+                }
+            };
+            info!("{}: {:?}", connection.addr, stream.spec());
+
+            let (reader, mut writer) = tokio::io::split(stream);
+            let mut lines = BufReader::new(reader).lines();
+
+            while let Some(line) = lines.next_line().await? {
+                stats.message_in();
+                let Some(toy) = most_copies(&line) else {
+                    warn!("{}: no toys in {line:?}", connection.addr);
+                    break;
+                };
+                info!("{line:?}  -->  {toy:?}");
+
+                writer.write_all(format!("{toy}\n").as_bytes()).await?;
+                writer.flush().await?;
+                stats.message_out();
+            }
+
+            Ok(())
+        },
+        shutdown,
+    )
+    .await
+}
+
+/// Picks the toy with the highest count from a line like `10x toy car,15x dog on a string`
+pub fn most_copies(line: &str) -> Option<&str> {
+    line.split(',')
+        .filter_map(|toy| {
+            let (count, _) = toy.split_once("x ")?;
+            Some((count.parse::<u64>().ok()?, toy))
+        })
+        .max_by_key(|(count, _)| *count)
+        .map(|(_, toy)| toy)
+}
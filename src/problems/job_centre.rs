@@ -0,0 +1,336 @@
+//! [Problem 9: Job Centre](https://protohackers.com/problem/9), priority job queues with JSON requests.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::future::Future;
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+use crate::{serve_until, Config, Connection, Error, ShutdownSummary};
+
+pub type ClientId = usize;
+pub type JobId = u64;
+
+#[derive(Deserialize, Debug)]
+#[serde(tag = "request", rename_all = "lowercase")]
+pub enum Request {
+    Put {
+        queue: String,
+        job: Map<String, Value>,
+        pri: u64,
+    },
+    Get {
+        queues: Vec<String>,
+        #[serde(default)]
+        wait: bool,
+    },
+    Delete {
+        id: JobId,
+    },
+    Abort {
+        id: JobId,
+    },
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct Job {
+    pub id: JobId,
+    pub job: Map<String, Value>,
+    pub pri: u64,
+    pub queue: String,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum Response {
+    #[serde(rename = "ok")]
+    Ok,
+    #[serde(rename = "ok")]
+    Created {
+        id: JobId,
+    },
+    #[serde(rename = "ok")]
+    Job(Job),
+    NoJob,
+    Error {
+        error: String,
+    },
+}
+
+#[derive(Debug)]
+enum IncomingEvent {
+    Put(String, Map<String, Value>, u64, oneshot::Sender<JobId>),
+    /// Replies with `None` right away if there is no job and the client does not want to wait
+    Get(ClientId, Vec<String>, bool, oneshot::Sender<Option<Job>>),
+    Delete(JobId, oneshot::Sender<bool>),
+    Abort(ClientId, JobId, oneshot::Sender<Result<bool, &'static str>>),
+    Disconnect(ClientId),
+}
+
+#[derive(Debug)]
+struct Waiter {
+    client: ClientId,
+    queues: Vec<String>,
+    reply: oneshot::Sender<Option<Job>>,
+}
+
+#[derive(Debug, Default)]
+struct JobCentre {
+    next_id: JobId,
+    jobs: HashMap<JobId, Job>,
+    /// Jobs and the client working on them
+    assigned: HashMap<JobId, ClientId>,
+    /// Highest priority first, oldest job first for equal priorities.
+    /// Entries of deleted or assigned jobs are skipped when popping.
+    queues: HashMap<String, BinaryHeap<(u64, Reverse<JobId>)>>,
+    waiters: Vec<Waiter>,
+}
+
+impl JobCentre {
+    fn put(&mut self, queue: String, job: Map<String, Value>, pri: u64) -> JobId {
+        let id = self.next_id;
+        self.next_id += 1;
+
+        self.jobs.insert(
+            id,
+            Job {
+                id,
+                job,
+                pri,
+                queue,
+            },
+        );
+        self.enqueue(id);
+        id
+    }
+
+    /// Makes the job available again, either to a waiting client or in its queue
+    fn enqueue(&mut self, id: JobId) {
+        let Some(job) = self.jobs.get(&id) else {
+            return;
+        };
+
+        self.waiters.retain(|waiter| !waiter.reply.is_closed());
+        if let Some(idx) = self
+            .waiters
+            .iter()
+            .position(|waiter| waiter.queues.contains(&job.queue))
+        {
+            let waiter = self.waiters.remove(idx);
+            if waiter.reply.send(Some(job.clone())).is_ok() {
+                self.assigned.insert(id, waiter.client);
+                return;
+            }
+        }
+
+        self.queues
+            .entry(job.queue.clone())
+            .or_default()
+            .push((job.pri, Reverse(id)));
+    }
+
+    fn get(&mut self, client: ClientId, queues: &[String]) -> Option<Job> {
+        loop {
+            // the best entry across all requested queues
+            let (queue, _) = queues
+                .iter()
+                .filter_map(|name| Some((name, self.queues.get(name)?.peek()?)))
+                .max_by_key(|(_, entry)| **entry)?;
+            let (_, Reverse(id)) = self.queues.get_mut(queue)?.pop()?;
+
+            if self.assigned.contains_key(&id) {
+                continue;
+            }
+            let Some(job) = self.jobs.get(&id) else {
+                continue;
+            };
+
+            self.assigned.insert(id, client);
+            return Some(job.clone());
+        }
+    }
+
+    fn delete(&mut self, id: JobId) -> bool {
+        self.assigned.remove(&id);
+        self.jobs.remove(&id).is_some()
+    }
+
+    fn abort(&mut self, client: ClientId, id: JobId) -> Result<bool, &'static str> {
+        if !self.jobs.contains_key(&id) {
+            return Ok(false);
+        }
+        if self.assigned.get(&id) != Some(&client) {
+            return Err("not working on this job");
+        }
+
+        self.assigned.remove(&id);
+        self.enqueue(id);
+        Ok(true)
+    }
+
+    fn disconnect(&mut self, client: ClientId) {
+        let working_on: Vec<JobId> = self
+            .assigned
+            .iter()
+            .filter(|(_, &other)| other == client)
+            .map(|(&id, _)| id)
+            .collect();
+
+        for id in working_on {
+            info!("Client {client} disconnected, re-queueing job {id}");
+            self.assigned.remove(&id);
+            self.enqueue(id);
+        }
+    }
+}
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    let (incoming_event_tx, mut incoming_event_rx) = mpsc::channel::<IncomingEvent>(128);
+
+    let _manager: JoinHandle<Result<(), Error>> = tokio::spawn(async move {
+        let mut centre = JobCentre::default();
+
+        while let Some(event) = incoming_event_rx.recv().await {
+            match event {
+                IncomingEvent::Put(queue, job, pri, reply) => {
+                    let _ = reply.send(centre.put(queue, job, pri));
+                }
+                IncomingEvent::Get(client, queues, wait, reply) => {
+                    match centre.get(client, &queues) {
+                        Some(job) => {
+                            let id = job.id;
+                            if reply.send(Some(job)).is_err() {
+                                // client is gone already
+                                centre.assigned.remove(&id);
+                                centre.enqueue(id);
+                            }
+                        }
+                        None if wait => centre.waiters.push(Waiter {
+                            client,
+                            queues,
+                            reply,
+                        }),
+                        None => {
+                            let _ = reply.send(None);
+                        }
+                    }
+                }
+                IncomingEvent::Delete(id, reply) => {
+                    let _ = reply.send(centre.delete(id));
+                }
+                IncomingEvent::Abort(client, id, reply) => {
+                    let _ = reply.send(centre.abort(client, id));
+                }
+                IncomingEvent::Disconnect(client) => centre.disconnect(client),
+            }
+        }
+        Ok(())
+    });
+
+    serve_until(
+        config,
+        move |connection| handle_client(connection, incoming_event_tx),
+        shutdown,
+    )
+    .await
+}
+
+async fn handle_client(
+    mut connection: Connection,
+    incoming_event_tx: mpsc::Sender<IncomingEvent>,
+) -> Result<(), Error> {
+    let client = connection.id;
+    let stats = connection.stats();
+    let (reader, writer) = connection.stream.split();
+    let (reader, mut writer) = (BufReader::new(reader), BufWriter::new(writer));
+    let mut lines = reader.lines();
+
+    let result = async {
+        while let Some(line) = lines.next_line().await? {
+            stats.message_in();
+            let response = match serde_json::from_str::<Request>(&line) {
+                Ok(request) => handle_request(client, request, &incoming_event_tx).await?,
+                Err(err) => {
+                    warn!(error = %err, "malformed request");
+                    Response::Error {
+                        error: err.to_string(),
+                    }
+                }
+            };
+
+            let mut response_bytes = serde_json::to_vec(&response)?;
+            response_bytes.push(b'\n');
+            writer.write_all(&response_bytes).await?;
+            writer.flush().await?;
+            stats.message_out();
+        }
+        Ok::<(), Error>(())
+    }
+    .await;
+
+    let _ = incoming_event_tx
+        .send(IncomingEvent::Disconnect(client))
+        .await;
+    result
+}
+
+async fn handle_request(
+    client: ClientId,
+    request: Request,
+    incoming_event_tx: &mpsc::Sender<IncomingEvent>,
+) -> Result<Response, Error> {
+    info!("[{client:>3}] {request:?}");
+
+    let response = match request {
+        Request::Put { queue, job, pri } => {
+            let (reply, id) = oneshot::channel();
+            incoming_event_tx
+                .send(IncomingEvent::Put(queue, job, pri, reply))
+                .await?;
+            Response::Created { id: id.await? }
+        }
+        Request::Get { queues, wait } => {
+            let (reply, job) = oneshot::channel();
+            incoming_event_tx
+                .send(IncomingEvent::Get(client, queues, wait, reply))
+                .await?;
+            match job.await? {
+                Some(job) => Response::Job(job),
+                None => Response::NoJob,
+            }
+        }
+        Request::Delete { id } => {
+            let (reply, deleted) = oneshot::channel();
+            incoming_event_tx
+                .send(IncomingEvent::Delete(id, reply))
+                .await?;
+            match deleted.await? {
+                true => Response::Ok,
+                false => Response::NoJob,
+            }
+        }
+        Request::Abort { id } => {
+            let (reply, aborted) = oneshot::channel();
+            incoming_event_tx
+                .send(IncomingEvent::Abort(client, id, reply))
+                .await?;
+            match aborted.await? {
+                Ok(true) => Response::Ok,
+                Ok(false) => Response::NoJob,
+                Err(error) => Response::Error {
+                    error: error.to_string(),
+                },
+            }
+        }
+    };
+
+    Ok(response)
+}
@@ -0,0 +1,33 @@
+//! [Problem 7: Line Reversal](https://protohackers.com/problem/7), reverses lines sent over [LRCP](crate::lrcp).
+
+use std::future::Future;
+
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tracing::info;
+
+use crate::{lrcp, Config, Error, ShutdownSummary};
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    lrcp::serve_until(
+        config,
+        |session| async move {
+            let (reader, mut writer) = tokio::io::split(session.stream);
+            let mut lines = BufReader::new(reader).lines();
+
+            while let Some(line) = lines.next_line().await? {
+                let reversed: String = line.chars().rev().collect();
+                info!("{line:?}  -->  {reversed:?}");
+
+                writer.write_all(format!("{reversed}\n").as_bytes()).await?;
+                writer.flush().await?;
+            }
+
+            Ok(())
+        },
+        shutdown,
+    )
+    .await
+}
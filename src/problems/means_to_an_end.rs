@@ -0,0 +1,132 @@
+//! [Problem 2: Means to an End](https://protohackers.com/problem/2), a binary protocol that inserts prices and queries their mean.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::num::NonZeroUsize;
+
+use nom::character::complete::one_of;
+use nom::combinator::eof;
+use nom::number::complete::be_i32;
+use nom::{Finish, IResult};
+use tracing::{debug, trace};
+
+use crate::codec::{FrameError, FrameReader, FrameWriter, Framing};
+use crate::{serve_until, Config, Error, ShutdownSummary};
+
+/// Type byte and two numbers
+const REQUEST_LEN: NonZeroUsize = NonZeroUsize::new(9).unwrap();
+/// The mean
+const RESPONSE_LEN: NonZeroUsize = NonZeroUsize::new(4).unwrap();
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    serve_until(
+        config,
+        |mut connection| async move {
+            let stats = connection.stats();
+            let (reader, writer) = connection.stream.split();
+            let mut reader = FrameReader::new(reader, Framing::Fixed(REQUEST_LEN));
+            let mut writer = FrameWriter::new(writer, Framing::Fixed(RESPONSE_LEN));
+
+            let mut entries = HashMap::new();
+
+            loop {
+                let buffer = match reader.read_frame().await {
+                    Ok(Some(buffer)) => buffer,
+                    Ok(None) | Err(FrameError::Truncated { .. }) => break,
+                    Err(err) => Err(err)?,
+                };
+                trace!(bytes = buffer.len(), "read message");
+                stats.message_in();
+
+                let message = Message::from_bytes(&buffer)?;
+                debug!(?message, "received");
+
+                match message {
+                    Message::Insert { timestamp, price } => {
+                        entries.insert(timestamp, price);
+                    }
+                    Message::Query { mintime, maxtime } => {
+                        if mintime > maxtime {
+                            writer.write_frame(&0_i32.to_be_bytes()).await?;
+                            writer.flush().await?;
+                            stats.message_out();
+                            continue;
+                        }
+
+                        let iter = entries
+                            .iter()
+                            .filter(|(&k, _)| mintime <= k && k <= maxtime);
+                        let entry_count = iter.clone().count() as i64;
+                        let sum: i64 = iter.map(|(_, v)| *v).map(|v| v as i64).sum();
+
+                        if entry_count == 0 {
+                            writer.write_frame(&0_i32.to_be_bytes()).await?;
+                            writer.flush().await?;
+                            stats.message_out();
+                            continue;
+                        }
+
+                        let mean = (sum / entry_count) as i32;
+
+                        debug!(mean, "responding");
+
+                        writer.write_frame(&mean.to_be_bytes()).await?;
+                        writer.flush().await?;
+                        stats.message_out();
+                    }
+                }
+            }
+
+            Ok(())
+        },
+        shutdown,
+    )
+    .await
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Message {
+    Insert { timestamp: i32, price: i32 },
+    Query { mintime: i32, maxtime: i32 },
+}
+
+impl Message {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        trace!("from_bytes:    {:02x?}", bytes);
+
+        let message = match parse_message(bytes).finish() {
+            Ok(ok) => ok.1,
+            Err(err) => {
+                return Err(Box::new(std::io::Error::other(err.code.description())));
+            }
+        };
+
+        Ok(message)
+    }
+}
+
+pub fn parse_message(i: &[u8]) -> IResult<&[u8], Message> {
+    trace!("parse_message: {:02x?}", i);
+
+    let (i, r#type) = one_of("QI")(i)?;
+    let (i, param1) = be_i32(i)?;
+    let (i, param2) = be_i32(i)?;
+    let (_, _) = eof(i)?;
+
+    let message = match r#type {
+        'I' => Message::Insert {
+            timestamp: param1,
+            price: param2,
+        },
+        'Q' => Message::Query {
+            mintime: param1,
+            maxtime: param2,
+        },
+        _ => unreachable!("This is unreachable because of the above parser"),
+    };
+
+    Ok((i, message))
+}
@@ -0,0 +1,152 @@
+//! [Problem 5: Mob in the Middle](https://protohackers.com/problem/5), a proxy for Budget Chat that rewrites Boguscoin addresses.
+
+use std::future::Future;
+use std::net::SocketAddr;
+
+use anyhow::Result;
+use tokio::io::{AsyncRead, AsyncWriteExt, BufWriter};
+use tokio::net::TcpStream;
+use tokio::select;
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::codec::{FrameError, FrameReader, Framing};
+use crate::stats::ConnectionStats;
+use crate::{serve_until, Config, Error, ShutdownSummary, Stream};
+
+pub const TONY: &str = "7YWHMfk9JZe0LM0g1ZauHuiSxhI";
+/// chat.protohackers.com, see `mob_in_the_middle_upstream` in the [`Config`]
+pub const DEFAULT_UPSTREAM: &str = "[2a03:b0c0:1:d0::116a:8001]:16963";
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    let target = config.mob_in_the_middle_upstream;
+    let framing = Framing::Line {
+        max_len: config.max_line_length,
+    };
+
+    serve_until(
+        config,
+        move |connection| async move {
+            forward(connection.stream, connection.addr, target, framing)
+                .await
+                .map_err(|err| format!("{err:#}").into())
+        },
+        shutdown,
+    )
+    .await
+}
+
+/// Also returns how many addresses were replaced
+pub fn do_the_boguscoin_rewrite(input: &str) -> (String, u64) {
+    let input = input.strip_suffix('\n').unwrap_or(input);
+
+    let mut words: Vec<&str> = input.split_ascii_whitespace().collect();
+    let mut rewrites = 0;
+
+    for word in words.iter_mut() {
+        if word.starts_with('7')
+            && word.len() >= 26
+            && word.len() <= 35
+            && word.chars().all(|char| char.is_alphanumeric())
+        {
+            *word = TONY;
+            rewrites += 1;
+        }
+    }
+
+    let rewritten = words.join(" ");
+    info!("rewritten {input:?}  -->  {rewritten:?}");
+    (rewritten, rewrites)
+}
+
+/// Closes both connections once either side closes or sends a line that is too long
+async fn forward(
+    mut inbound: Stream,
+    original_addr: SocketAddr,
+    target_addr: SocketAddr,
+    framing: Framing,
+) -> anyhow::Result<()> {
+    info!("Accept - {original_addr:?} -> {target_addr:?}");
+
+    let mut outbound = TcpStream::connect(target_addr).await?;
+    let stats = inbound.stats().clone();
+
+    let (inbound_r, inbound_w) = inbound.split();
+    let (mut inbound_r, mut inbound_w) = (
+        FrameReader::new(inbound_r, framing),
+        BufWriter::new(inbound_w),
+    );
+
+    let (outbound_r, outbound_w) = outbound.split();
+    let (mut outbound_r, mut outbound_w) = (
+        FrameReader::new(outbound_r, framing),
+        BufWriter::new(outbound_w),
+    );
+
+    let span = info_span!("o2t", "{original_addr:?} -> {target_addr:?}");
+    let original_to_target = async {
+        while let Some(line) = read_line(&mut inbound_r, &stats).await? {
+            stats.message_in();
+            let (line, rewrites) = do_the_boguscoin_rewrite(&line);
+            stats.count("rewrites", rewrites);
+            outbound_w
+                .write_all(format!("{}\n", line).as_bytes())
+                .await?;
+            outbound_w.flush().await?;
+        }
+        info!("Disconnect o2t half");
+        Ok::<(), anyhow::Error>(())
+    }
+    .instrument(span);
+
+    let span = info_span!("t2o", "{target_addr:?} -> {original_addr:?}");
+    let target_to_original = async {
+        while let Some(line) = read_line(&mut outbound_r, &stats).await? {
+            let (line, rewrites) = do_the_boguscoin_rewrite(&line);
+            stats.count("rewrites", rewrites);
+            inbound_w
+                .write_all(format!("{}\n", line).as_bytes())
+                .await?;
+            inbound_w.flush().await?;
+            stats.message_out();
+        }
+        info!("Disconnect t2o half");
+        Ok::<(), anyhow::Error>(())
+    }
+    .instrument(span);
+
+    select! {
+        _ = original_to_target => {},
+        _ = target_to_original => {},
+    }
+
+    info!("Disconnect");
+
+    Ok(())
+}
+
+/// `None` once the connection should be closed
+async fn read_line(
+    reader: &mut FrameReader<impl AsyncRead + Unpin>,
+    stats: &ConnectionStats,
+) -> anyhow::Result<Option<String>> {
+    match reader.read_string().await {
+        Ok(Some(line)) => Ok(Some(line)),
+        Ok(None) => {
+            warn!("EOF");
+            Ok(None)
+        }
+        Err(FrameError::Truncated { .. }) => {
+            warn!("Disconnected without sending \\n");
+            Ok(None)
+        }
+        Err(err @ FrameError::TooLong { .. }) => {
+            warn!(error = %err, "line too long");
+            stats.count("lines_too_long", 1);
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
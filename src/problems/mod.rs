@@ -0,0 +1,166 @@
+//! The servers for every problem, see [`Problem::run`].
+
+use std::fmt;
+use std::future::Future;
+use std::str::FromStr;
+
+use crate::{shutdown, Config, Error, ShutdownSummary};
+
+pub mod budget_chat;
+pub mod insecure_sockets_layer;
+pub mod job_centre;
+pub mod line_reversal;
+pub mod means_to_an_end;
+pub mod mob_in_the_middle;
+pub mod pest_control;
+pub mod prime_time;
+pub mod smoke_test;
+pub mod speed_daemon;
+pub mod unusual_database_program;
+pub mod voracious_code_storage;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Problem {
+    SmokeTest,
+    PrimeTime,
+    MeansToAnEnd,
+    BudgetChat,
+    UnusualDatabaseProgram,
+    MobInTheMiddle,
+    SpeedDaemon,
+    LineReversal,
+    InsecureSocketsLayer,
+    JobCentre,
+    VoraciousCodeStorage,
+    PestControl,
+}
+
+impl Problem {
+    /// In the order of their numbers
+    pub const ALL: [Self; 12] = [
+        Self::SmokeTest,
+        Self::PrimeTime,
+        Self::MeansToAnEnd,
+        Self::BudgetChat,
+        Self::UnusualDatabaseProgram,
+        Self::MobInTheMiddle,
+        Self::SpeedDaemon,
+        Self::LineReversal,
+        Self::InsecureSocketsLayer,
+        Self::JobCentre,
+        Self::VoraciousCodeStorage,
+        Self::PestControl,
+    ];
+
+    pub fn number(self) -> usize {
+        Self::ALL
+            .iter()
+            .position(|&problem| problem == self)
+            .unwrap()
+    }
+
+    /// The title in kebab case, e.g. `speed-daemon`
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::SmokeTest => "smoke-test",
+            Self::PrimeTime => "prime-time",
+            Self::MeansToAnEnd => "means-to-an-end",
+            Self::BudgetChat => "budget-chat",
+            Self::UnusualDatabaseProgram => "unusual-database-program",
+            Self::MobInTheMiddle => "mob-in-the-middle",
+            Self::SpeedDaemon => "speed-daemon",
+            Self::LineReversal => "line-reversal",
+            Self::InsecureSocketsLayer => "insecure-sockets-layer",
+            Self::JobCentre => "job-centre",
+            Self::VoraciousCodeStorage => "voracious-code-storage",
+            Self::PestControl => "pest-control",
+        }
+    }
+
+    /// Problems 4 and 7 listen on a UDP port, all others on a TCP port
+    pub fn is_udp(self) -> bool {
+        matches!(self, Self::UnusualDatabaseProgram | Self::LineReversal)
+    }
+
+    /// Serves the problem on the address from `config` until the process receives
+    /// SIGINT or SIGTERM
+    pub async fn run(self, config: &Config) -> Result<ShutdownSummary, Error> {
+        self.run_until(config, shutdown::signal()).await
+    }
+
+    /// Serves the problem until `shutdown` completes, see [`serve_until`](crate::serve_until)
+    pub async fn run_until(
+        self,
+        config: &Config,
+        shutdown: impl Future<Output = ()>,
+    ) -> Result<ShutdownSummary, Error> {
+        match self {
+            Self::SmokeTest => smoke_test::run(config, shutdown).await,
+            Self::PrimeTime => prime_time::run(config, shutdown).await,
+            Self::MeansToAnEnd => means_to_an_end::run(config, shutdown).await,
+            Self::BudgetChat => budget_chat::run(config, shutdown).await,
+            Self::UnusualDatabaseProgram => unusual_database_program::run(config, shutdown).await,
+            Self::MobInTheMiddle => mob_in_the_middle::run(config, shutdown).await,
+            Self::SpeedDaemon => speed_daemon::run(config, shutdown).await,
+            Self::LineReversal => line_reversal::run(config, shutdown).await,
+            Self::InsecureSocketsLayer => insecure_sockets_layer::run(config, shutdown).await,
+            Self::JobCentre => job_centre::run(config, shutdown).await,
+            Self::VoraciousCodeStorage => voracious_code_storage::run(config, shutdown).await,
+            Self::PestControl => pest_control::run(config, shutdown).await,
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Accepts the number, the name or `problem<number>`, e.g. `6`, `speed-daemon` or `problem6`
+impl FromStr for Problem {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let number = s.strip_prefix("problem").unwrap_or(s);
+        if let Ok(number) = number.parse::<usize>() {
+            return Self::ALL
+                .get(number)
+                .copied()
+                .ok_or_else(|| format!("there is no problem {number}"));
+        }
+
+        Self::ALL
+            .into_iter()
+            .find(|problem| problem.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| format!("unknown problem {s:?}, see `protohackers list`"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_problems_by_number_or_name() {
+        for problem in Problem::ALL {
+            let number = problem.number();
+            assert_eq!(number.to_string().parse(), Ok(problem));
+            assert_eq!(format!("problem{number}").parse(), Ok(problem));
+            assert_eq!(problem.name().parse(), Ok(problem));
+            assert_eq!(problem.name().to_uppercase().parse(), Ok(problem));
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_problems() {
+        assert_eq!(
+            "12".parse::<Problem>(),
+            Err("there is no problem 12".to_string())
+        );
+        for unknown in ["", "-1", "speed_daemon", "problem", "problem-6"] {
+            let err = unknown.parse::<Problem>().unwrap_err();
+            assert!(err.starts_with("unknown problem"), "{unknown:?}: {err}");
+        }
+    }
+}
@@ -0,0 +1,492 @@
+//! [Problem 11: Pest Control](https://protohackers.com/problem/11), checks site visits against the policies of an authority server.
+
+use std::collections::{BTreeMap, HashMap};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context, Result};
+use tokio::io::{BufReader, BufWriter};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use types::{Action, Message, Population};
+
+use crate::{serve_until, Config, ShutdownSummary};
+
+/// The official authority server, see `pest_control_authority` in the [`Config`]
+pub const DEFAULT_AUTHORITY: &str = "pestcontrol.protohackers.com:20547";
+
+/// site -> task that talks to the authority server for that site. Shared by all connections.
+#[derive(Debug, Clone)]
+struct Sites {
+    authority: Arc<str>,
+    tasks: Arc<Mutex<BTreeMap<u32, mpsc::Sender<Vec<Population>>>>>,
+}
+
+pub mod types {
+    use anyhow::{bail, ensure, Context};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    pub const PROTOCOL: &str = "pestcontrol";
+    pub const VERSION: u32 = 1;
+
+    /// Anything longer is rejected before reading the content
+    const MAX_MESSAGE_LEN: u32 = 1024 * 1024;
+    /// type byte, length and checksum
+    const MIN_MESSAGE_LEN: u32 = 1 + 4 + 1;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Action {
+        Cull,
+        Conserve,
+    }
+
+    impl Action {
+        fn from_u8(byte: u8) -> anyhow::Result<Self> {
+            match byte {
+                0x90 => Ok(Self::Cull),
+                0xa0 => Ok(Self::Conserve),
+                _ => bail!("Unexpected action {byte:#04x}"),
+            }
+        }
+
+        fn to_u8(self) -> u8 {
+            match self {
+                Self::Cull => 0x90,
+                Self::Conserve => 0xa0,
+            }
+        }
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Population {
+        pub species: String,
+        pub count: u32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct TargetPopulation {
+        pub species: String,
+        pub min: u32,
+        pub max: u32,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Message {
+        Hello {
+            protocol: String,
+            version: u32,
+        },
+        Error {
+            message: String,
+        },
+        Ok,
+        DialAuthority {
+            site: u32,
+        },
+        TargetPopulations {
+            site: u32,
+            populations: Vec<TargetPopulation>,
+        },
+        CreatePolicy {
+            species: String,
+            action: Action,
+        },
+        DeletePolicy {
+            policy: u32,
+        },
+        PolicyResult {
+            policy: u32,
+        },
+        SiteVisit {
+            site: u32,
+            populations: Vec<Population>,
+        },
+    }
+
+    impl Message {
+        pub fn hello() -> Self {
+            Self::Hello {
+                protocol: PROTOCOL.to_string(),
+                version: VERSION,
+            }
+        }
+
+        pub fn error(message: impl ToString) -> Self {
+            Self::Error {
+                message: message.to_string(),
+            }
+        }
+
+        /// Reads a complete message. The declared length has to match the content
+        /// exactly and all bytes have to sum up to 0, otherwise the message is rejected.
+        pub async fn from_bytes<R: AsyncRead + Unpin>(reader: &mut R) -> anyhow::Result<Message> {
+            let type_byte = reader.read_u8().await.context("reading type byte")?;
+            let length = reader.read_u32().await.context("reading length")?;
+            ensure!(
+                (MIN_MESSAGE_LEN..=MAX_MESSAGE_LEN).contains(&length),
+                "Invalid message length {length}"
+            );
+
+            // everything after the length, including the checksum
+            let mut rest = vec![0_u8; (length - 5) as usize];
+            reader
+                .read_exact(&mut rest)
+                .await
+                .context("reading content")?;
+
+            let checksum = rest
+                .iter()
+                .chain(&length.to_be_bytes())
+                .fold(type_byte, |sum, byte| sum.wrapping_add(*byte));
+            ensure!(
+                checksum == 0,
+                "Invalid checksum, bytes sum up to {checksum}"
+            );
+
+            let mut content = &rest[..rest.len() - 1];
+            let message = Self::decode(type_byte, &mut content).await?;
+            ensure!(
+                content.is_empty(),
+                "{} unused bytes in message of type {type_byte:#04x}",
+                content.len()
+            );
+
+            Ok(message)
+        }
+
+        async fn decode(type_byte: u8, reader: &mut &[u8]) -> anyhow::Result<Message> {
+            match type_byte {
+                0x50 => {
+                    let protocol = read_str(reader).await?;
+                    let version = reader.read_u32().await?;
+                    Ok(Self::Hello { protocol, version })
+                }
+                0x51 => {
+                    let message = read_str(reader).await?;
+                    Ok(Self::Error { message })
+                }
+                0x52 => Ok(Self::Ok),
+                0x53 => {
+                    let site = reader.read_u32().await?;
+                    Ok(Self::DialAuthority { site })
+                }
+                0x54 => {
+                    let site = reader.read_u32().await?;
+                    let count = reader.read_u32().await?;
+                    let mut populations = Vec::with_capacity(count.min(1024) as usize);
+                    for _ in 0..count {
+                        let species = read_str(reader).await?;
+                        let min = reader.read_u32().await?;
+                        let max = reader.read_u32().await?;
+                        populations.push(TargetPopulation { species, min, max });
+                    }
+                    Ok(Self::TargetPopulations { site, populations })
+                }
+                0x55 => {
+                    let species = read_str(reader).await?;
+                    let action = Action::from_u8(reader.read_u8().await?)?;
+                    Ok(Self::CreatePolicy { species, action })
+                }
+                0x56 => {
+                    let policy = reader.read_u32().await?;
+                    Ok(Self::DeletePolicy { policy })
+                }
+                0x57 => {
+                    let policy = reader.read_u32().await?;
+                    Ok(Self::PolicyResult { policy })
+                }
+                0x58 => {
+                    let site = reader.read_u32().await?;
+                    let count = reader.read_u32().await?;
+                    let mut populations = Vec::with_capacity(count.min(1024) as usize);
+                    for _ in 0..count {
+                        let species = read_str(reader).await?;
+                        let count = reader.read_u32().await?;
+                        populations.push(Population { species, count });
+                    }
+                    Ok(Self::SiteVisit { site, populations })
+                }
+                _ => bail!("Unexpected message type byte {type_byte:#04x}"),
+            }
+        }
+
+        pub async fn to_bytes<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> anyhow::Result<()> {
+            let mut content = Vec::new();
+
+            let type_byte = match self {
+                Self::Hello { protocol, version } => {
+                    write_str(&mut content, protocol).await?;
+                    content.write_u32(*version).await?;
+                    0x50
+                }
+                Self::Error { message } => {
+                    write_str(&mut content, message).await?;
+                    0x51
+                }
+                Self::Ok => 0x52,
+                Self::DialAuthority { site } => {
+                    content.write_u32(*site).await?;
+                    0x53
+                }
+                Self::TargetPopulations { site, populations } => {
+                    content.write_u32(*site).await?;
+                    content.write_u32(populations.len() as u32).await?;
+                    for population in populations {
+                        write_str(&mut content, &population.species).await?;
+                        content.write_u32(population.min).await?;
+                        content.write_u32(population.max).await?;
+                    }
+                    0x54
+                }
+                Self::CreatePolicy { species, action } => {
+                    write_str(&mut content, species).await?;
+                    content.write_u8(action.to_u8()).await?;
+                    0x55
+                }
+                Self::DeletePolicy { policy } => {
+                    content.write_u32(*policy).await?;
+                    0x56
+                }
+                Self::PolicyResult { policy } => {
+                    content.write_u32(*policy).await?;
+                    0x57
+                }
+                Self::SiteVisit { site, populations } => {
+                    content.write_u32(*site).await?;
+                    content.write_u32(populations.len() as u32).await?;
+                    for population in populations {
+                        write_str(&mut content, &population.species).await?;
+                        content.write_u32(population.count).await?;
+                    }
+                    0x58
+                }
+            };
+
+            let length = MIN_MESSAGE_LEN + content.len() as u32;
+            let mut message = Vec::with_capacity(length as usize);
+            message.write_u8(type_byte).await?;
+            message.write_u32(length).await?;
+            message.extend(content);
+
+            let sum = message
+                .iter()
+                .fold(0_u8, |sum, byte| sum.wrapping_add(*byte));
+            message.push(0_u8.wrapping_sub(sum));
+
+            writer.write_all(&message).await?;
+            writer.flush().await?;
+            Ok(())
+        }
+    }
+
+    async fn read_str(reader: &mut &[u8]) -> anyhow::Result<String> {
+        let len = reader.read_u32().await? as usize;
+        ensure!(len <= reader.len(), "String length {len} exceeds message");
+
+        let mut string = vec![0_u8; len];
+        reader.read_exact(&mut string).await?;
+        Ok(String::from_utf8_lossy(&string).to_string())
+    }
+
+    async fn write_str(writer: &mut Vec<u8>, string: &str) -> anyhow::Result<()> {
+        writer.write_u32(string.len() as u32).await?;
+        writer.write_all(string.as_bytes()).await?;
+        Ok(())
+    }
+}
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, crate::Error> {
+    let sites = Sites {
+        authority: config.pest_control_authority.as_str().into(),
+        tasks: Arc::default(),
+    };
+
+    serve_until(
+        config,
+        move |connection| async move {
+            let addr = connection.addr;
+            let stats = connection.stats();
+            let (reader, writer) = connection.stream.into_split();
+            let (mut reader, mut writer) = (BufReader::new(reader), BufWriter::new(writer));
+
+            Message::hello().to_bytes(&mut writer).await?;
+            stats.message_out();
+
+            let result = async {
+                let hello = Message::from_bytes(&mut reader).await?;
+                stats.message_in();
+                match hello {
+                    Message::Hello { protocol, version }
+                        if protocol == types::PROTOCOL && version == types::VERSION => {}
+                    other => bail!("Expected hello, got {other:?}"),
+                }
+
+                loop {
+                    let message = match Message::from_bytes(&mut reader).await {
+                        Ok(message) => message,
+                        // Disconnecting between messages is fine
+                        Err(err) if is_eof(&err) => return Ok(()),
+                        Err(err) => return Err(err),
+                    };
+                    stats.message_in();
+
+                    match message {
+                        Message::SiteVisit { site, populations } => {
+                            info!("{addr}: site visit {site} {populations:?}");
+                            sites.visit(site, populations).await?;
+                        }
+                        other => bail!("Unexpected message {other:?}"),
+                    }
+                }
+            }
+            .await;
+
+            if let Err(err) = result {
+                warn!("{addr}: {err:?}");
+                Message::error(err).to_bytes(&mut writer).await?;
+                stats.message_out();
+            }
+
+            Ok(())
+        },
+        shutdown,
+    )
+    .await
+}
+
+fn is_eof(err: &anyhow::Error) -> bool {
+    err.downcast_ref::<std::io::Error>()
+        .is_some_and(|err| err.kind() == std::io::ErrorKind::UnexpectedEof)
+}
+
+impl Sites {
+    /// Validates the observations and hands them to the task of that site
+    async fn visit(&self, site: u32, populations: Vec<Population>) -> Result<()> {
+        let mut counts = HashMap::<&str, u32>::new();
+        for population in &populations {
+            let count = counts
+                .entry(&population.species)
+                .or_insert(population.count);
+            if *count != population.count {
+                bail!("Conflicting counts for {}", population.species);
+            }
+        }
+
+        let tx = {
+            let mut tasks = self.tasks.lock().unwrap();
+            let tx = tasks.get(&site).filter(|tx| !tx.is_closed()).cloned();
+            match tx {
+                Some(tx) => tx,
+                None => {
+                    let (tx, rx) = mpsc::channel(128);
+                    tasks.insert(site, tx.clone());
+                    let authority = self.authority.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = handle_site(site, &authority, rx).await {
+                            error!("Site {site}: {err:?}");
+                        }
+                    });
+                    tx
+                }
+            }
+        };
+
+        // If the site task died in the meantime the next visit starts a new one
+        if tx.send(populations).await.is_err() {
+            warn!("Site {site} is not available");
+        }
+        Ok(())
+    }
+}
+
+/// Talks to the authority server of a single site and keeps its policies in line with the visits
+async fn handle_site(
+    site: u32,
+    authority: &str,
+    mut visits: mpsc::Receiver<Vec<Population>>,
+) -> Result<()> {
+    let stream = TcpStream::connect(authority)
+        .await
+        .with_context(|| format!("connecting to authority {authority}"))?;
+    let (reader, writer) = stream.into_split();
+    let (mut reader, mut writer) = (BufReader::new(reader), BufWriter::new(writer));
+
+    Message::hello().to_bytes(&mut writer).await?;
+    match Message::from_bytes(&mut reader).await? {
+        Message::Hello { protocol, version }
+            if protocol == types::PROTOCOL && version == types::VERSION => {}
+        other => bail!("Expected hello from authority, got {other:?}"),
+    }
+
+    Message::DialAuthority { site }
+        .to_bytes(&mut writer)
+        .await?;
+    let targets = match Message::from_bytes(&mut reader).await? {
+        Message::TargetPopulations {
+            site: target_site,
+            populations,
+        } if target_site == site => populations,
+        other => bail!("Expected target populations, got {other:?}"),
+    };
+    info!("Site {site}: targets {targets:?}");
+
+    // species -> (policy, action)
+    let mut policies = HashMap::<String, (u32, Action)>::new();
+
+    while let Some(populations) = visits.recv().await {
+        for target in &targets {
+            let count = populations
+                .iter()
+                .find(|population| population.species == target.species)
+                .map_or(0, |population| population.count);
+
+            let wanted = if count < target.min {
+                Some(Action::Conserve)
+            } else if count > target.max {
+                Some(Action::Cull)
+            } else {
+                None
+            };
+
+            let current = policies.get(&target.species).map(|(_, action)| *action);
+            if current == wanted {
+                continue;
+            }
+
+            if let Some((policy, _)) = policies.remove(&target.species) {
+                Message::DeletePolicy { policy }
+                    .to_bytes(&mut writer)
+                    .await?;
+                match Message::from_bytes(&mut reader).await? {
+                    Message::Ok => info!("Site {site}: deleted policy {policy}"),
+                    other => bail!("Expected ok, got {other:?}"),
+                }
+            }
+
+            if let Some(action) = wanted {
+                Message::CreatePolicy {
+                    species: target.species.clone(),
+                    action,
+                }
+                .to_bytes(&mut writer)
+                .await?;
+                match Message::from_bytes(&mut reader).await? {
+                    Message::PolicyResult { policy } => {
+                        info!(
+                            "Site {site}: {action:?} {} as policy {policy}",
+                            target.species
+                        );
+                        policies.insert(target.species.clone(), (policy, action));
+                    }
+                    other => bail!("Expected policy result, got {other:?}"),
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
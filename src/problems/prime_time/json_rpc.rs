@@ -0,0 +1,114 @@
+//! [JSON-RPC 2.0](https://www.jsonrpc.org/specification) over the same newline delimited
+//! connection: one request or batch per line, one response or batch per line. Errors are
+//! answered with the standard error objects and keep the connection open.
+
+use serde_json::{json, Value};
+
+use super::methods::Method;
+
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+
+/// The response to a line, `None` if it only contained notifications.
+/// `max_digits` is passed on to [`Method::call`].
+pub fn answer(line: &str, max_digits: usize) -> Option<Value> {
+    let request = match serde_json::from_str::<Value>(line) {
+        Ok(request) => request,
+        Err(err) => return Some(error(Value::Null, PARSE_ERROR, err.to_string())),
+    };
+
+    match request {
+        Value::Array(batch) if batch.is_empty() => Some(error(
+            Value::Null,
+            INVALID_REQUEST,
+            "empty batch".to_string(),
+        )),
+        Value::Array(batch) => {
+            let responses: Vec<Value> = batch
+                .into_iter()
+                .filter_map(|request| call(request, max_digits))
+                .collect();
+            (!responses.is_empty()).then_some(Value::Array(responses))
+        }
+        request => call(request, max_digits),
+    }
+}
+
+/// The response to a line longer than `max_len` bytes, which is not read
+pub fn too_long(max_len: usize) -> Value {
+    invalid_request(
+        Value::Null,
+        &format!("request is longer than {max_len} bytes"),
+    )
+}
+
+/// The response to a single request, `None` for notifications
+fn call(request: Value, max_digits: usize) -> Option<Value> {
+    let Value::Object(mut request) = request else {
+        return Some(invalid_request(Value::Null, "request must be an object"));
+    };
+    // Requests without an id are notifications and never get an answer
+    let id = request.remove("id");
+    let invalid = |data| Some(invalid_request(id.clone().unwrap_or(Value::Null), data));
+
+    if !matches!(
+        id,
+        None | Some(Value::Null | Value::Number(_) | Value::String(_))
+    ) {
+        return invalid("id must be a string, a number or null");
+    }
+    if request.get("jsonrpc") != Some(&json!("2.0")) {
+        return invalid(r#"jsonrpc must be "2.0""#);
+    }
+    let Some(Value::String(method)) = request.get("method") else {
+        return invalid("method must be a string");
+    };
+    let Some(method) = Method::from_name(method) else {
+        let data = format!("unknown method {method:?}");
+        return id.map(|id| error(id, METHOD_NOT_FOUND, data));
+    };
+
+    // By name or by position
+    let param = match request.remove("params") {
+        Some(Value::Object(mut params)) => params.remove(method.param()),
+        Some(Value::Array(params)) if params.len() <= 1 => params.into_iter().next(),
+        Some(Value::Array(_)) => {
+            let data = format!("{} takes a single parameter", method.name());
+            return id.map(|id| error(id, INVALID_PARAMS, data));
+        }
+        None => None,
+        Some(_) => return invalid("params must be an object or an array"),
+    };
+    let result = match param {
+        Some(param) => method.call(&param, max_digits),
+        None => Err(format!("missing parameter {}", method.param())),
+    };
+
+    let id = id?;
+    Some(match result {
+        Ok(result) => json!({"jsonrpc": "2.0", "result": result, "id": id}),
+        Err(data) => error(id, INVALID_PARAMS, data),
+    })
+}
+
+fn invalid_request(id: Value, data: &str) -> Value {
+    error(id, INVALID_REQUEST, data.to_string())
+}
+
+/// `data` says what exactly was wrong
+fn error(id: Value, code: i64, data: String) -> Value {
+    let message = match code {
+        PARSE_ERROR => "Parse error",
+        INVALID_REQUEST => "Invalid Request",
+        METHOD_NOT_FOUND => "Method not found",
+        INVALID_PARAMS => "Invalid params",
+        _ => "Internal error",
+    };
+    json!({
+        "jsonrpc": "2.0",
+        "error": {"code": code, "message": message, "data": data},
+        "id": id,
+    })
+}
@@ -0,0 +1,115 @@
+//! The methods of the extended and JSON-RPC protocols. Each takes a single parameter.
+
+use serde_json::{Number, Value};
+
+use super::{oracle, primality};
+
+/// `nextPrime` refuses larger numbers, the primality tests would take too long
+pub const MAX_NEXT_PRIME_DIGITS: usize = 100;
+
+/// `primeCount` refuses larger numbers
+pub const MAX_PRIME_COUNT: u64 = 100_000_000_000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    IsPrime,
+    Factorize,
+    NextPrime,
+    PrimeCount,
+    IsPrimeBatch,
+}
+
+impl Method {
+    pub const ALL: [Self; 5] = [
+        Self::IsPrime,
+        Self::Factorize,
+        Self::NextPrime,
+        Self::PrimeCount,
+        Self::IsPrimeBatch,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|method| method.name() == name)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::IsPrime => "isPrime",
+            Self::Factorize => "factorize",
+            Self::NextPrime => "nextPrime",
+            Self::PrimeCount => "primeCount",
+            Self::IsPrimeBatch => "isPrimeBatch",
+        }
+    }
+
+    /// The name of the parameter
+    pub fn param(self) -> &'static str {
+        match self {
+            Self::IsPrimeBatch => "numbers",
+            _ => "number",
+        }
+    }
+
+    /// The field next to `method` that holds the result outside of JSON-RPC
+    pub fn result_field(self) -> &'static str {
+        match self {
+            Self::IsPrime | Self::NextPrime => "prime",
+            Self::Factorize => "factors",
+            Self::PrimeCount => "count",
+            Self::IsPrimeBatch => "primes",
+        }
+    }
+
+    /// Fails with a description of what is wrong with `param`.
+    /// `isPrime` and `isPrimeBatch` refuse integers with more than `max_digits` digits.
+    pub fn call(self, param: &Value, max_digits: usize) -> Result<Value, String> {
+        let number = || match param {
+            Value::Number(number) => Ok(number),
+            _ => Err(format!("{} must be a number", self.param())),
+        };
+        let is_prime = |number: &Number| match primality::too_large(number, max_digits) {
+            true => Err(format!("integers must have at most {max_digits} digits")),
+            false => Ok(Value::Bool(oracle::global().is_prime_number(number))),
+        };
+
+        match self {
+            Self::IsPrime => is_prime(number()?),
+            Self::Factorize => {
+                let n = primality::integer(number()?, 20)
+                    .and_then(|n| u64::try_from(n).ok())
+                    .filter(|&n| n > 0)
+                    .ok_or("number must be an integer from 1 to 2^64 - 1")?;
+                Ok(primality::factorize(n).into())
+            }
+            Self::NextPrime => {
+                let n = primality::integer(number()?, MAX_NEXT_PRIME_DIGITS).ok_or_else(|| {
+                    format!("number must be an integer with at most {MAX_NEXT_PRIME_DIGITS} digits")
+                })?;
+                let prime = primality::next_prime(&n).to_string();
+                Ok(Value::Number(
+                    serde_json::from_str::<Number>(&prime).unwrap(),
+                ))
+            }
+            Self::PrimeCount => {
+                let n = primality::integer(number()?, 20)
+                    .and_then(|n| i128::try_from(n).ok())
+                    .filter(|&n| n <= MAX_PRIME_COUNT as i128)
+                    .ok_or_else(|| format!("number must be an integer up to {MAX_PRIME_COUNT}"))?;
+                Ok(primality::prime_count(n.max(0) as u64).into())
+            }
+            Self::IsPrimeBatch => {
+                let Value::Array(numbers) = param else {
+                    return Err("numbers must be an array of numbers".to_string());
+                };
+                numbers
+                    .iter()
+                    .map(|number| match number {
+                        Value::Number(number) => is_prime(number),
+                        _ => Err("numbers must be an array of numbers".to_string()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()
+                    .map(Value::Array)
+            }
+        }
+    }
+}
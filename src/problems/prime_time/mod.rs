@@ -0,0 +1,321 @@
+//! [Problem 1: Prime Time](https://protohackers.com/problem/1), JSON requests that ask whether a number is prime.
+//!
+//! Besides the original protocol, see [`Protocol`], the server speaks an extended one with more
+//! [`methods`] and the same methods over [`json_rpc`].
+
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use serde_json::{Number, Value};
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::sync::{mpsc, Semaphore};
+use tokio::task::{self, JoinHandle};
+use tokio::{pin, select};
+use tracing::{debug, warn, Span};
+
+use crate::codec::{FrameError, FrameReader, Framing};
+use crate::stats::ConnectionStats;
+use crate::{serve_until, Config, Connection, Error, ShutdownSummary};
+
+pub mod json_rpc;
+pub mod methods;
+pub mod oracle;
+pub mod primality;
+
+use methods::Method;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Protocol {
+    /// `isPrime` only, anything else is answered with `malformed` before disconnecting
+    #[default]
+    Strict,
+    /// Also `factorize`, `nextPrime`, `primeCount` and `isPrimeBatch`,
+    /// answered like `isPrime`
+    Extended,
+    /// The same methods as JSON-RPC 2.0, errors keep the connection open
+    JsonRpc,
+}
+
+/// Requests per connection that are evaluated or wait for their response to be written
+pub const DEFAULT_WINDOW: usize = 32;
+
+/// Checking a prime this long takes a fraction of a second
+pub const DEFAULT_MAX_DIGITS: usize = 1000;
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    let protocol = config.prime_time_protocol;
+    let window = config.prime_time_window.max(1);
+    let max_digits = config.prime_time_max_digits;
+    let framing = Framing::Line {
+        max_len: config.max_line_length,
+    };
+    // Shared by all connections, so together they cannot fill the blocking pool
+    let evaluations = Arc::new(Semaphore::new(
+        thread::available_parallelism().map_or(4, |threads| threads.get()),
+    ));
+    // Rather than in the middle of the first request
+    oracle::global();
+    serve_until(
+        config,
+        move |connection| {
+            handle(
+                connection,
+                protocol,
+                window,
+                framing,
+                max_digits,
+                evaluations.clone(),
+            )
+        },
+        shutdown,
+    )
+    .await
+}
+
+/// What to do once a request has been evaluated
+#[derive(Debug)]
+enum Outcome {
+    Respond(Vec<u8>),
+    /// Only JSON-RPC notifications
+    Ignore,
+    /// Answer with `malformed` and disconnect
+    Malformed,
+    /// Answer and disconnect
+    Close(Vec<u8>),
+}
+
+impl Outcome {
+    fn respond(response: &impl Serialize) -> Self {
+        Self::Respond(response_line(response))
+    }
+
+    /// For a request longer than `max_len`, which is not read any further
+    fn too_long(protocol: Protocol, max_len: usize) -> Self {
+        match protocol {
+            Protocol::Strict | Protocol::Extended => Self::Malformed,
+            Protocol::JsonRpc => Self::Close(response_line(&json_rpc::too_long(max_len))),
+        }
+    }
+}
+
+fn response_line(response: &impl Serialize) -> Vec<u8> {
+    let mut bytes = serde_json::to_vec(response).expect("responses are plain JSON");
+    bytes.push(b'\n');
+    bytes
+}
+
+/// Evaluates the requests of a connection concurrently on the blocking pool, so a huge number
+/// does not hold up the requests behind it. Once `window` requests are in flight, reading waits
+/// for the oldest one to be answered, and it also waits for one of the `evaluations` shared by
+/// all connections. Requests that did not start before the connection is gone are skipped.
+/// Responses are written in the order of the requests.
+/// A line that is longer than the framing allows is answered after the requests before it and
+/// ends the connection.
+async fn handle(
+    mut connection: Connection,
+    protocol: Protocol,
+    window: usize,
+    framing: Framing,
+    max_digits: usize,
+    evaluations: Arc<Semaphore>,
+) -> Result<(), Error> {
+    let gone = Gone::default();
+    let is_gone = gone.0.clone();
+    let stats = connection.stats();
+    let reader_stats = stats.clone();
+    let (reader, writer) = connection.stream.split();
+    let mut lines = FrameReader::new(reader, framing);
+    let mut writer = BufWriter::new(writer);
+    let (in_flight_tx, mut in_flight_rx) = mpsc::channel::<JoinHandle<Outcome>>(window);
+
+    let read = async move {
+        loop {
+            let line = match lines.read_string().await {
+                Ok(Some(line)) => line,
+                Ok(None) | Err(FrameError::Truncated { .. }) => break,
+                Err(err @ FrameError::TooLong { max, .. }) => {
+                    warn!(error = %err, "request too long");
+                    reader_stats.message_in();
+                    reader_stats.count("lines_too_long", 1);
+                    let outcome = Outcome::too_long(protocol, max);
+                    let _ = in_flight_tx.send(task::spawn(async { outcome })).await;
+                    break;
+                }
+                Err(err) => return Err(err.into()),
+            };
+            reader_stats.message_in();
+            let stats = reader_stats.clone();
+            let span = Span::current();
+            let is_gone = is_gone.clone();
+            let permit = evaluations
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("the semaphore is never closed");
+            let outcome = task::spawn_blocking(move || {
+                let _permit = permit;
+                if is_gone.load(Ordering::Relaxed) {
+                    return Outcome::Ignore;
+                }
+                span.in_scope(|| evaluate(&line, protocol, max_digits, &stats))
+            });
+            if in_flight_tx.send(outcome).await.is_err() {
+                // The writer stopped after a malformed request
+                break;
+            }
+        }
+        Ok::<_, Error>(())
+    };
+
+    let write = async move {
+        while let Some(outcome) = in_flight_rx.recv().await {
+            match outcome.await? {
+                Outcome::Respond(response) => {
+                    writer.write_all(&response).await?;
+                    stats.message_out();
+                }
+                Outcome::Ignore => {}
+                Outcome::Malformed => {
+                    writer.write_all(b"malformed").await?;
+                    writer.flush().await?;
+                    stats.message_out();
+                    // disconnect
+                    return Ok(());
+                }
+                Outcome::Close(response) => {
+                    writer.write_all(&response).await?;
+                    writer.flush().await?;
+                    stats.message_out();
+                    return Ok(());
+                }
+            }
+            // Responses that are ready together are sent together
+            if in_flight_rx.is_empty() {
+                writer.flush().await?;
+            }
+        }
+        Ok::<_, Error>(())
+    };
+
+    pin!(read, write);
+    select! {
+        result = &mut write => result,
+        result = &mut read => {
+            result?;
+            write.await
+        }
+    }
+}
+
+/// Set once [`handle`] returns or is dropped
+#[derive(Debug, Default)]
+struct Gone(Arc<AtomicBool>);
+
+impl Drop for Gone {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Runs on the blocking pool. Integers with more than `max_digits` digits are malformed,
+/// they would keep the thread busy for too long.
+fn evaluate(line: &str, protocol: Protocol, max_digits: usize, stats: &ConnectionStats) -> Outcome {
+    match protocol {
+        Protocol::Strict => match serde_json::from_str::<Request>(line) {
+            Ok(request)
+                if request.method_is_valid()
+                    && primality::too_large(&request.number, max_digits) =>
+            {
+                warn!(max_digits, "number too large");
+                Outcome::Malformed
+            }
+            Ok(request) if request.method_is_valid() => {
+                let (prime, source) = oracle::global().lookup_number(&request.number);
+                let response = Response::new(prime);
+                stats.count("primes_checked", 1);
+                if let Some(source) = source {
+                    stats.count(source.counter(), 1);
+                }
+                debug!(?request, prime = response.prime, "responding");
+                Outcome::respond(&response)
+            }
+            Ok(request) => {
+                warn!(method = request.method, "invalid method");
+                Outcome::Malformed
+            }
+            Err(err) => {
+                warn!(error = %err, "malformed request");
+                Outcome::Malformed
+            }
+        },
+        Protocol::Extended => match answer(line, max_digits) {
+            Some(response) => Outcome::respond(&response),
+            None => {
+                warn!(line, "malformed request");
+                Outcome::Malformed
+            }
+        },
+        Protocol::JsonRpc => match json_rpc::answer(line, max_digits) {
+            Some(response) => Outcome::respond(&response),
+            None => {
+                debug!("only notifications");
+                Outcome::Ignore
+            }
+        },
+    }
+}
+
+/// Answers a request of the extended protocol, `None` if it is malformed
+fn answer(line: &str, max_digits: usize) -> Option<Value> {
+    let Ok(Value::Object(request)) = serde_json::from_str::<Value>(line) else {
+        return None;
+    };
+    let method = Method::from_name(request.get("method")?.as_str()?)?;
+    let result = method.call(request.get(method.param())?, max_digits).ok()?;
+
+    let mut response = serde_json::Map::new();
+    response.insert("method".to_string(), method.name().into());
+    response.insert(method.result_field().to_string(), result);
+    Some(Value::Object(response))
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Response {
+    pub method: &'static str,
+    pub prime: bool,
+}
+
+impl Response {
+    pub fn new(prime: bool) -> Self {
+        Self {
+            method: "isPrime",
+            prime,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Request {
+    pub method: String,
+    /// Exactly as it was sent, integers can have any size
+    pub number: Number,
+}
+
+impl Request {
+    pub fn method_is_valid(&self) -> bool {
+        self.method == "isPrime"
+    }
+
+    /// Asks the [global oracle](oracle::global)
+    pub fn is_prime(&self) -> bool {
+        oracle::global().is_prime_number(&self.number)
+    }
+}
@@ -0,0 +1,221 @@
+//! Answers for numbers that are asked about over and over again, across all connections,
+//! see [`PrimeOracle`].
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{LazyLock, Mutex};
+
+use num_bigint::BigUint;
+use serde_json::Number;
+
+use super::primality;
+
+/// A sieve up to here takes 1 MiB
+pub const DEFAULT_SIEVE_LIMIT: u64 = 1 << 24;
+pub const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+/// Larger numbers are never cached, so a full cache of the default capacity stays
+/// below 5 MiB however large the numbers that clients send
+pub const MAX_CACHED_BITS: u64 = 4096;
+
+static GLOBAL: LazyLock<PrimeOracle> = LazyLock::new(PrimeOracle::default);
+
+/// The oracle of the process, behind [`Request::is_prime`](super::Request::is_prime) and the
+/// `isPrime` and `isPrimeBatch` methods. Builds the sieve on first use.
+pub fn global() -> &'static PrimeOracle {
+    &GLOBAL
+}
+
+/// Where an answer came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Sieve,
+    Cache,
+    /// Not in the cache, computed and added to it if it has at most [`MAX_CACHED_BITS`]
+    Computed,
+}
+
+impl Source {
+    /// The connection counter, see [`ConnectionStats::count`](crate::stats::ConnectionStats::count)
+    pub fn counter(self) -> &'static str {
+        match self {
+            Self::Sieve => "prime_sieve_hits",
+            Self::Cache => "prime_cache_hits",
+            Self::Computed => "prime_cache_misses",
+        }
+    }
+}
+
+/// How often each [`Source`] answered since the oracle was created
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct OracleStats {
+    pub sieve_hits: u64,
+    pub cache_hits: u64,
+    pub cache_misses: u64,
+    /// Numbers in the cache right now
+    pub cached: usize,
+}
+
+/// Answers numbers up to the sieve limit from a sieve built up front, and larger ones from a
+/// cache of the most recently asked ones or with [`primality::is_prime`] if they are not in it.
+/// Safe to share between threads, the cache sits behind a mutex that is not held while
+/// computing.
+#[derive(Debug)]
+pub struct PrimeOracle {
+    sieve_limit: u64,
+    /// Bit i says whether 2i + 1 is a prime
+    sieve: Vec<u64>,
+    cache: Mutex<Lru>,
+    sieve_hits: AtomicU64,
+    cache_hits: AtomicU64,
+    cache_misses: AtomicU64,
+}
+
+impl Default for PrimeOracle {
+    fn default() -> Self {
+        Self::new(DEFAULT_SIEVE_LIMIT, DEFAULT_CACHE_CAPACITY)
+    }
+}
+
+impl PrimeOracle {
+    /// A `cache_capacity` of 0 disables the cache
+    pub fn new(sieve_limit: u64, cache_capacity: usize) -> Self {
+        Self {
+            sieve_limit,
+            sieve: sieve(sieve_limit),
+            cache: Mutex::new(Lru::new(cache_capacity)),
+            sieve_hits: AtomicU64::new(0),
+            cache_hits: AtomicU64::new(0),
+            cache_misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn is_prime(&self, n: &BigUint) -> bool {
+        self.lookup(n).0
+    }
+
+    pub fn lookup(&self, n: &BigUint) -> (bool, Source) {
+        if let Some(n) = u64::try_from(n).ok().filter(|&n| n <= self.sieve_limit) {
+            self.sieve_hits.fetch_add(1, Ordering::Relaxed);
+            let prime = match n {
+                2 => true,
+                n if n % 2 == 0 => false,
+                n => self.sieve[(n / 2) as usize / 64] & (1 << ((n / 2) % 64)) != 0,
+            };
+            return (prime, Source::Sieve);
+        }
+
+        if let Some(prime) = self.cache.lock().unwrap().get(n) {
+            self.cache_hits.fetch_add(1, Ordering::Relaxed);
+            return (prime, Source::Cache);
+        }
+        let prime = primality::is_prime(n);
+        if n.bits() <= MAX_CACHED_BITS {
+            self.cache.lock().unwrap().insert(n, prime);
+        }
+        self.cache_misses.fetch_add(1, Ordering::Relaxed);
+        (prime, Source::Computed)
+    }
+
+    /// Like [`primality::is_prime_number`]
+    pub fn is_prime_number(&self, number: &Number) -> bool {
+        self.lookup_number(number).0
+    }
+
+    /// Without a source for numbers that cannot be primes to begin with,
+    /// see [`primality::candidate`]
+    pub fn lookup_number(&self, number: &Number) -> (bool, Option<Source>) {
+        match primality::candidate(number) {
+            Some(n) => {
+                let (prime, source) = self.lookup(&n);
+                (prime, Some(source))
+            }
+            None => (false, None),
+        }
+    }
+
+    pub fn sieve_limit(&self) -> u64 {
+        self.sieve_limit
+    }
+
+    pub fn stats(&self) -> OracleStats {
+        OracleStats {
+            sieve_hits: self.sieve_hits.load(Ordering::Relaxed),
+            cache_hits: self.cache_hits.load(Ordering::Relaxed),
+            cache_misses: self.cache_misses.load(Ordering::Relaxed),
+            cached: self.cache.lock().unwrap().len(),
+        }
+    }
+}
+
+/// Sieve of Eratosthenes over the odd numbers up to `limit`
+fn sieve(limit: u64) -> Vec<u64> {
+    let len = (limit as usize).div_ceil(2);
+    let mut bits = vec![u64::MAX; len.div_ceil(64).max(1)];
+    // 1 is not a prime
+    bits[0] &= !1;
+
+    let mut i = 1;
+    while (2 * i + 1) * (2 * i + 1) <= limit as usize {
+        if bits[i / 64] & (1 << (i % 64)) != 0 {
+            let p = 2 * i + 1;
+            for j in ((p * p) / 2..len).step_by(p) {
+                bits[j / 64] &= !(1 << (j % 64));
+            }
+        }
+        i += 1;
+    }
+    bits
+}
+
+/// Evicts the least recently used number once it is full
+#[derive(Debug)]
+struct Lru {
+    capacity: usize,
+    /// Counts every use, the higher the more recent
+    tick: u64,
+    /// number -> (prime, last use)
+    entries: HashMap<BigUint, (bool, u64)>,
+    /// last use -> number
+    uses: BTreeMap<u64, BigUint>,
+}
+
+impl Lru {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            tick: 0,
+            entries: HashMap::new(),
+            uses: BTreeMap::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn get(&mut self, n: &BigUint) -> Option<bool> {
+        let (prime, last_use) = self.entries.get_mut(n)?;
+        self.tick += 1;
+        let key = self.uses.remove(last_use).expect("every entry has a use");
+        *last_use = self.tick;
+        self.uses.insert(self.tick, key);
+        Some(*prime)
+    }
+
+    fn insert(&mut self, n: &BigUint, prime: bool) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        // Another thread may have computed it at the same time
+        if let Some((_, last_use)) = self.entries.insert(n.clone(), (prime, self.tick)) {
+            self.uses.remove(&last_use);
+        }
+        self.uses.insert(self.tick, n.clone());
+
+        while self.entries.len() > self.capacity {
+            let (_, oldest) = self.uses.pop_first().expect("more entries than uses");
+            self.entries.remove(&oldest);
+        }
+    }
+}
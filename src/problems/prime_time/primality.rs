@@ -0,0 +1,299 @@
+//! Primality of JSON numbers of any size, without rounding them to `f64` first.
+
+use num_bigint::{BigInt, BigUint, Sign};
+use serde_json::Number;
+
+const SMALL_PRIMES: [u32; 25] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+];
+
+/// Miller–Rabin with these bases never lets a composite below [`DETERMINISTIC_LIMIT`] pass
+const BASES: [u32; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// The smallest composite that passes all [`BASES`]
+const DETERMINISTIC_LIMIT: u128 = 318_665_857_834_031_151_167_461;
+
+/// Numbers from [`DETERMINISTIC_LIMIT`] on get these rounds on top. The bases are fixed, so
+/// composites that pass all of them can be constructed and would be answered as primes.
+const EXTRA_BASES: [u32; 20] = [
+    41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+/// Whether the JSON number is a prime. Negative numbers and non-integers are not, `7.0` and
+/// `0.7e1` are the integer 7.
+pub fn is_prime_number(number: &Number) -> bool {
+    candidate(number).is_some_and(|n| is_prime(&n))
+}
+
+/// The value of the JSON number if it could be a prime, `None` for negative numbers,
+/// non-integers and multiples of 10 too large to write out
+pub fn candidate(number: &Number) -> Option<BigUint> {
+    let decimal = Decimal::parse(number);
+    // With the trailing zeros gone, any other scale is a non-integer or a multiple of 10
+    if decimal.negative || decimal.scale != 0 {
+        return None;
+    }
+    match decimal.digits.is_empty() {
+        true => Some(BigUint::ZERO),
+        false => BigUint::parse_bytes(decimal.digits.as_bytes(), 10),
+    }
+}
+
+/// Whether the JSON number is a [`candidate`] with more than `max_digits` digits. Everything
+/// else is answered without testing it, however large it is.
+pub fn too_large(number: &Number, max_digits: usize) -> bool {
+    let decimal = Decimal::parse(number);
+    !decimal.negative && decimal.scale == 0 && decimal.digits.len() > max_digits
+}
+
+/// The value of the JSON number if it is an integer with at most `max_digits` digits
+pub fn integer(number: &Number, max_digits: usize) -> Option<BigInt> {
+    let decimal = Decimal::parse(number);
+    if decimal.digits.is_empty() {
+        return Some(BigInt::from(0));
+    }
+    if decimal.scale < 0 || decimal.digits.len() as i128 + decimal.scale > max_digits as i128 {
+        return None;
+    }
+
+    let digits = format!("{}{}", decimal.digits, "0".repeat(decimal.scale as usize));
+    let magnitude = BigUint::parse_bytes(digits.as_bytes(), 10)?;
+    let sign = match decimal.negative {
+        true => Sign::Minus,
+        false => Sign::Plus,
+    };
+    Some(BigInt::from_biguint(sign, magnitude))
+}
+
+/// A JSON number as `digits * 10^scale`, exactly as it was sent
+struct Decimal {
+    negative: bool,
+    /// Without leading and trailing zeros, empty for 0
+    digits: String,
+    scale: i128,
+}
+
+impl Decimal {
+    fn parse(number: &Number) -> Self {
+        // Without rounding, thanks to serde_json's `arbitrary_precision`
+        let text = number.to_string();
+        let (negative, text) = match text.strip_prefix('-') {
+            Some(text) => (true, text),
+            None => (false, text.as_str()),
+        };
+
+        let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+            Some((mantissa, exponent)) => {
+                // Saturates, such numbers are far too small or large to be written out anyway
+                let exponent =
+                    exponent
+                        .parse::<i128>()
+                        .unwrap_or(match exponent.starts_with('-') {
+                            true => i128::MIN / 2,
+                            false => i128::MAX / 2,
+                        });
+                (mantissa, exponent)
+            }
+            None => (text, 0),
+        };
+        let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+
+        let all_digits = format!("{integer}{fraction}");
+        let digits = all_digits.trim_end_matches('0');
+        let scale = exponent + (all_digits.len() - digits.len()) as i128 - fraction.len() as i128;
+        Self {
+            negative,
+            digits: digits.trim_start_matches('0').to_string(),
+            scale,
+        }
+    }
+}
+
+/// Deterministic below [`DETERMINISTIC_LIMIT`], Miller–Rabin with 32 rounds above
+pub fn is_prime(n: &BigUint) -> bool {
+    if let Ok(n) = u64::try_from(n) {
+        return is_prime_u64(n);
+    }
+
+    let one = BigUint::from(1_u32);
+    if SMALL_PRIMES.iter().any(|&p| n % p == BigUint::ZERO) {
+        return false;
+    }
+    let extra: &[u32] = match u128::try_from(n) {
+        Ok(n) if n < DETERMINISTIC_LIMIT => &[],
+        _ => &EXTRA_BASES,
+    };
+
+    let n_minus_one = n - 1_u32;
+    let s = n_minus_one.trailing_zeros().unwrap_or(0);
+    let d = &n_minus_one >> s;
+    BASES.iter().chain(extra).all(|&base| {
+        let mut x = BigUint::from(base).modpow(&d, n);
+        if x == one || x == n_minus_one {
+            return true;
+        }
+        for _ in 1..s {
+            x = &x * &x % n;
+            if x == n_minus_one {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+fn is_prime_u64(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+    for p in SMALL_PRIMES {
+        if n.is_multiple_of(p as u64) {
+            return n == p as u64;
+        }
+    }
+
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    BASES.iter().all(|&base| {
+        let mut x = pow_mod(base as u64, d, n);
+        if x == 1 || x == n - 1 {
+            return true;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                return true;
+            }
+        }
+        false
+    })
+}
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+fn pow_mod(mut base: u64, mut exponent: u64, m: u64) -> u64 {
+    let mut result = 1;
+    base %= m;
+    while exponent > 0 {
+        if exponent & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exponent >>= 1;
+    }
+    result
+}
+
+/// The prime factors in ascending order, repeated as often as they divide `n`.
+/// Empty for 0 and 1.
+pub fn factorize(mut n: u64) -> Vec<u64> {
+    let mut factors = Vec::new();
+    if n < 2 {
+        return factors;
+    }
+    for p in SMALL_PRIMES {
+        while n.is_multiple_of(p as u64) {
+            factors.push(p as u64);
+            n /= p as u64;
+        }
+    }
+
+    split(n, &mut factors);
+    factors.sort_unstable();
+    factors
+}
+
+fn split(n: u64, factors: &mut Vec<u64>) {
+    if n == 1 {
+        return;
+    }
+    if is_prime_u64(n) {
+        factors.push(n);
+        return;
+    }
+    let divisor = pollard_rho(n);
+    split(divisor, factors);
+    split(n / divisor, factors);
+}
+
+/// A non-trivial divisor of an odd composite without small factors
+fn pollard_rho(n: u64) -> u64 {
+    for c in 1.. {
+        let f = |x: u64| ((mul_mod(x, x, n) as u128 + c) % n as u128) as u64;
+        let (mut x, mut y, mut divisor) = (2, 2, 1);
+        while divisor == 1 {
+            x = f(x);
+            y = f(f(y));
+            divisor = gcd(x.abs_diff(y), n);
+        }
+        // Otherwise the cycle closed without a divisor, try another polynomial
+        if divisor != n {
+            return divisor;
+        }
+    }
+    unreachable!("some polynomial finds a divisor")
+}
+
+fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+/// The smallest prime larger than `n`
+pub fn next_prime(n: &BigInt) -> BigUint {
+    let Some(n) = n.to_biguint().filter(|n| *n >= BigUint::from(2_u32)) else {
+        return BigUint::from(2_u32);
+    };
+
+    let mut candidate = n + 1_u32;
+    if !candidate.bit(0) {
+        candidate += 1_u32;
+    }
+    while !is_prime(&candidate) {
+        candidate += 2_u32;
+    }
+    candidate
+}
+
+/// The number of primes up to and including `n`, in about n^(3/4) steps
+/// (the Lucy Hedgehog algorithm).
+pub fn prime_count(n: u64) -> u64 {
+    if n < 2 {
+        return 0;
+    }
+    let root = n.isqrt() as usize;
+
+    // Start with everything from 2 on, then remove the multiples of every prime p up to
+    // the root. `small[v]` counts up to v, `large[i]` counts up to n / i.
+    let mut small: Vec<u64> = (0..=root as u64).map(|v| v.saturating_sub(1)).collect();
+    let mut large: Vec<u64> = (0..=root as u64)
+        .map(|i| n.checked_div(i).map_or(0, |v| v - 1))
+        .collect();
+
+    for p in 2..=root {
+        // Not a prime, its multiples are gone already
+        if small[p] == small[p - 1] {
+            continue;
+        }
+        let below_p = small[p - 1];
+        let square = (p * p) as u64;
+
+        for i in 1..=root.min((n / square) as usize) {
+            let d = i * p;
+            let count = match d <= root {
+                true => large[d],
+                false => small[(n / d as u64) as usize],
+            };
+            large[i] -= count - below_p;
+        }
+        for v in (p * p..=root).rev() {
+            small[v] -= small[v / p] - below_p;
+        }
+    }
+
+    large[1]
+}
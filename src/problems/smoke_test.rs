@@ -0,0 +1,21 @@
+//! [Problem 0: Smoke Test](https://protohackers.com/problem/0), a TCP echo server.
+
+use std::future::Future;
+
+use crate::{serve_until, Config, Error, ShutdownSummary};
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    serve_until(
+        config,
+        |mut connection| async move {
+            let (mut reader, mut writer) = connection.stream.split();
+            tokio::io::copy(&mut reader, &mut writer).await?;
+            Ok(())
+        },
+        shutdown,
+    )
+    .await
+}
@@ -0,0 +1,584 @@
+//! [Problem 6: Speed Daemon](https://protohackers.com/problem/6), speed cameras report plates and dispatchers hand out tickets.
+
+use std::future::Future;
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use anyhow::Result;
+use tickets::TicketEvent;
+use tokio::io::{AsyncWriteExt, BufReader, BufWriter};
+use tokio::sync::mpsc;
+use tracing::{error, info, warn};
+use types::{ClientMessage, ClientState, Error, Heartbeat, ServerMessage};
+
+use crate::stream::TimedOut;
+use crate::{serve_until, Config, ShutdownSummary, Stream};
+
+pub mod types {
+    use anyhow::{bail, Context};
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    #[derive(Debug)]
+    pub enum ClientMessage {
+        Plate(Plate),
+        WantHeartbeat(WantHeartbeat),
+        IAmCamera(IAmCamera),
+        IAmDispatcher(IAmDispatcher),
+    }
+
+    impl ClientMessage {
+        pub async fn from_bytes<R: AsyncRead + Unpin>(
+            reader: &mut R,
+        ) -> anyhow::Result<ClientMessage> {
+            let type_byte = reader.read_u8().await.context("reading type byte")?;
+
+            match type_byte {
+                0x20 => {
+                    let plate_len = reader.read_u8().await?;
+
+                    let mut plate = vec![0u8; plate_len as usize];
+                    reader.read_exact(&mut plate).await?;
+
+                    let timestamp = reader.read_u32().await?;
+
+                    let plate = Plate {
+                        plate: String::from_utf8_lossy(&plate).to_string(),
+                        timestamp,
+                    };
+
+                    Ok(Self::Plate(plate))
+                }
+                0x40 => {
+                    let interval = reader.read_u32().await?;
+                    let want_heartbeat = WantHeartbeat { interval };
+                    Ok(Self::WantHeartbeat(want_heartbeat))
+                }
+                0x80 => {
+                    let road = reader.read_u16().await?;
+                    let mile = reader.read_u16().await?;
+                    let limit = reader.read_u16().await?;
+                    let i_am_camera = IAmCamera { road, mile, limit };
+                    Ok(Self::IAmCamera(i_am_camera))
+                }
+                0x81 => {
+                    let numroads = reader.read_u8().await?;
+                    let mut roads = Vec::with_capacity(numroads as usize);
+
+                    for _ in 0..(numroads as usize) {
+                        roads.push(reader.read_u16().await?);
+                    }
+
+                    let i_am_dispatcher = IAmDispatcher { roads };
+                    Ok(Self::IAmDispatcher(i_am_dispatcher))
+                }
+                _ => bail!("Unexpected client message type byte {type_byte}"),
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub enum ServerMessage {
+        Error(Error),
+        Ticket(Ticket),
+        Heartbeat(Heartbeat),
+    }
+
+    impl ServerMessage {
+        pub async fn to_bytes<W: AsyncWrite + Unpin>(&self, writer: &mut W) -> anyhow::Result<()> {
+            match self {
+                Self::Error(error) => {
+                    if error.msg.len() > 255 {
+                        bail!("Error messages may not be longer than 255 characters");
+                    }
+
+                    writer.write_u8(0x10).await?;
+                    writer.write_u8(error.msg.len() as u8).await?;
+                    writer.write_all(error.msg.as_bytes()).await?;
+                    Ok(())
+                }
+                Self::Ticket(ticket) => {
+                    if ticket.plate.len() > 255 {
+                        bail!("Plate string may not be longer than 255 characters");
+                    }
+
+                    writer.write_u8(0x21).await?;
+                    writer.write_u8(ticket.plate.len() as u8).await?;
+                    writer.write_all(ticket.plate.as_bytes()).await?;
+                    writer.write_u16(ticket.road).await?;
+                    writer.write_u16(ticket.mile1).await?;
+                    writer.write_u32(ticket.timestamp1).await?;
+                    writer.write_u16(ticket.mile2).await?;
+                    writer.write_u32(ticket.timestamp2).await?;
+                    writer.write_u16(ticket.speed).await?;
+                    Ok(())
+                }
+                Self::Heartbeat(_) => {
+                    writer.write_u8(0x41).await?;
+                    Ok(())
+                }
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Error {
+        msg: String,
+    }
+
+    impl Error {
+        pub fn with_msg(msg: impl ToString) -> Self {
+            Self {
+                msg: msg.to_string(),
+            }
+        }
+
+        pub fn msg(&self) -> &String {
+            &self.msg
+        }
+    }
+
+    #[derive(Debug)]
+    pub struct Plate {
+        pub plate: String,
+        pub timestamp: u32,
+    }
+
+    #[derive(Debug, Clone)]
+    pub struct Ticket {
+        pub plate: String,
+        pub road: u16,
+        pub mile1: u16,
+        pub timestamp1: u32,
+        pub mile2: u16,
+        pub timestamp2: u32,
+        /// (100x miles per hour)
+        pub speed: u16,
+    }
+
+    /// The server must now send Heartbeat messages to this client at the given interval,
+    /// which is specified in "deciseconds", of which there are 10 per second. (So an interval
+    /// of "25" would mean a Heartbeat message every 2.5 seconds).
+    /// The heartbeats help to assure the client that the server is still functioning,
+    /// even in the absence of any other communication.
+    /// An interval of 0 deciseconds means the client does not want to receive heartbeats (this is the default setting).
+    #[derive(Debug)]
+    pub struct WantHeartbeat {
+        pub interval: u32,
+    }
+
+    #[derive(Debug)]
+    pub struct Heartbeat();
+
+    #[derive(Debug, Clone, Copy)]
+    pub struct IAmCamera {
+        pub road: u16,
+        pub mile: u16,
+        /// in miles per hour
+        pub limit: u16,
+    }
+
+    #[derive(Debug)]
+    pub struct IAmDispatcher {
+        pub roads: Vec<u16>,
+    }
+
+    #[derive(Debug)]
+    pub enum ClientState {
+        Camera { state: IAmCamera },
+        Dispatcher { state: IAmDispatcher },
+        Connecting,
+    }
+}
+
+mod tickets {
+    use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+    use std::net::SocketAddr;
+
+    use tokio::sync::mpsc::{self, error::SendError};
+    use tracing::{info, warn};
+
+    use super::types::{IAmCamera, Plate, ServerMessage, Ticket};
+
+    const SECONDS_PER_DAY: u32 = 86_400;
+
+    #[derive(Debug)]
+    pub enum TicketEvent {
+        Observation(Plate, IAmCamera),
+        DispatcherConnected(SocketAddr, Vec<u16>, mpsc::Sender<ServerMessage>),
+        DispatcherDisconnected(SocketAddr, Vec<u16>),
+        /// The dispatcher that was waited for disconnected before it had room for the ticket
+        Undelivered(Ticket),
+    }
+
+    #[derive(Debug, Default)]
+    struct Road {
+        /// in miles per hour
+        speed_limit: u16,
+        /// plate -> (timestamp -> mile)
+        observations: HashMap<String, BTreeMap<u32, u16>>,
+        dispatchers: Vec<(SocketAddr, mpsc::Sender<ServerMessage>)>,
+        /// Tickets waiting for a dispatcher to connect
+        pending: VecDeque<Ticket>,
+    }
+
+    impl Road {
+        /// Records an observation and returns a ticket for every neighbouring
+        /// observation that shows the car going too fast.
+        fn observe(&mut self, plate: &str, timestamp: u32, mile: u16) -> Vec<Ticket> {
+            let observations = self.observations.entry(plate.to_string()).or_default();
+            // The first observation counts, the ticket might already be based on it
+            if observations.contains_key(&timestamp) {
+                return Vec::new();
+            }
+            observations.insert(timestamp, mile);
+
+            let previous = observations.range(..timestamp).next_back();
+            let next = observations.range(timestamp + 1..).next();
+
+            [previous, next]
+                .into_iter()
+                .flatten()
+                .map(|(&other_timestamp, &other_mile)| {
+                    if other_timestamp < timestamp {
+                        ((other_mile, other_timestamp), (mile, timestamp))
+                    } else {
+                        ((mile, timestamp), (other_mile, other_timestamp))
+                    }
+                })
+                .filter_map(|((mile1, timestamp1), (mile2, timestamp2))| {
+                    let speed = average_speed(mile1, timestamp1, mile2, timestamp2);
+                    if speed < self.speed_limit as u64 * 100 + 50 {
+                        return None;
+                    }
+
+                    Some(Ticket {
+                        plate: plate.to_string(),
+                        road: 0,
+                        mile1,
+                        timestamp1,
+                        mile2,
+                        timestamp2,
+                        speed: speed.min(u16::MAX as u64) as u16,
+                    })
+                })
+                .collect()
+        }
+
+        /// Hands the ticket to the first dispatcher that has room for it. If all of them are
+        /// busy, waits for the first one in the background and dispatches the ticket again
+        /// through `retry` if it disconnects. Without any dispatcher the ticket is queued until
+        /// one shows up.
+        fn dispatch(&mut self, ticket: Ticket, retry: &mpsc::WeakSender<TicketEvent>) {
+            self.dispatchers.retain(|(_, tx)| !tx.is_closed());
+
+            for (addr, tx) in self.dispatchers.iter() {
+                if tx.try_send(ServerMessage::Ticket(ticket.clone())).is_ok() {
+                    info!("Dispatched {ticket:?} to {addr}");
+                    return;
+                }
+            }
+
+            if let Some((addr, tx)) = self.dispatchers.first() {
+                info!(
+                    "Dispatchers for road {} are busy, waiting for {addr}",
+                    ticket.road
+                );
+                let (tx, retry) = (tx.clone(), retry.clone());
+                tokio::spawn(async move {
+                    let Err(SendError(ServerMessage::Ticket(ticket))) =
+                        tx.send(ServerMessage::Ticket(ticket)).await
+                    else {
+                        return;
+                    };
+                    if let Some(retry) = retry.upgrade() {
+                        let _ = retry.send(TicketEvent::Undelivered(ticket)).await;
+                    }
+                });
+                return;
+            }
+
+            info!(
+                "No dispatcher for road {}, queueing {ticket:?}",
+                ticket.road
+            );
+            self.pending.push_back(ticket);
+        }
+    }
+
+    /// Average speed between two observations in 100x miles per hour
+    fn average_speed(mile1: u16, timestamp1: u32, mile2: u16, timestamp2: u32) -> u64 {
+        let distance = mile1.abs_diff(mile2) as u64;
+        let duration = timestamp1.abs_diff(timestamp2) as u64;
+
+        // rounded to the nearest 0.01 mph
+        (distance * 3600 * 100 + duration / 2) / duration
+    }
+
+    /// `retry` is the sender of `rx`, weak so the loop still ends once all clients are gone
+    pub async fn handle_tickets(
+        mut rx: mpsc::Receiver<TicketEvent>,
+        retry: mpsc::WeakSender<TicketEvent>,
+    ) {
+        let mut roads = HashMap::<u16, Road>::new();
+        // plate -> days on which a ticket was already issued
+        let mut ticketed_days = HashMap::<String, HashSet<u32>>::new();
+
+        while let Some(event) = rx.recv().await {
+            match event {
+                TicketEvent::Observation(plate, camera) => {
+                    let road = roads.entry(camera.road).or_default();
+                    road.speed_limit = camera.limit;
+
+                    for mut ticket in road.observe(&plate.plate, plate.timestamp, camera.mile) {
+                        ticket.road = camera.road;
+
+                        let days = ticket.timestamp1 / SECONDS_PER_DAY
+                            ..=ticket.timestamp2 / SECONDS_PER_DAY;
+                        let already_ticketed =
+                            ticketed_days.entry(ticket.plate.clone()).or_default();
+                        if days.clone().any(|day| already_ticketed.contains(&day)) {
+                            info!(
+                                "Already ticketed {} on one of the days {days:?}",
+                                ticket.plate
+                            );
+                            continue;
+                        }
+                        already_ticketed.extend(days);
+
+                        road.dispatch(ticket, &retry);
+                    }
+                }
+                TicketEvent::DispatcherConnected(addr, dispatcher_roads, tx) => {
+                    for road in dispatcher_roads {
+                        let road = roads.entry(road).or_default();
+                        road.dispatchers.push((addr, tx.clone()));
+
+                        let pending: Vec<Ticket> = road.pending.drain(..).collect();
+                        for ticket in pending {
+                            road.dispatch(ticket, &retry);
+                        }
+                    }
+                }
+                TicketEvent::DispatcherDisconnected(addr, dispatcher_roads) => {
+                    for road in dispatcher_roads {
+                        match roads.get_mut(&road) {
+                            Some(road) => road.dispatchers.retain(|(other, _)| *other != addr),
+                            None => warn!("Dispatcher {addr} was never registered for road {road}"),
+                        }
+                    }
+                }
+                TicketEvent::Undelivered(ticket) => {
+                    roads
+                        .entry(ticket.road)
+                        .or_default()
+                        .dispatch(ticket, &retry);
+                }
+            }
+        }
+    }
+}
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, crate::Error> {
+    let (ticket_tx, ticket_rx) = mpsc::channel::<TicketEvent>(1024);
+    tokio::spawn(tickets::handle_tickets(ticket_rx, ticket_tx.downgrade()));
+
+    serve_until(
+        config,
+        move |connection| async move {
+            handle_client(connection.stream, connection.addr, ticket_tx)
+                .await
+                .map_err(|err| format!("{err:#}").into())
+        },
+        shutdown,
+    )
+    .await
+}
+
+async fn handle_client(
+    stream: Stream,
+    addr: SocketAddr,
+    ticket_tx: mpsc::Sender<TicketEvent>,
+) -> Result<()> {
+    let stats = stream.stats().clone();
+    let (reader, writer) = stream.into_split();
+    let (mut reader, mut writer) = (BufReader::new(reader), BufWriter::new(writer));
+
+    let (tx, mut rx) = mpsc::channel::<ServerMessage>(512);
+    let writer_stats = stats.clone();
+    let writer_task = tokio::spawn(async move {
+        while let Some(message) = rx.recv().await {
+            if let ServerMessage::Error(error) = &message {
+                warn!("Sending error: {}", error.msg());
+            }
+            match message.to_bytes(&mut writer).await {
+                Ok(()) => {
+                    writer_stats.message_out();
+                    if let ServerMessage::Ticket(_) = message {
+                        writer_stats.count("tickets_issued", 1);
+                    }
+                }
+                Err(err) => error!("Could not send message: {}, {:?}", err.root_cause(), err),
+            }
+            if let Err(err) = writer.flush().await {
+                error!("Could not flush: {err}");
+                break;
+            }
+        }
+    });
+
+    let mut heartbeat_running = false;
+    let mut heartbeat_task = None;
+    let mut client_state = ClientState::Connecting;
+
+    let result = async {
+        loop {
+            let client_msg = match types::ClientMessage::from_bytes(&mut reader).await {
+                Ok(client_msg) => client_msg,
+                Err(err) => {
+                    let timed_out = err
+                        .downcast_ref::<std::io::Error>()
+                        .and_then(|err| TimedOut::find(err));
+                    if let Some(timed_out) = timed_out {
+                        info!(%timed_out, "timed out");
+                        tx.send(ServerMessage::Error(Error::with_msg(timed_out)))
+                            .await?;
+                        break;
+                    }
+                    let disconnected = err
+                        .downcast_ref::<std::io::Error>()
+                        .is_some_and(|err| err.kind() == ErrorKind::UnexpectedEof);
+                    if !disconnected {
+                        warn!("Illegal message: {err:?}");
+                        tx.send(ServerMessage::Error(Error::with_msg("illegal msg")))
+                            .await?;
+                    }
+                    break;
+                }
+            };
+            stats.message_in();
+
+            match client_msg {
+                ClientMessage::Plate(plate) => match client_state {
+                    ClientState::Camera { state } => {
+                        info!("Received {plate:?} on {state:?}");
+                        ticket_tx
+                            .send(TicketEvent::Observation(plate, state))
+                            .await?;
+                    }
+                    _ => {
+                        tx.send(ServerMessage::Error(Error::with_msg(
+                            "You are not a camera",
+                        )))
+                        .await?;
+                        break;
+                    }
+                },
+                ClientMessage::WantHeartbeat(hearbeat) => {
+                    if heartbeat_running {
+                        tx.send(ServerMessage::Error(Error::with_msg(
+                            "Cannot request multiple heartbeats",
+                        )))
+                        .await?;
+                        break;
+                    }
+
+                    heartbeat_running = true;
+
+                    // An interval of 0 means no heartbeats at all
+                    if hearbeat.interval == 0 {
+                        continue;
+                    }
+
+                    let duration = hearbeat.interval as u64 * 100;
+                    let duration = Duration::from_millis(duration);
+
+                    let tx = tx.clone();
+                    heartbeat_task = Some(tokio::spawn(async move {
+                        let mut interval = tokio::time::interval(duration);
+                        loop {
+                            interval.tick().await;
+                            if tx
+                                .send(ServerMessage::Heartbeat(Heartbeat()))
+                                .await
+                                .is_err()
+                            {
+                                break;
+                            };
+                        }
+                    }));
+                }
+                ClientMessage::IAmCamera(i_am_camera) => match client_state {
+                    ClientState::Camera { .. } => {
+                        tx.send(ServerMessage::Error(Error::with_msg(
+                            "You are already a camera",
+                        )))
+                        .await?;
+                        break;
+                    }
+                    ClientState::Dispatcher { .. } => {
+                        tx.send(ServerMessage::Error(Error::with_msg(
+                            "You are a dispatcher, not a camera",
+                        )))
+                        .await?;
+                        break;
+                    }
+                    ClientState::Connecting => {
+                        client_state = ClientState::Camera { state: i_am_camera };
+                        info!("Client has identified as camera: {client_state:?}");
+                    }
+                },
+                ClientMessage::IAmDispatcher(i_am_dispatcher) => match client_state {
+                    ClientState::Camera { .. } => {
+                        tx.send(ServerMessage::Error(Error::with_msg(
+                            "You are a camera, not a dispatcher",
+                        )))
+                        .await?;
+                        break;
+                    }
+                    ClientState::Dispatcher { .. } => {
+                        tx.send(ServerMessage::Error(Error::with_msg(
+                            "You are already a dispatcher",
+                        )))
+                        .await?;
+                        break;
+                    }
+                    ClientState::Connecting => {
+                        ticket_tx
+                            .send(TicketEvent::DispatcherConnected(
+                                addr,
+                                i_am_dispatcher.roads.clone(),
+                                tx.clone(),
+                            ))
+                            .await?;
+                        client_state = ClientState::Dispatcher {
+                            state: i_am_dispatcher,
+                        };
+                        info!("Client has identified as dispatcher: {client_state:?}");
+                    }
+                },
+            }
+        }
+        Ok::<(), anyhow::Error>(())
+    }
+    .await;
+
+    if let Some(heartbeat_task) = heartbeat_task {
+        heartbeat_task.abort();
+    }
+    if let ClientState::Dispatcher { state } = client_state {
+        ticket_tx
+            .send(TicketEvent::DispatcherDisconnected(addr, state.roads))
+            .await?;
+    }
+
+    // Let the writer send everything that is still queued, then close the socket
+    drop(tx);
+    writer_task.await?;
+
+    info!("Disconnect");
+    result
+}
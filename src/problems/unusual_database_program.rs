@@ -0,0 +1,67 @@
+//! [Problem 4: Unusual Database Program](https://protohackers.com/problem/4), a key-value store over UDP.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tracing::debug;
+
+use crate::udp::UdpOptions;
+use crate::{udp, Config, Error, ShutdownSummary};
+
+/// Requests and replies have to be shorter than 1000 bytes
+const MAX_DATAGRAM_SIZE: usize = 999;
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    let database = Arc::new(Mutex::new(HashMap::<Vec<u8>, Vec<u8>>::with_capacity(
+        10_000,
+    )));
+    let options = UdpOptions {
+        max_datagram_size: MAX_DATAGRAM_SIZE,
+        ..UdpOptions::default()
+    };
+
+    udp::serve_until(
+        config,
+        options,
+        move |datagram| async move {
+            let data = datagram.data;
+            debug!(bytes = data.len(), "datagram");
+            datagram.stats.count("datagrams_handled", 1);
+
+            if let Some(first_space_idx) = data.iter().position(|&byte| byte == b'=') {
+                // Insert
+                let (key, value) = data.split_at(first_space_idx);
+                database
+                    .lock()
+                    .unwrap()
+                    .insert(key.to_owned(), value.to_owned());
+            } else {
+                // Retrieve
+                let key = data;
+                if key == b"version" {
+                    datagram.reply.send(b"version=norom - v69.420").await?;
+                    return Ok(());
+                }
+                let mut value = database
+                    .lock()
+                    .unwrap()
+                    .get(&key)
+                    .cloned()
+                    // Stored values start with their `=`, missing keys get an empty one
+                    .unwrap_or_else(|| b"=".to_vec());
+
+                let mut reply = key;
+                reply.append(&mut value);
+                datagram.reply.send(&reply).await?;
+            }
+
+            Ok(())
+        },
+        shutdown,
+    )
+    .await
+}
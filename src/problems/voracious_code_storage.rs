@@ -0,0 +1,293 @@
+//! [Problem 10: Voracious Code Storage](https://protohackers.com/problem/10), a versioned file store with a line based protocol.
+
+use std::collections::BTreeMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncWriteExt, BufWriter};
+use tracing::{info, warn};
+
+use crate::codec::{FrameError, FrameReader, Framing};
+use crate::{serve_until, Config, Error, ShutdownSummary};
+
+/// Larger files are refused and end the connection, their content is never read
+pub const MAX_FILE_SIZE: usize = 1024 * 1024;
+
+/// file name -> all revisions of the file, oldest first. Shared by all connections.
+#[derive(Debug, Clone, Default)]
+struct Files(Arc<Mutex<BTreeMap<String, Vec<Vec<u8>>>>>);
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Command {
+    Help,
+    Get {
+        file: String,
+        revision: Option<usize>,
+    },
+    Put {
+        file: String,
+        length: usize,
+    },
+    List {
+        dir: String,
+    },
+}
+
+/// Everything the client can get wrong. Each variant maps to the exact response.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Invalid {
+    /// Ends the connection
+    IllegalMethod(String),
+    Usage(&'static str),
+    IllegalFileName,
+    IllegalDirName,
+    NoSuchRevision,
+}
+
+impl Invalid {
+    pub fn response(&self) -> String {
+        match self {
+            Self::IllegalMethod(method) => format!("ERR illegal method: {method}"),
+            Self::Usage(usage) => format!("ERR usage: {usage}"),
+            Self::IllegalFileName => "ERR illegal file name".to_string(),
+            Self::IllegalDirName => "ERR illegal dir name".to_string(),
+            Self::NoSuchRevision => "ERR no such revision".to_string(),
+        }
+    }
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, Invalid> {
+        let args: Vec<&str> = line.split_ascii_whitespace().collect();
+        let Some(method) = args.first() else {
+            return Err(Invalid::IllegalMethod(String::new()));
+        };
+
+        match (method.to_ascii_uppercase().as_str(), &args[1..]) {
+            ("HELP", _) => Ok(Self::Help),
+            ("GET", [file]) | ("GET", [file, _]) => {
+                if !is_valid_file_name(file) {
+                    return Err(Invalid::IllegalFileName);
+                }
+                let revision = match args.get(2) {
+                    Some(revision) => {
+                        Some(parse_revision(revision).ok_or(Invalid::NoSuchRevision)?)
+                    }
+                    None => None,
+                };
+                Ok(Self::Get {
+                    file: file.to_string(),
+                    revision,
+                })
+            }
+            ("GET", _) => Err(Invalid::Usage("GET file [revision]")),
+            ("PUT", [file, length]) => {
+                if !is_valid_file_name(file) {
+                    return Err(Invalid::IllegalFileName);
+                }
+                Ok(Self::Put {
+                    file: file.to_string(),
+                    // the reference implementation treats garbage as 0
+                    length: length.parse().unwrap_or(0),
+                })
+            }
+            ("PUT", _) => Err(Invalid::Usage("PUT file length newline data")),
+            ("LIST", [dir]) => {
+                if !is_valid_dir_name(dir) {
+                    return Err(Invalid::IllegalDirName);
+                }
+                Ok(Self::List {
+                    dir: dir.to_string(),
+                })
+            }
+            ("LIST", _) => Err(Invalid::Usage("LIST dir")),
+            _ => Err(Invalid::IllegalMethod(method.to_string())),
+        }
+    }
+}
+
+fn is_valid_dir_name(name: &str) -> bool {
+    name.starts_with('/')
+        && !name.contains("//")
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '/' | '.' | '_' | '-'))
+}
+
+fn is_valid_file_name(name: &str) -> bool {
+    is_valid_dir_name(name) && !name.ends_with('/')
+}
+
+fn is_text(content: &[u8]) -> bool {
+    content
+        .iter()
+        .all(|byte| byte.is_ascii_graphic() || byte.is_ascii_whitespace())
+}
+
+/// Accepts `r3` as well as `3`. Revisions start at 1.
+fn parse_revision(revision: &str) -> Option<usize> {
+    let revision = revision.strip_prefix('r').unwrap_or(revision);
+    revision.parse().ok().filter(|&revision| revision > 0)
+}
+
+impl Files {
+    /// Returns the revision of the file. Storing the same content as the
+    /// latest revision again does not create a new revision.
+    fn put(&self, file: String, content: Vec<u8>) -> usize {
+        let mut files = self.0.lock().unwrap();
+        let revisions = files.entry(file).or_default();
+
+        if revisions.last() != Some(&content) {
+            revisions.push(content);
+        }
+        revisions.len()
+    }
+
+    fn get(&self, file: &str, revision: Option<usize>) -> Result<Vec<u8>, &'static str> {
+        let files = self.0.lock().unwrap();
+        let revisions = files.get(file).ok_or("ERR no such file")?;
+
+        let content = match revision {
+            Some(revision) => revisions.get(revision - 1),
+            None => revisions.last(),
+        };
+        content.cloned().ok_or("ERR no such revision")
+    }
+
+    /// Directory style listing, sorted by name. Sub directories end in a `/`.
+    fn list(&self, dir: &str) -> Vec<String> {
+        let prefix = match dir.ends_with('/') {
+            true => dir.to_string(),
+            false => format!("{dir}/"),
+        };
+
+        let files = self.0.lock().unwrap();
+        let mut entries = BTreeMap::new();
+
+        for (file, revisions) in files.range(prefix.clone()..) {
+            let Some(rest) = file.strip_prefix(&prefix) else {
+                break;
+            };
+
+            match rest.split_once('/') {
+                Some((sub_dir, _)) => entries.insert(format!("{sub_dir}/"), "DIR".to_string()),
+                None => entries.insert(rest.to_string(), format!("r{}", revisions.len())),
+            };
+        }
+
+        entries
+            .into_iter()
+            .map(|(name, info)| format!("{name} {info}"))
+            .collect()
+    }
+}
+
+pub async fn run(
+    config: &Config,
+    shutdown: impl Future<Output = ()>,
+) -> Result<ShutdownSummary, Error> {
+    let files = Files::default();
+    let framing = Framing::Line {
+        max_len: config.max_line_length,
+    };
+
+    serve_until(
+        config,
+        move |mut connection| async move {
+            let stats = connection.stats();
+            let (reader, writer) = connection.stream.split();
+            let (mut reader, mut writer) =
+                (FrameReader::new(reader, framing), BufWriter::new(writer));
+
+            loop {
+                writer.write_all(b"READY\n").await?;
+                writer.flush().await?;
+
+                let line = match reader.read_frame().await {
+                    Ok(Some(line)) => line,
+                    Ok(None) | Err(FrameError::Truncated { .. }) => break,
+                    Err(err @ FrameError::TooLong { .. }) => {
+                        warn!("{}: {err}", connection.addr);
+                        stats.count("lines_too_long", 1);
+                        writer.write_all(b"ERR line too long\n").await?;
+                        writer.flush().await?;
+                        stats.message_out();
+                        break;
+                    }
+                    Err(err) => return Err(err.into()),
+                };
+                stats.message_in();
+                let request = String::from_utf8_lossy(&line);
+                info!("{}: {:?}", connection.addr, request);
+
+                let command = match Command::parse(&request) {
+                    Ok(command) => command,
+                    Err(invalid) => {
+                        warn!("{}: {invalid:?}", connection.addr);
+                        let response = format!("{}\n", invalid.response());
+                        writer.write_all(response.as_bytes()).await?;
+                        stats.message_out();
+
+                        if let Invalid::IllegalMethod(_) = invalid {
+                            writer.flush().await?;
+                            break;
+                        }
+                        continue;
+                    }
+                };
+
+                match command {
+                    Command::Help => {
+                        writer.write_all(b"OK usage: HELP|GET|PUT|LIST\n").await?;
+                    }
+                    Command::Get { file, revision } => match files.get(&file, revision) {
+                        Ok(content) => {
+                            writer
+                                .write_all(format!("OK {}\n", content.len()).as_bytes())
+                                .await?;
+                            writer.write_all(&content).await?;
+                        }
+                        Err(response) => {
+                            writer.write_all(format!("{response}\n").as_bytes()).await?;
+                        }
+                    },
+                    Command::Put { file, length } => {
+                        if length > MAX_FILE_SIZE {
+                            warn!("{}: refusing {length} bytes", connection.addr);
+                            writer.write_all(b"ERR file too large\n").await?;
+                            writer.flush().await?;
+                            stats.message_out();
+                            break;
+                        }
+                        let content = reader.read_bytes(length).await?;
+
+                        if !is_text(&content) {
+                            writer.write_all(b"ERR text files only\n").await?;
+                            stats.message_out();
+                            continue;
+                        }
+
+                        let revision = files.put(file, content);
+                        writer
+                            .write_all(format!("OK r{revision}\n").as_bytes())
+                            .await?;
+                    }
+                    Command::List { dir } => {
+                        let entries = files.list(&dir);
+                        writer
+                            .write_all(format!("OK {}\n", entries.len()).as_bytes())
+                            .await?;
+                        for entry in entries {
+                            writer.write_all(format!("{entry}\n").as_bytes()).await?;
+                        }
+                    }
+                }
+                stats.message_out();
+            }
+
+            Ok(())
+        },
+        shutdown,
+    )
+    .await
+}
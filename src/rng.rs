@@ -0,0 +1,57 @@
+//! xorshift64*, good enough to make a mess and to pick test data. Shared by all tasks
+//! without locking, so the same seed only repeats a run if the tasks take turns the same way.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use tracing::info;
+
+#[derive(Debug)]
+pub(crate) struct Rng(AtomicU64);
+
+impl Rng {
+    /// `None` picks a seed from the clock, the seed is logged for `purpose` either way
+    pub(crate) fn new(seed: Option<u64>, purpose: &str) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |since| since.as_nanos() as u64)
+        });
+        info!(seed, "{purpose} seed");
+        // Zero would stay zero forever
+        Self(AtomicU64::new(seed.max(1)))
+    }
+
+    pub(crate) fn next(&self) -> u64 {
+        let mut x = self.0.load(Ordering::Relaxed);
+        loop {
+            let mut next = x;
+            next ^= next >> 12;
+            next ^= next << 25;
+            next ^= next >> 27;
+            match self
+                .0
+                .compare_exchange_weak(x, next, Ordering::Relaxed, Ordering::Relaxed)
+            {
+                Ok(_) => return next.wrapping_mul(0x2545_f491_4f6c_dd1d),
+                Err(current) => x = current,
+            }
+        }
+    }
+
+    /// In `1..=max`
+    pub(crate) fn between_one_and(&self, max: usize) -> usize {
+        1 + (self.next() % max.max(1) as u64) as usize
+    }
+
+    pub(crate) fn percent(&self, percent: f64) -> bool {
+        (self.next() % 10_000) as f64 / 100.0 < percent
+    }
+
+    pub(crate) fn up_to(&self, max: Duration) -> Duration {
+        match max.as_nanos() as u64 {
+            0 => Duration::ZERO,
+            max => Duration::from_nanos(self.next() % (max + 1)),
+        }
+    }
+}
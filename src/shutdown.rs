@@ -0,0 +1,124 @@
+//! Stopping servers without cutting off connections in the middle of a write.
+
+use std::fmt;
+use std::future::Future;
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+use std::time::Duration;
+
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+/// Completes on the first SIGINT (Ctrl-C) or SIGTERM
+pub async fn signal() {
+    let ctrl_c = async {
+        if let Err(err) = tokio::signal::ctrl_c().await {
+            error!(error = %err, "could not listen for Ctrl-C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(err) => {
+                error!(error = %err, "could not listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+}
+
+/// What happened to the connections that were still running when the server shut down
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Summary {
+    /// Finished on their own before the deadline
+    pub drained: usize,
+    /// Still running at the deadline and cancelled
+    pub aborted: usize,
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} connections drained, {} aborted",
+            self.drained, self.aborted
+        )
+    }
+}
+
+/// The handler tasks of a server together with the number of them that are running
+pub(crate) struct Handlers {
+    running: Arc<AtomicUsize>,
+    tasks: JoinSet<()>,
+}
+
+/// Counts a handler as running for as long as it is alive, including when it gets aborted
+struct RunningGuard(Arc<AtomicUsize>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl Handlers {
+    pub(crate) fn new() -> Self {
+        Self {
+            running: Arc::new(AtomicUsize::new(0_usize)),
+            tasks: JoinSet::new(),
+        }
+    }
+
+    /// Spawns the handler and returns how many are running now, including this one
+    pub(crate) fn spawn<F>(&mut self, handler: F) -> usize
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        // forget about the ones that are done already
+        while self.tasks.try_join_next().is_some() {}
+
+        let currently_running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+        let guard = RunningGuard(self.running.clone());
+        self.tasks.spawn(async move {
+            let _guard = guard;
+            handler.await;
+        });
+        currently_running
+    }
+
+    /// Waits for the running handlers to finish and aborts whatever is left after the deadline
+    pub(crate) async fn drain(mut self, deadline: Duration) -> Summary {
+        let in_flight = self.running.load(Ordering::SeqCst);
+        if in_flight > 0 {
+            info!(?deadline, in_flight, "waiting for connections to finish");
+        }
+
+        let _ = tokio::time::timeout(deadline, async {
+            while self.tasks.join_next().await.is_some() {}
+        })
+        .await;
+
+        let aborted = self.running.load(Ordering::SeqCst);
+        self.tasks.shutdown().await;
+
+        Summary {
+            drained: in_flight.saturating_sub(aborted),
+            aborted,
+        }
+    }
+}
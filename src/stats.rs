@@ -0,0 +1,262 @@
+//! Numbers about the connections of every server in this process, for capacity planning.
+//!
+//! Every server registers a [`ServerStats`] and every connection gets a [`ConnectionStats`]
+//! that adds to the totals of its server as it goes. [`snapshot`] returns the totals of all
+//! servers at any time.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+use tracing::info;
+
+use crate::stream::TimedOut;
+
+static SERVERS: Mutex<Vec<ServerStats>> = Mutex::new(Vec::new());
+
+/// The totals of all servers in this process
+pub fn snapshot() -> Vec<Snapshot> {
+    SERVERS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(ServerStats::snapshot)
+        .collect()
+}
+
+/// Why a connection ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CloseReason {
+    /// The handler returned without an error
+    Closed,
+    /// Over a connection limit
+    Rejected,
+    TimedOut(TimedOut),
+    /// The handler returned an error
+    Failed,
+    /// Still running when the server shut down
+    Aborted,
+}
+
+impl fmt::Display for CloseReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Closed => write!(f, "closed"),
+            Self::Rejected => write!(f, "rejected"),
+            Self::TimedOut(timed_out) => write!(f, "{timed_out}"),
+            Self::Failed => write!(f, "failed"),
+            Self::Aborted => write!(f, "aborted"),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counters {
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    messages_in: AtomicU64,
+    messages_out: AtomicU64,
+    /// Protocol specific, e.g. how many numbers were checked for primality
+    custom: Mutex<BTreeMap<&'static str, u64>>,
+}
+
+#[derive(Debug)]
+struct ServerInner {
+    name: String,
+    started: Instant,
+    accepted: AtomicU64,
+    active: AtomicU64,
+    closed: AtomicU64,
+    rejected: AtomicU64,
+    timed_out: AtomicU64,
+    failed: AtomicU64,
+    aborted: AtomicU64,
+    counters: Counters,
+}
+
+/// The totals of a single server
+#[derive(Debug, Clone)]
+pub struct ServerStats(Arc<ServerInner>);
+
+impl ServerStats {
+    /// Creates the stats of a server and adds them to the ones returned by [`snapshot`]
+    pub fn register(name: impl Into<String>) -> Self {
+        let stats = Self(Arc::new(ServerInner {
+            name: name.into(),
+            started: Instant::now(),
+            accepted: AtomicU64::new(0),
+            active: AtomicU64::new(0),
+            closed: AtomicU64::new(0),
+            rejected: AtomicU64::new(0),
+            timed_out: AtomicU64::new(0),
+            failed: AtomicU64::new(0),
+            aborted: AtomicU64::new(0),
+            counters: Counters::default(),
+        }));
+        SERVERS.lock().unwrap().push(stats.clone());
+        stats
+    }
+
+    /// Counts a new connection as accepted and active
+    pub fn connection(&self) -> Arc<ConnectionStats> {
+        self.0.accepted.fetch_add(1, Ordering::Relaxed);
+        self.0.active.fetch_add(1, Ordering::Relaxed);
+        Arc::new(ConnectionStats {
+            server: self.clone(),
+            connected: Instant::now(),
+            closed: AtomicBool::new(false),
+            counters: Counters::default(),
+        })
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let server = &self.0;
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        Snapshot {
+            name: server.name.clone(),
+            uptime: server.started.elapsed(),
+            accepted: load(&server.accepted),
+            active: load(&server.active),
+            closed: load(&server.closed),
+            rejected: load(&server.rejected),
+            timed_out: load(&server.timed_out),
+            failed: load(&server.failed),
+            aborted: load(&server.aborted),
+            bytes_in: load(&server.counters.bytes_in),
+            bytes_out: load(&server.counters.bytes_out),
+            messages_in: load(&server.counters.messages_in),
+            messages_out: load(&server.counters.messages_out),
+            counters: server
+                .counters
+                .custom
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|(&name, &count)| (name, count))
+                .collect(),
+        }
+    }
+}
+
+/// The totals of a server at one point in time
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snapshot {
+    pub name: String,
+    pub uptime: Duration,
+    pub accepted: u64,
+    pub active: u64,
+    /// The ones that ended without an error. The ones after it count the other close reasons.
+    pub closed: u64,
+    pub rejected: u64,
+    pub timed_out: u64,
+    pub failed: u64,
+    pub aborted: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub messages_in: u64,
+    pub messages_out: u64,
+    pub counters: BTreeMap<&'static str, u64>,
+}
+
+/// The numbers of a single connection. Everything counted here is added
+/// to the totals of the server right away.
+///
+/// A connection that is dropped without being closed counts as [`CloseReason::Aborted`].
+#[derive(Debug)]
+pub struct ConnectionStats {
+    server: ServerStats,
+    connected: Instant,
+    closed: AtomicBool,
+    counters: Counters,
+}
+
+/// The numbers of a single connection at one point in time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionSummary {
+    pub duration: Duration,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub messages_in: u64,
+    pub messages_out: u64,
+}
+
+impl ConnectionStats {
+    fn add(&self, counter: impl Fn(&Counters) -> &AtomicU64, n: u64) {
+        counter(&self.counters).fetch_add(n, Ordering::Relaxed);
+        counter(&self.server.0.counters).fetch_add(n, Ordering::Relaxed);
+    }
+
+    pub fn message_in(&self) {
+        self.add(|counters| &counters.messages_in, 1);
+    }
+
+    pub fn message_out(&self) {
+        self.add(|counters| &counters.messages_out, 1);
+    }
+
+    /// Adds to a protocol specific counter. `name` is snake_case, it becomes part of the
+    /// metric name, see [`metrics`](crate::metrics).
+    pub fn count(&self, name: &'static str, n: u64) {
+        for counters in [&self.counters, &self.server.0.counters] {
+            *counters.custom.lock().unwrap().entry(name).or_default() += n;
+        }
+    }
+
+    pub(crate) fn add_bytes_in(&self, n: u64) {
+        self.add(|counters| &counters.bytes_in, n);
+    }
+
+    pub(crate) fn add_bytes_out(&self, n: u64) {
+        self.add(|counters| &counters.bytes_out, n);
+    }
+
+    pub fn summary(&self) -> ConnectionSummary {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        ConnectionSummary {
+            duration: self.connected.elapsed(),
+            bytes_in: load(&self.counters.bytes_in),
+            bytes_out: load(&self.counters.bytes_out),
+            messages_in: load(&self.counters.messages_in),
+            messages_out: load(&self.counters.messages_out),
+        }
+    }
+
+    /// Counts the connection as ended and logs the `disconnect` event.
+    /// Only the first call counts.
+    pub(crate) fn close(&self, reason: CloseReason) {
+        if self.closed.swap(true, Ordering::Relaxed) {
+            return;
+        }
+
+        let summary = self.summary();
+        info!(
+            %reason,
+            duration = ?summary.duration,
+            bytes_in = summary.bytes_in,
+            bytes_out = summary.bytes_out,
+            messages_in = summary.messages_in,
+            messages_out = summary.messages_out,
+            "disconnect"
+        );
+
+        let server = &self.server.0;
+        server.active.fetch_sub(1, Ordering::Relaxed);
+        let counter = match reason {
+            CloseReason::Closed => &server.closed,
+            CloseReason::Rejected => &server.rejected,
+            CloseReason::TimedOut(_) => &server.timed_out,
+            CloseReason::Failed => &server.failed,
+            CloseReason::Aborted => &server.aborted,
+        };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for ConnectionStats {
+    fn drop(&mut self) {
+        self.close(CloseReason::Aborted);
+    }
+}
@@ -0,0 +1,267 @@
+//! The stream of a [`Connection`](crate::Connection): a [`TcpStream`] with timeouts
+//! that counts the bytes going through it.
+//!
+//! Timeouts only fire while the handler waits for the client. Once one of them passes,
+//! reads fail with an [`io::Error`] of kind [`TimedOut`](io::ErrorKind::TimedOut) that carries
+//! a [`TimedOut`]. So do writes that have to wait because the client does not read, a client
+//! cannot keep the handler stuck by doing nothing at all. Writes that fit into the socket buffer
+//! still go through, so the handler can say goodbye in whatever way its protocol wants before
+//! it returns and the socket is closed.
+
+use std::fmt;
+use std::future::Future;
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::TcpStream;
+use tokio::time::{Instant, Sleep};
+
+use crate::stats::ConnectionStats;
+
+pub type ReadHalf<'a> = tokio::io::ReadHalf<&'a mut Stream>;
+pub type WriteHalf<'a> = tokio::io::WriteHalf<&'a mut Stream>;
+pub type OwnedReadHalf = tokio::io::ReadHalf<Stream>;
+pub type OwnedWriteHalf = tokio::io::WriteHalf<Stream>;
+
+/// `None` disables a timeout
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Timeouts {
+    /// Nothing was read or written for this long
+    pub idle: Option<Duration>,
+    /// Nothing was read this long after connecting
+    pub first_byte: Option<Duration>,
+    /// This long after connecting, no matter what
+    pub session: Option<Duration>,
+}
+
+/// Which timeout made a read fail
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimedOut {
+    Idle,
+    FirstByte,
+    Session,
+}
+
+impl TimedOut {
+    /// Finds the timeout anywhere in the chain of `err`
+    pub fn find(err: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        let mut err = Some(err);
+        while let Some(current) = err {
+            if let Some(timed_out) = current.downcast_ref::<Self>() {
+                return Some(*timed_out);
+            }
+            // The source of an io::Error is the source of its payload, not the payload itself
+            if let Some(timed_out) = current
+                .downcast_ref::<io::Error>()
+                .and_then(|err| err.get_ref())
+                .and_then(|payload| payload.downcast_ref::<Self>())
+            {
+                return Some(*timed_out);
+            }
+            err = current.source();
+        }
+        None
+    }
+}
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Idle => write!(f, "idle timeout"),
+            Self::FirstByte => write!(f, "no data received in time"),
+            Self::Session => write!(f, "session timeout"),
+        }
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+impl From<TimedOut> for io::Error {
+    fn from(timed_out: TimedOut) -> Self {
+        io::Error::new(io::ErrorKind::TimedOut, timed_out)
+    }
+}
+
+pub struct Stream {
+    inner: TcpStream,
+    stats: Arc<ConnectionStats>,
+    timeouts: Timeouts,
+    connected: Instant,
+    last_activity: Instant,
+    received_any: bool,
+    // Separate timers, the halves of a split stream are polled by different tasks
+    read_timer: Pin<Box<Sleep>>,
+    write_timer: Pin<Box<Sleep>>,
+}
+
+impl Stream {
+    pub fn new(inner: TcpStream, timeouts: Timeouts, stats: Arc<ConnectionStats>) -> Self {
+        let now = Instant::now();
+        Self {
+            inner,
+            stats,
+            timeouts,
+            connected: now,
+            last_activity: now,
+            received_any: false,
+            read_timer: Box::pin(tokio::time::sleep_until(now)),
+            write_timer: Box::pin(tokio::time::sleep_until(now)),
+        }
+    }
+
+    /// Splits the stream into halves that can be used at the same time, e.g. from different tasks
+    pub fn split(&mut self) -> (ReadHalf<'_>, WriteHalf<'_>) {
+        tokio::io::split(self)
+    }
+
+    pub fn into_split(self) -> (OwnedReadHalf, OwnedWriteHalf) {
+        tokio::io::split(self)
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.inner.peer_addr()
+    }
+
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> TcpStream {
+        self.inner
+    }
+
+    pub fn stats(&self) -> &Arc<ConnectionStats> {
+        &self.stats
+    }
+
+    pub fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    /// When the session ends regardless of activity
+    pub fn session_deadline(&self) -> Option<Instant> {
+        self.timeouts
+            .session
+            .map(|session| self.connected + session)
+    }
+
+    /// The earliest timeout that applies right now
+    fn deadline(&self) -> Option<(Instant, TimedOut)> {
+        let first_byte = match self.received_any {
+            true => None,
+            false => self
+                .timeouts
+                .first_byte
+                .map(|timeout| self.connected + timeout),
+        };
+        let idle = self
+            .timeouts
+            .idle
+            .map(|timeout| self.last_activity + timeout);
+
+        [
+            (first_byte, TimedOut::FirstByte),
+            (idle, TimedOut::Idle),
+            (self.session_deadline(), TimedOut::Session),
+        ]
+        .into_iter()
+        .filter_map(|(deadline, reason)| Some((deadline?, reason)))
+        .min_by_key(|(deadline, _)| *deadline)
+    }
+
+    /// Called while the socket is not ready. Fails once the deadline has passed.
+    fn poll_timeout(&mut self, cx: &mut Context<'_>, read: bool) -> Poll<io::Error> {
+        let Some((deadline, reason)) = self.deadline() else {
+            return Poll::Pending;
+        };
+        let timer = match read {
+            true => &mut self.read_timer,
+            false => &mut self.write_timer,
+        };
+        if timer.deadline() != deadline {
+            timer.as_mut().reset(deadline);
+        }
+        timer.as_mut().poll(cx).map(|()| reason.into())
+    }
+}
+
+impl AsyncRead for Stream {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+
+        match Pin::new(&mut this.inner).poll_read(cx, buf) {
+            Poll::Ready(result) => {
+                if buf.filled().len() > filled {
+                    this.stats
+                        .add_bytes_in((buf.filled().len() - filled) as u64);
+                    this.received_any = true;
+                    this.last_activity = Instant::now();
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => this.poll_timeout(cx, true).map(Err),
+        }
+    }
+}
+
+impl AsyncWrite for Stream {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.inner).poll_write(cx, buf) {
+            Poll::Ready(result) => {
+                if let Ok(written @ 1..) = result {
+                    this.stats.add_bytes_out(written as u64);
+                    this.last_activity = Instant::now();
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => this.poll_timeout(cx, false).map(Err),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_shutdown(cx)
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+
+        match Pin::new(&mut this.inner).poll_write_vectored(cx, bufs) {
+            Poll::Ready(result) => {
+                if let Ok(written @ 1..) = result {
+                    this.stats.add_bytes_out(written as u64);
+                    this.last_activity = Instant::now();
+                }
+                Poll::Ready(result)
+            }
+            Poll::Pending => this.poll_timeout(cx, false).map(Err),
+        }
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+}
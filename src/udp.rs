@@ -0,0 +1,281 @@
+//! Datagram servers: the UDP counterpart to [`serve`](crate::serve).
+//!
+//! Peers are tracked by their address. The first datagram from an address counts as a new
+//! connection and a peer that stays quiet for [`UdpOptions::peer_timeout`] counts as
+//! disconnected. Every peer gets a `connection` span and [`ConnectionStats`] just like a TCP
+//! connection does, every datagram counts as a message.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+use tokio::select;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+use tracing::{debug, error, info, info_span, warn, Instrument, Span};
+
+use crate::limit::Reservation;
+use crate::shutdown::{self, Handlers};
+use crate::stats::{CloseReason, ConnectionStats, ServerStats};
+use crate::{log_totals, metrics, Config, Error, ShutdownSummary};
+
+/// Largest payload of a UDP datagram over IPv4
+pub const MAX_DATAGRAM_SIZE: usize = 65_507;
+pub const DEFAULT_PEER_TIMEOUT: Duration = Duration::from_secs(60);
+/// Datagrams waiting for a per peer task. More than that are dropped.
+const PEER_QUEUE_SIZE: usize = 128;
+
+#[derive(Debug, Clone)]
+pub struct UdpOptions {
+    /// Larger datagrams are dropped, larger replies are refused
+    pub max_datagram_size: usize,
+    /// Handle every peer in its own task. Datagrams of a single peer are still handled
+    /// one after another, but a slow peer does not hold up the others.
+    /// Otherwise every datagram is handled on the receiving loop, in the order they arrive.
+    /// Connection limits only apply to per peer tasks.
+    pub per_peer_tasks: bool,
+    /// Peers that do not send anything for this long are forgotten
+    pub peer_timeout: Duration,
+}
+
+impl Default for UdpOptions {
+    fn default() -> Self {
+        Self {
+            max_datagram_size: MAX_DATAGRAM_SIZE,
+            per_peer_tasks: false,
+            peer_timeout: DEFAULT_PEER_TIMEOUT,
+        }
+    }
+}
+
+/// A single datagram together with a way to answer it
+#[derive(Debug)]
+pub struct Datagram {
+    pub data: Vec<u8>,
+    pub addr: SocketAddr,
+    /// Identifies the peer, the same for all datagrams until it times out
+    pub id: usize,
+    pub reply: Reply,
+    /// Shared by all datagrams of the peer
+    pub stats: Arc<ConnectionStats>,
+}
+
+/// Sends datagrams back to a peer
+#[derive(Debug, Clone)]
+pub struct Reply {
+    socket: Arc<UdpSocket>,
+    addr: SocketAddr,
+    max_datagram_size: usize,
+    stats: Arc<ConnectionStats>,
+}
+
+impl Reply {
+    pub async fn send(&self, data: &[u8]) -> Result<(), Error> {
+        if data.len() > self.max_datagram_size {
+            return Err(format!(
+                "reply of {} bytes exceeds the maximum datagram size of {}",
+                data.len(),
+                self.max_datagram_size
+            )
+            .into());
+        }
+        self.socket.send_to(data, self.addr).await?;
+        self.stats.add_bytes_out(data.len() as u64);
+        self.stats.message_out();
+        Ok(())
+    }
+
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+struct Peer {
+    id: usize,
+    span: Span,
+    stats: Arc<ConnectionStats>,
+    last_seen: Instant,
+    /// Only used with per peer tasks
+    tx: Option<mpsc::Sender<Datagram>>,
+}
+
+/// Serves datagrams until the process receives SIGINT or SIGTERM, see [`serve_until`]
+pub async fn serve<H, Fut>(
+    config: &Config,
+    options: UdpOptions,
+    handler: H,
+) -> Result<ShutdownSummary, Error>
+where
+    H: FnOnce(Datagram) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<(), Error>> + Send,
+{
+    serve_until(config, options, handler, shutdown::signal()).await
+}
+
+/// Serves datagrams until `shutdown` completes. Just like [`serve`](crate::serve) every
+/// datagram is handled by its own clone of `handler`.
+pub async fn serve_until<H, Fut, S>(
+    config: &Config,
+    options: UdpOptions,
+    handler: H,
+    shutdown: S,
+) -> Result<ShutdownSummary, Error>
+where
+    H: FnOnce(Datagram) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<(), Error>> + Send,
+    S: Future<Output = ()>,
+{
+    let addr = config.listen_addr().await?;
+    let socket = Arc::new(UdpSocket::bind(addr).await?);
+    info!(%addr, "listening (UDP)");
+    metrics::start(config).await?;
+    let server_stats = ServerStats::register(format!("udp://{addr}"));
+
+    let mut peers = HashMap::<SocketAddr, Peer>::new();
+    let mut id = 0_usize;
+    let mut handlers = Handlers::new();
+    let limit = config.connection_limit();
+    // One byte more than allowed, to tell datagrams that fit apart from truncated ones
+    let mut buffer = vec![0_u8; options.max_datagram_size + 1];
+    let mut expiry =
+        tokio::time::interval((options.peer_timeout / 2).max(Duration::from_millis(10)));
+    tokio::pin!(shutdown);
+
+    loop {
+        let (len, addr) = select! {
+            received = socket.recv_from(&mut buffer) => match received {
+                Ok(received) => received,
+                Err(err) => {
+                    error!(error = %err, "UDP socket failed");
+                    break;
+                }
+            },
+            _ = expiry.tick() => {
+                let now = Instant::now();
+                peers.retain(|_, peer| {
+                    let alive = now.duration_since(peer.last_seen) < options.peer_timeout;
+                    // Per peer tasks close their stats once their queue is closed
+                    if !alive && peer.tx.is_none() {
+                        peer.span.in_scope(|| peer.stats.close(CloseReason::Closed));
+                    }
+                    alive
+                });
+                continue;
+            }
+            _ = &mut shutdown => break,
+        };
+
+        let peer = peers.entry(addr).or_insert_with(|| {
+            let span = info_span!("connection", id, %addr);
+            let stats = server_stats.connection();
+            let tx = options.per_peer_tasks.then(|| {
+                let (tx, rx) = mpsc::channel(PEER_QUEUE_SIZE);
+                let limit = limit.clone();
+                let handler = handler.clone();
+                let stats = stats.clone();
+                let currently_running = handlers.spawn(
+                    async move {
+                        let _permit = match limit.admit(Reservation::default(), addr.ip()).await {
+                            Ok(permit) => permit,
+                            Err(rejected) => {
+                                warn!(%rejected, "rejected");
+                                stats.close(CloseReason::Rejected);
+                                return;
+                            }
+                        };
+                        handle_peer(rx, handler).await;
+                        stats.close(CloseReason::Closed);
+                    }
+                    .instrument(span.clone()),
+                );
+                span.in_scope(|| info!(running = currently_running, "connect"));
+                tx
+            });
+            if tx.is_none() {
+                span.in_scope(|| info!("connect"));
+            }
+            let peer = Peer {
+                id,
+                span,
+                stats,
+                last_seen: Instant::now(),
+                tx,
+            };
+            id = id.wrapping_add(1);
+            peer
+        });
+        peer.last_seen = Instant::now();
+        peer.stats.add_bytes_in(len as u64);
+
+        if len > options.max_datagram_size {
+            peer.span
+                .in_scope(|| debug!(len, "dropping oversized datagram"));
+            continue;
+        }
+        peer.stats.message_in();
+
+        let datagram = Datagram {
+            data: buffer[..len].to_vec(),
+            addr,
+            id: peer.id,
+            reply: Reply {
+                socket: socket.clone(),
+                addr,
+                max_datagram_size: options.max_datagram_size,
+                stats: peer.stats.clone(),
+            },
+            stats: peer.stats.clone(),
+        };
+
+        match &peer.tx {
+            Some(tx) => {
+                if tx.try_send(datagram).is_err() {
+                    peer.span
+                        .in_scope(|| debug!("dropping datagram, peer is busy or was rejected"));
+                }
+            }
+            None => {
+                let handled = handler.clone()(datagram).instrument(peer.span.clone());
+                if let Err(err) = handled.await {
+                    peer.span
+                        .in_scope(|| error!(error = %err, "handler failed"));
+                }
+            }
+        }
+    }
+
+    info!(%addr, "stopped listening");
+    // Closes the queues of the per peer tasks
+    for peer in peers.into_values() {
+        if peer.tx.is_none() {
+            peer.span.in_scope(|| peer.stats.close(CloseReason::Closed));
+        }
+    }
+
+    let summary = handlers
+        .drain(Duration::from_secs(config.shutdown_timeout))
+        .await;
+    info!(
+        drained = summary.drained,
+        aborted = summary.aborted,
+        "shutdown"
+    );
+    log_totals(&server_stats);
+
+    Ok(summary)
+}
+
+async fn handle_peer<H, Fut>(mut rx: mpsc::Receiver<Datagram>, handler: H)
+where
+    H: FnOnce(Datagram) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<(), Error>> + Send,
+{
+    while let Some(datagram) = rx.recv().await {
+        if let Err(err) = handler.clone()(datagram).await {
+            error!(error = %err, "handler failed");
+        }
+    }
+}
@@ -0,0 +1,125 @@
+mod common;
+
+use common::TestServer;
+use protohackers::problems::budget_chat::Username;
+use protohackers::problems::Problem;
+use protohackers::Config;
+
+#[test]
+fn validates_names() {
+    assert_eq!(Username::new("bob42").unwrap().get(), "bob42");
+    assert!(Username::new("").is_err());
+    assert!(Username::new("bob smith").is_err());
+    assert!(Username::new("bob!").is_err());
+}
+
+#[tokio::test]
+async fn relays_joins_messages_and_parts() {
+    let server = TestServer::start(Problem::BudgetChat).await;
+
+    let mut alice = server.connect().await;
+    alice.expect_line("name?").await;
+    alice.send_line("alice").await;
+    alice.expect_line("* LIST: ").await;
+
+    let mut bob = server.connect().await;
+    bob.expect_line("name?").await;
+    bob.send_line("bob").await;
+    bob.expect_line("* LIST: alice").await;
+    alice.expect_line("* JOIN: bob").await;
+
+    bob.send_line("hi alice").await;
+    alice.expect_line("[bob] hi alice").await;
+    alice.send_line("hi bob").await;
+    bob.expect_line("[alice] hi bob").await;
+
+    drop(bob);
+    alice.expect_line("* PART: bob").await;
+}
+
+#[tokio::test]
+async fn disconnects_invalid_names_without_telling_anyone() {
+    let server = TestServer::start(Problem::BudgetChat).await;
+
+    let mut alice = server.connect().await;
+    alice.expect_line("name?").await;
+    alice.send_line("alice").await;
+    alice.expect_line("* LIST: ").await;
+
+    let mut invalid = server.connect().await;
+    invalid.expect_line("name?").await;
+    invalid.send_line("not valid").await;
+    invalid.expect_closed().await;
+
+    let mut carol = server.connect().await;
+    carol.expect_line("name?").await;
+    carol.send_line("carol").await;
+    carol.expect_line("* LIST: alice").await;
+    alice.expect_line("* JOIN: carol").await;
+}
+
+async fn start_with_max_line_length(max_line_length: usize) -> TestServer {
+    let config = Config {
+        max_line_length,
+        ..Config::default()
+    };
+    TestServer::start_with(Problem::BudgetChat, config).await
+}
+
+#[tokio::test]
+async fn disconnects_names_that_are_too_long() {
+    let server = start_with_max_line_length(64).await;
+
+    let mut client = server.connect().await;
+    client.expect_line("name?").await;
+    client.send("a".repeat(4096)).await;
+    client
+        .expect_line("* name longer than 64 bytes, disconnecting")
+        .await;
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn disconnects_messages_that_are_too_long() {
+    let server = start_with_max_line_length(64).await;
+
+    let mut alice = server.connect().await;
+    alice.expect_line("name?").await;
+    alice.send_line("alice").await;
+    alice.expect_line("* LIST: ").await;
+
+    let mut bob = server.connect().await;
+    bob.expect_line("name?").await;
+    bob.send_line("bob").await;
+    bob.expect_line("* LIST: alice").await;
+    alice.expect_line("* JOIN: bob").await;
+
+    let message = "x".repeat(64);
+    bob.send_line(&message).await;
+    alice.expect_line(&format!("[bob] {message}")).await;
+
+    bob.send_line(&"x".repeat(4096)).await;
+    bob.expect_line("* message longer than 64 bytes, disconnecting")
+        .await;
+    bob.expect_closed().await;
+    alice.expect_line("* PART: bob").await;
+}
+
+#[tokio::test]
+async fn accepts_lines_ending_in_crlf() {
+    let server = TestServer::start(Problem::BudgetChat).await;
+
+    let mut alice = server.connect().await;
+    alice.expect_line("name?").await;
+    alice.send("alice\r\n").await;
+    alice.expect_line("* LIST: ").await;
+
+    let mut bob = server.connect().await;
+    bob.expect_line("name?").await;
+    bob.send_line("bob").await;
+    bob.expect_line("* LIST: alice").await;
+    alice.expect_line("* JOIN: bob").await;
+
+    alice.send("hi bob\r\n").await;
+    bob.expect_line("[alice] hi bob").await;
+}
@@ -0,0 +1,127 @@
+mod common;
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use common::{Client, TestServer, UdpClient};
+use protohackers::chaos::ChaosOptions;
+use protohackers::problems::Problem;
+
+fn byte_by_byte() -> ChaosOptions {
+    ChaosOptions {
+        max_chunk: Some(1),
+        jitter: Duration::from_millis(2),
+        seed: Some(1),
+        ..ChaosOptions::default()
+    }
+}
+
+fn message(kind: u8, first: i32, second: i32) -> Vec<u8> {
+    let mut message = vec![kind];
+    message.extend(first.to_be_bytes());
+    message.extend(second.to_be_bytes());
+    message
+}
+
+#[tokio::test]
+async fn means_to_an_end_reads_fragmented_messages() {
+    let server = TestServer::start(Problem::MeansToAnEnd).await;
+    let mut client = Client::connect(server.chaos(byte_by_byte()).await).await;
+
+    client.send(message(b'I', 12345, 101)).await;
+    client.send(message(b'I', 12346, 102)).await;
+    client.send(message(b'Q', 12288, 16384)).await;
+    client.expect(101_i32.to_be_bytes()).await;
+}
+
+#[tokio::test]
+async fn means_to_an_end_reads_coalesced_messages() {
+    let server = TestServer::start(Problem::MeansToAnEnd).await;
+    let options = ChaosOptions {
+        coalesce: Some(Duration::from_millis(50)),
+        max_chunk: Some(7),
+        seed: Some(2),
+        ..ChaosOptions::default()
+    };
+    let mut client = Client::connect(server.chaos(options).await).await;
+
+    for (timestamp, price) in [(1, 10), (2, 20), (3, 30)] {
+        client.send(message(b'I', timestamp, price)).await;
+    }
+    client.send(message(b'Q', 1, 2)).await;
+    client.send(message(b'Q', 1, 3)).await;
+    client.expect(15_i32.to_be_bytes()).await;
+    client.expect(20_i32.to_be_bytes()).await;
+}
+
+#[tokio::test]
+async fn speed_daemon_reads_fragmented_messages() {
+    let server = TestServer::start(Problem::SpeedDaemon).await;
+    let mut client = Client::connect(server.chaos(byte_by_byte()).await).await;
+
+    // WantHeartbeat for every 0.1 seconds, then an illegal message type
+    client.send([0x40, 0, 0, 0, 1]).await;
+    client.expect([0x41]).await;
+    client.send([0x99]).await;
+    client.expect(b"\x10\x0billegal msg").await;
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn budget_chat_reads_fragmented_lines() {
+    let server = TestServer::start(Problem::BudgetChat).await;
+    let proxy = server.chaos(byte_by_byte()).await;
+
+    let mut alice = Client::connect(proxy).await;
+    alice.expect_line("name?").await;
+    alice.send_line("alice").await;
+    alice.expect_line("* LIST: ").await;
+
+    let mut bob = Client::connect(proxy).await;
+    bob.expect_line("name?").await;
+    bob.send_line("bob").await;
+    bob.expect_line("* LIST: alice").await;
+    alice.expect_line("* JOIN: bob").await;
+
+    bob.send_line("hello from a slow network").await;
+    alice.expect_line("[bob] hello from a slow network").await;
+}
+
+#[tokio::test]
+async fn unusual_database_program_answers_reordered_datagrams() {
+    let server = TestServer::start(Problem::UnusualDatabaseProgram).await;
+    let options = ChaosOptions {
+        jitter: Duration::from_millis(20),
+        reorder_percent: 50.0,
+        reorder_delay: Duration::from_millis(30),
+        seed: Some(3),
+        ..ChaosOptions::default()
+    };
+    let client = UdpClient::connect(server.chaos(options).await).await;
+
+    let keys: Vec<String> = (0..20).map(|key| format!("key{key}")).collect();
+    for key in &keys {
+        client.send(key).await;
+    }
+
+    // Every key is independent of the others, so only the order can change
+    let mut answers = HashSet::new();
+    for _ in &keys {
+        answers.insert(String::from_utf8(client.recv().await).unwrap());
+    }
+    let expected: HashSet<String> = keys.iter().map(|key| format!("{key}=")).collect();
+    assert_eq!(answers, expected);
+}
+
+#[tokio::test]
+async fn drops_every_datagram() {
+    let server = TestServer::start(Problem::UnusualDatabaseProgram).await;
+    let options = ChaosOptions {
+        drop_percent: 100.0,
+        ..ChaosOptions::default()
+    };
+    let client = UdpClient::connect(server.chaos(options).await).await;
+
+    client.send("version").await;
+    client.expect_silence(Duration::from_millis(200)).await;
+}
@@ -0,0 +1,172 @@
+use std::num::NonZeroUsize;
+
+use protohackers::codec::{FrameError, FrameReader, FrameWriter, Framing, PrefixWidth};
+
+fn reader(input: &[u8], framing: Framing) -> FrameReader<&[u8]> {
+    FrameReader::new(input, framing)
+}
+
+async fn write(framing: Framing, frames: &[&[u8]]) -> Vec<u8> {
+    let mut writer = FrameWriter::new(Vec::new(), framing);
+    for frame in frames {
+        writer.write_frame(frame).await.unwrap();
+    }
+    writer.flush().await.unwrap();
+    writer.into_inner()
+}
+
+fn fixed(len: usize) -> Framing {
+    Framing::Fixed(NonZeroUsize::new(len).unwrap())
+}
+
+#[tokio::test]
+async fn reads_and_writes_lines() {
+    let framing = Framing::Line { max_len: 5 };
+    let mut lines = reader(b"hello\n\nhi\n", framing);
+    assert_eq!(lines.read_frame().await.unwrap().unwrap(), b"hello");
+    assert_eq!(lines.read_frame().await.unwrap().unwrap(), b"");
+    assert_eq!(lines.read_string().await.unwrap().unwrap(), "hi");
+    assert!(lines.read_frame().await.unwrap().is_none());
+
+    assert_eq!(
+        write(framing, &[b"hello", b"", b"hi"]).await,
+        b"hello\n\nhi\n"
+    );
+    let mut writer = FrameWriter::new(Vec::new(), framing);
+    assert!(matches!(
+        writer.write_frame(b"a\nb").await,
+        Err(FrameError::NewlineInLine)
+    ));
+}
+
+#[tokio::test]
+async fn drops_the_carriage_return_of_a_line() {
+    let mut lines = reader(b"hello\r\na\rb\n\r\r\n", Framing::Line { max_len: 5 });
+    assert_eq!(lines.read_frame().await.unwrap().unwrap(), b"hello");
+    assert_eq!(lines.read_frame().await.unwrap().unwrap(), b"a\rb");
+    // Only the last one
+    assert_eq!(lines.read_frame().await.unwrap().unwrap(), b"\r");
+    assert!(lines.read_frame().await.unwrap().is_none());
+
+    let mut lines = reader(b"hello!\r\n", Framing::Line { max_len: 5 });
+    assert!(matches!(
+        lines.read_frame().await,
+        Err(FrameError::TooLong { max: 5, .. })
+    ));
+}
+
+#[tokio::test]
+async fn reads_and_writes_fixed_size_records() {
+    let framing = fixed(3);
+    let mut records = reader(b"abcdef", framing);
+    assert_eq!(records.read_frame().await.unwrap().unwrap(), b"abc");
+    assert_eq!(records.read_frame().await.unwrap().unwrap(), b"def");
+    assert!(records.read_frame().await.unwrap().is_none());
+
+    assert_eq!(write(framing, &[b"abc", b"def"]).await, b"abcdef");
+    let mut writer = FrameWriter::new(Vec::new(), framing);
+    assert!(matches!(
+        writer.write_frame(b"abcd").await,
+        Err(FrameError::WrongSize {
+            len: 4,
+            expected: 3
+        })
+    ));
+}
+
+#[tokio::test]
+async fn reads_and_writes_length_prefixed_messages() {
+    for (width, encoded) in [
+        (PrefixWidth::U8, &b"\x02hi\x00"[..]),
+        (PrefixWidth::U16, b"\x00\x02hi\x00\x00"),
+        (PrefixWidth::U32, b"\x00\x00\x00\x02hi\x00\x00\x00\x00"),
+    ] {
+        let framing = Framing::Prefixed { width, max_len: 16 };
+        let mut messages = reader(encoded, framing);
+        assert_eq!(messages.read_frame().await.unwrap().unwrap(), b"hi");
+        assert_eq!(messages.read_frame().await.unwrap().unwrap(), b"");
+        assert!(messages.read_frame().await.unwrap().is_none());
+
+        assert_eq!(write(framing, &[b"hi", b""]).await, encoded);
+    }
+}
+
+#[tokio::test]
+async fn rejects_frames_that_are_too_long() {
+    let mut lines = reader(b"hello world\n", Framing::Line { max_len: 5 });
+    assert!(matches!(
+        lines.read_frame().await,
+        Err(FrameError::TooLong { max: 5, .. })
+    ));
+
+    // Fails on the prefix, before reading the content
+    let framing = Framing::Prefixed {
+        width: PrefixWidth::U16,
+        max_len: 5,
+    };
+    let mut messages = reader(b"\x01\x00", framing);
+    assert!(matches!(
+        messages.read_frame().await,
+        Err(FrameError::TooLong { len: 256, max: 5 })
+    ));
+
+    let mut writer = FrameWriter::new(Vec::new(), Framing::Line { max_len: 5 });
+    assert!(matches!(
+        writer.write_frame(b"hello world").await,
+        Err(FrameError::TooLong { len: 11, max: 5 })
+    ));
+    // The prefix caps the length as well
+    let framing = Framing::Prefixed {
+        width: PrefixWidth::U8,
+        max_len: 1024,
+    };
+    let mut writer = FrameWriter::new(Vec::new(), framing);
+    assert!(matches!(
+        writer.write_frame(&[0; 256]).await,
+        Err(FrameError::TooLong { len: 256, max: 255 })
+    ));
+}
+
+#[tokio::test]
+async fn reports_frames_cut_off_by_the_end_of_the_stream() {
+    let mut lines = reader(b"hello\nwor", Framing::Line { max_len: 16 });
+    assert_eq!(lines.read_frame().await.unwrap().unwrap(), b"hello");
+    assert!(matches!(
+        lines.read_frame().await,
+        Err(FrameError::Truncated { received: 3 })
+    ));
+
+    let mut records = reader(b"abcde", fixed(3));
+    assert_eq!(records.read_frame().await.unwrap().unwrap(), b"abc");
+    assert!(matches!(
+        records.read_frame().await,
+        Err(FrameError::Truncated { received: 2 })
+    ));
+
+    let framing = Framing::Prefixed {
+        width: PrefixWidth::U16,
+        max_len: 16,
+    };
+    for (input, received) in [(&b"\x00"[..], 1), (b"\x00\x05", 2), (b"\x00\x05abc", 5)] {
+        let mut messages = reader(input, framing);
+        match messages.read_frame().await {
+            Err(FrameError::Truncated { received: actual }) => assert_eq!(actual, received),
+            other => panic!("expected a truncated frame, got {other:?}"),
+        }
+    }
+
+    let mut bytes = reader(b"ab", Framing::Line { max_len: 16 });
+    assert!(matches!(
+        bytes.read_bytes(3).await,
+        Err(FrameError::Truncated { received: 2 })
+    ));
+}
+
+#[tokio::test]
+async fn rejects_lines_that_are_not_utf8() {
+    let mut lines = reader(b"\xff\n", Framing::Line { max_len: 16 });
+    assert!(matches!(
+        lines.read_string().await,
+        Err(FrameError::InvalidUtf8(_))
+    ));
+}
@@ -0,0 +1,256 @@
+//! Starts a problem server on an ephemeral port and talks to it with scripted clients.
+//!
+//! Every receiving method waits at most [`TIMEOUT`] and panics with what it was waiting for,
+//! so a hanging server fails the test instead of blocking it.
+
+// Every test file uses a different part of this module
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use protohackers::chaos::{self, ChaosOptions};
+use protohackers::problems::Problem;
+use protohackers::{stats, Config, Error, ShutdownSummary};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+pub struct TestServer {
+    pub addr: SocketAddr,
+    problem: Problem,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<Result<ShutdownSummary, Error>>,
+}
+
+impl TestServer {
+    pub async fn start(problem: Problem) -> Self {
+        Self::start_with(problem, Config::default()).await
+    }
+
+    /// `bind` and `port` of `config` are replaced with a free port on localhost
+    pub async fn start_with(problem: Problem, config: Config) -> Self {
+        // The OS does not hand out the same ephemeral port again right away
+        let addr = match problem.is_udp() {
+            true => UdpSocket::bind("127.0.0.1:0").await.unwrap().local_addr(),
+            false => TcpListener::bind("127.0.0.1:0").await.unwrap().local_addr(),
+        }
+        .unwrap();
+        let config = Config {
+            bind: addr.ip().to_string(),
+            port: addr.port(),
+            shutdown_timeout: 1,
+            ..config
+        };
+
+        let (shutdown, stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            problem
+                .run_until(&config, async {
+                    let _ = stopped.await;
+                })
+                .await
+        });
+
+        // Servers register their stats right after binding
+        let name = match problem {
+            Problem::UnusualDatabaseProgram => format!("udp://{addr}"),
+            Problem::LineReversal => format!("lrcp://{addr}"),
+            _ => format!("tcp://{addr}"),
+        };
+        timeout(TIMEOUT, async {
+            while !stats::snapshot().iter().any(|server| server.name == name) {
+                assert!(!task.is_finished(), "{problem} did not start");
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .unwrap_or_else(|_| panic!("{problem} did not start listening on {addr}"));
+
+        Self {
+            addr,
+            problem,
+            shutdown,
+            task,
+        }
+    }
+
+    pub async fn connect(&self) -> Client {
+        Client::connect(self.addr).await
+    }
+
+    pub async fn udp_client(&self) -> UdpClient {
+        UdpClient::connect(self.addr).await
+    }
+
+    /// Starts a [`chaos`] proxy in front of the server and returns its address.
+    /// It runs until the test ends.
+    pub async fn chaos(&self, options: ChaosOptions) -> SocketAddr {
+        let target = self.addr;
+        match self.problem.is_udp() {
+            true => {
+                let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+                let addr = socket.local_addr().unwrap();
+                tokio::spawn(chaos::udp(socket, target, options));
+                addr
+            }
+            false => {
+                let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+                let addr = listener.local_addr().unwrap();
+                tokio::spawn(chaos::tcp(listener, target, options));
+                addr
+            }
+        }
+    }
+
+    pub async fn stop(self) -> ShutdownSummary {
+        let _ = self.shutdown.send(());
+        timeout(TIMEOUT * 2, self.task)
+            .await
+            .expect("server did not shut down")
+            .unwrap()
+            .unwrap()
+    }
+}
+
+pub struct Client {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Client {
+    pub async fn connect(addr: SocketAddr) -> Self {
+        let stream = timeout(TIMEOUT, TcpStream::connect(addr))
+            .await
+            .expect("timed out connecting")
+            .expect("could not connect");
+        let (reader, writer) = stream.into_split();
+        Self {
+            reader: BufReader::new(reader),
+            writer,
+        }
+    }
+
+    pub async fn send(&mut self, bytes: impl AsRef<[u8]>) {
+        self.writer.write_all(bytes.as_ref()).await.unwrap();
+    }
+
+    /// Appends the `\n`
+    pub async fn send_line(&mut self, line: &str) {
+        self.send(format!("{line}\n")).await;
+    }
+
+    pub async fn recv_exact(&mut self, len: usize) -> Vec<u8> {
+        let mut buffer = vec![0_u8; len];
+        timeout(TIMEOUT, self.reader.read_exact(&mut buffer))
+            .await
+            .unwrap_or_else(|_| panic!("timed out waiting for {len} bytes"))
+            .unwrap_or_else(|err| panic!("could not read {len} bytes: {err}"));
+        buffer
+    }
+
+    pub async fn expect(&mut self, expected: impl AsRef<[u8]>) {
+        let expected = expected.as_ref();
+        let received = self.recv_exact(expected.len()).await;
+        assert_eq!(
+            received,
+            expected,
+            "expected {:?}, received {:?}",
+            String::from_utf8_lossy(expected),
+            String::from_utf8_lossy(&received)
+        );
+    }
+
+    /// Without the `\n`
+    pub async fn recv_line(&mut self) -> String {
+        let mut line = String::new();
+        let read = timeout(TIMEOUT, self.reader.read_line(&mut line))
+            .await
+            .expect("timed out waiting for a line")
+            .unwrap();
+        assert!(read > 0, "connection closed while waiting for a line");
+        assert!(
+            line.ends_with('\n'),
+            "connection closed in the middle of {line:?}"
+        );
+        line.pop();
+        line
+    }
+
+    pub async fn expect_line(&mut self, expected: &str) {
+        assert_eq!(self.recv_line().await, expected);
+    }
+
+    /// Fails if anything is received before the server closes the connection
+    pub async fn expect_closed(&mut self) {
+        let mut rest = Vec::new();
+        timeout(TIMEOUT, self.reader.read_to_end(&mut rest))
+            .await
+            .expect("timed out waiting for the server to close the connection")
+            .unwrap();
+        assert!(
+            rest.is_empty(),
+            "received {:?} before the connection was closed",
+            String::from_utf8_lossy(&rest)
+        );
+    }
+
+    /// Fails if anything is received within `wait`
+    pub async fn expect_silence(&mut self, wait: Duration) {
+        let mut byte = [0_u8; 1];
+        if let Ok(read) = timeout(wait, self.reader.read(&mut byte)).await {
+            match read.unwrap() {
+                0 => panic!("connection closed"),
+                _ => panic!("received {byte:?}"),
+            }
+        }
+    }
+}
+
+pub struct UdpClient {
+    socket: UdpSocket,
+}
+
+impl UdpClient {
+    pub async fn connect(addr: SocketAddr) -> Self {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        socket.connect(addr).await.unwrap();
+        Self { socket }
+    }
+
+    pub async fn send(&self, datagram: impl AsRef<[u8]>) {
+        self.socket.send(datagram.as_ref()).await.unwrap();
+    }
+
+    pub async fn recv(&self) -> Vec<u8> {
+        let mut buffer = vec![0_u8; 65_536];
+        let len = timeout(TIMEOUT, self.socket.recv(&mut buffer))
+            .await
+            .expect("timed out waiting for a datagram")
+            .unwrap();
+        buffer.truncate(len);
+        buffer
+    }
+
+    /// Fails if a datagram is received within `wait`
+    pub async fn expect_silence(&self, wait: Duration) {
+        let mut buffer = vec![0_u8; 65_536];
+        if let Ok(received) = timeout(wait, self.socket.recv(&mut buffer)).await {
+            let len = received.unwrap();
+            panic!("received {:?}", String::from_utf8_lossy(&buffer[..len]));
+        }
+    }
+
+    pub async fn expect(&self, expected: impl AsRef<[u8]>) {
+        let received = self.recv().await;
+        assert_eq!(
+            String::from_utf8_lossy(&received),
+            String::from_utf8_lossy(expected.as_ref())
+        );
+    }
+}
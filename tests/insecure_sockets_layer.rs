@@ -0,0 +1,56 @@
+mod common;
+
+use common::TestServer;
+use protohackers::problems::insecure_sockets_layer::most_copies;
+use protohackers::problems::Problem;
+
+#[test]
+fn picks_the_toy_with_the_most_copies() {
+    assert_eq!(
+        most_copies("10x toy car,15x dog on a string,4x inflatable motorcycle"),
+        Some("15x dog on a string")
+    );
+    assert_eq!(most_copies("1x a"), Some("1x a"));
+    assert_eq!(most_copies("no toys here"), None);
+}
+
+#[tokio::test]
+async fn answers_the_example_session() {
+    let server = TestServer::start(Problem::InsecureSocketsLayer).await;
+    let mut client = server.connect().await;
+
+    // xor(123), addpos, reversebits
+    client.send([0x02, 0x7b, 0x05, 0x01, 0x00]).await;
+
+    // 4x dog,5x car
+    client
+        .send([
+            0xf2, 0x20, 0xba, 0x44, 0x18, 0x84, 0xba, 0xaa, 0xd0, 0x26, 0x44, 0xa4, 0xa8, 0x7e,
+        ])
+        .await;
+    // 5x car
+    client
+        .expect([0x72, 0x20, 0xba, 0xd8, 0x78, 0x70, 0xee])
+        .await;
+
+    // 3x rat,2x cat, positions go on in both directions
+    client
+        .send([
+            0x6a, 0x48, 0xd6, 0x58, 0x34, 0x44, 0xd6, 0x7a, 0x98, 0x4e, 0x0c, 0xcc, 0x94, 0x31,
+        ])
+        .await;
+    // 3x rat
+    client
+        .expect([0xf2, 0xd0, 0x26, 0xc8, 0xa4, 0xd8, 0x7e])
+        .await;
+}
+
+#[tokio::test]
+async fn disconnects_clients_with_a_cipher_that_changes_nothing() {
+    let server = TestServer::start(Problem::InsecureSocketsLayer).await;
+    let mut client = server.connect().await;
+
+    // xor(0) leaves every byte alone
+    client.send([0x02, 0x00, 0x00]).await;
+    client.expect_closed().await;
+}
@@ -0,0 +1,150 @@
+use protohackers::isl::{CipherSpec, CipherStream, Operation, MAX_SPEC_LEN};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+#[test]
+fn parses_cipher_specs() {
+    assert_eq!(
+        CipherSpec::from_bytes(&[0x02, 0x01, 0x01, 0x00])
+            .unwrap()
+            .operations(),
+        [Operation::Xor(1), Operation::ReverseBits]
+    );
+    assert_eq!(
+        CipherSpec::from_bytes(&[0x05, 0x04, 0x00, 0x03, 0x00])
+            .unwrap()
+            .operations(),
+        [Operation::AddPos, Operation::Add(0), Operation::XorPos]
+    );
+
+    for invalid in [
+        &[][..],
+        &[0x01],
+        &[0x02],
+        &[0x06, 0x00],
+        &[0x01, 0x00, 0x01],
+    ] {
+        assert!(CipherSpec::from_bytes(invalid).is_err(), "{invalid:02x?}");
+    }
+}
+
+#[test]
+fn rejects_cipher_specs_that_change_nothing() {
+    for noop in [
+        &[0x00][..],
+        &[0x02, 0x00, 0x00],
+        &[0x02, 0xab, 0x02, 0xab, 0x00],
+        &[0x01, 0x01, 0x00],
+        &[0x02, 0xa0, 0x02, 0x0b, 0x02, 0xab, 0x00],
+        &[0x03, 0x04, 0x00, 0x03, 0x00],
+    ] {
+        let err = CipherSpec::from_bytes(noop).unwrap_err();
+        assert_eq!(err.to_string(), "cipher spec does not change anything");
+    }
+}
+
+#[test]
+fn encodes_like_the_examples() {
+    let spec = CipherSpec::from_bytes(&[0x02, 0x01, 0x01, 0x00]).unwrap();
+    let encoded: Vec<u8> = (0..)
+        .zip(b"hello")
+        .map(|(pos, &byte)| spec.encode(byte, pos))
+        .collect();
+    assert_eq!(encoded, [0x96, 0x26, 0xb6, 0xb6, 0x76]);
+
+    let spec = CipherSpec::from_bytes(&[0x05, 0x05, 0x00]).unwrap();
+    let encoded: Vec<u8> = (0..)
+        .zip(b"hello")
+        .map(|(pos, &byte)| spec.encode(byte, pos))
+        .collect();
+    assert_eq!(encoded, [0x68, 0x67, 0x70, 0x72, 0x77]);
+}
+
+#[test]
+fn decodes_what_it_encodes() {
+    let spec = CipherSpec::new(vec![
+        Operation::ReverseBits,
+        Operation::Add(3),
+        Operation::XorPos,
+        Operation::Xor(0x5a),
+        Operation::AddPos,
+    ])
+    .unwrap();
+    for pos in 0..512 {
+        for byte in 0..=u8::MAX {
+            assert_eq!(spec.decode(spec.encode(byte, pos), pos), byte);
+        }
+    }
+}
+
+#[tokio::test]
+async fn reads_cipher_specs_up_to_the_maximum_length() {
+    // Forty one byte operations and the terminator are one byte too many
+    let mut too_long = [0x01_u8; MAX_SPEC_LEN].to_vec();
+    too_long.push(0x00);
+    let err = CipherSpec::read_from(&mut &too_long[..]).await.unwrap_err();
+    assert_eq!(err.to_string(), "cipher spec too long");
+
+    // The operand would be byte 81, it is not read at all
+    let mut operand_too_far = [0x01_u8; MAX_SPEC_LEN - 1].to_vec();
+    operand_too_far.push(0x02);
+    let err = CipherSpec::read_from(&mut &operand_too_far[..])
+        .await
+        .unwrap_err();
+    assert_eq!(err.to_string(), "cipher spec too long");
+
+    let mut longest = [0x02_u8, 0x01].repeat(MAX_SPEC_LEN / 2 - 1);
+    longest.extend([0x01, 0x00]);
+    let mut rest = &b"after"[..];
+    let mut reader = longest.as_slice().chain(&mut rest);
+    let spec = CipherSpec::read_from(&mut reader).await.unwrap();
+    assert_eq!(spec.operations().len(), MAX_SPEC_LEN / 2);
+    let mut after = String::new();
+    reader.read_to_string(&mut after).await.unwrap();
+    assert_eq!(after, "after");
+}
+
+#[tokio::test]
+async fn ciphers_both_directions_of_a_stream() {
+    let (client, server) = tokio::io::duplex(64);
+    let (mut client_reader, mut client_writer) = tokio::io::split(client);
+
+    client_writer
+        .write_all(&[0x02, 0x7b, 0x05, 0x00])
+        .await
+        .unwrap();
+    let spec = CipherSpec::from_bytes(&[0x02, 0x7b, 0x05, 0x00]).unwrap();
+    let encode = |data: &[u8], start: u64| -> Vec<u8> {
+        (start..)
+            .zip(data)
+            .map(|(pos, &byte)| spec.encode(byte, pos))
+            .collect()
+    };
+    client_writer
+        .write_all(&encode(b"4x dog,5x car\n", 0))
+        .await
+        .unwrap();
+
+    let mut stream = CipherStream::accept(server).await.unwrap();
+    let mut request = [0_u8; 14];
+    stream.read_exact(&mut request).await.unwrap();
+    assert_eq!(&request, b"4x dog,5x car\n");
+
+    // Larger than the pipe, so the stream has to keep the encoded rest until there is room
+    let response = b"5x car\n".repeat(50);
+    let write = async {
+        stream.write_all(&response).await.unwrap();
+        stream.flush().await.unwrap();
+    };
+    let mut received = vec![0_u8; response.len()];
+    let read = client_reader.read_exact(&mut received);
+    let ((), read) = tokio::join!(write, read);
+    read.unwrap();
+    assert_eq!(received, encode(&response, 0));
+
+    // Positions continue where they left off
+    stream.write_all(b"x").await.unwrap();
+    stream.flush().await.unwrap();
+    let mut byte = [0_u8; 1];
+    client_reader.read_exact(&mut byte).await.unwrap();
+    assert_eq!(byte.to_vec(), encode(b"x", response.len() as u64));
+}
@@ -0,0 +1,92 @@
+mod common;
+
+use common::{Client, TestServer};
+use protohackers::problems::job_centre::Request;
+use protohackers::problems::Problem;
+use serde_json::{json, Value};
+
+async fn request(client: &mut Client, request: Value) -> Value {
+    client.send_line(&request.to_string()).await;
+    serde_json::from_str(&client.recv_line().await).unwrap()
+}
+
+#[test]
+fn parses_requests() {
+    let request: Request =
+        serde_json::from_str(r#"{"request":"get","queues":["q1","q2"]}"#).unwrap();
+    assert!(matches!(request, Request::Get { queues, wait: false } if queues == ["q1", "q2"]));
+
+    assert!(serde_json::from_str::<Request>(r#"{"request":"put","queue":"q1"}"#).is_err());
+    assert!(serde_json::from_str::<Request>(r#"{"request":"explode"}"#).is_err());
+}
+
+#[tokio::test]
+async fn hands_out_the_highest_priority_job() {
+    let server = TestServer::start(Problem::JobCentre).await;
+    let mut client = server.connect().await;
+
+    let low = request(
+        &mut client,
+        json!({"request": "put", "queue": "q", "job": {"n": 1}, "pri": 1}),
+    )
+    .await;
+    let high = request(
+        &mut client,
+        json!({"request": "put", "queue": "q", "job": {"n": 2}, "pri": 2}),
+    )
+    .await;
+    assert_eq!(low["status"], "ok");
+    assert_ne!(low["id"], high["id"]);
+
+    let job = request(&mut client, json!({"request": "get", "queues": ["q"]})).await;
+    assert_eq!(job["status"], "ok");
+    assert_eq!(job["id"], high["id"]);
+    assert_eq!(job["job"], json!({"n": 2}));
+    assert_eq!(job["pri"], 2);
+    assert_eq!(job["queue"], "q");
+
+    let deleted = request(&mut client, json!({"request": "delete", "id": low["id"]})).await;
+    assert_eq!(deleted, json!({"status": "ok"}));
+    let empty = request(&mut client, json!({"request": "get", "queues": ["q"]})).await;
+    assert_eq!(empty, json!({"status": "no-job"}));
+}
+
+#[tokio::test]
+async fn requeues_jobs_of_disconnected_clients() {
+    let server = TestServer::start(Problem::JobCentre).await;
+
+    let mut worker = server.connect().await;
+    let mut waiter = server.connect().await;
+    waiter
+        .send_line(&json!({"request": "get", "queues": ["q"], "wait": true}).to_string())
+        .await;
+
+    let created = request(
+        &mut worker,
+        json!({"request": "put", "queue": "q", "job": {}, "pri": 5}),
+    )
+    .await;
+    let job: Value = serde_json::from_str(&waiter.recv_line().await).unwrap();
+    assert_eq!(job["id"], created["id"]);
+
+    drop(waiter);
+    let job = request(
+        &mut worker,
+        json!({"request": "get", "queues": ["q"], "wait": true}),
+    )
+    .await;
+    assert_eq!(job["id"], created["id"]);
+}
+
+#[tokio::test]
+async fn answers_malformed_requests_with_an_error() {
+    let server = TestServer::start(Problem::JobCentre).await;
+    let mut client = server.connect().await;
+
+    client.send_line("not json").await;
+    let response: Value = serde_json::from_str(&client.recv_line().await).unwrap();
+    assert_eq!(response["status"], "error");
+
+    let aborted = request(&mut client, json!({"request": "abort", "id": 12345})).await;
+    assert_eq!(aborted, json!({"status": "no-job"}));
+}
@@ -0,0 +1,84 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use protohackers::limit::{ConnectionLimit, OverloadPolicy, Rejected, Reservation};
+use tokio::time::timeout;
+
+const A: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 1));
+const B: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 0, 2, 2));
+const QUEUE_TIMEOUT: Duration = Duration::from_millis(300);
+
+fn limit(policy: OverloadPolicy) -> ConnectionLimit {
+    ConnectionLimit::new(Some(2), Some(1), policy, QUEUE_TIMEOUT)
+}
+
+#[tokio::test]
+async fn pause_gives_the_total_slot_back_while_waiting_for_an_ip() {
+    let limit = limit(OverloadPolicy::Pause);
+    let _a = limit.admit(limit.reserve().await, A).await.unwrap();
+
+    let reservation = limit.reserve().await;
+    let waiting = tokio::spawn({
+        let limit = limit.clone();
+        async move { limit.admit(reservation, A).await.map(drop) }
+    });
+
+    // Both total slots were reserved, B only gets one if the waiting connection let go of it
+    let reservation = timeout(Duration::from_secs(1), limit.reserve())
+        .await
+        .expect("the waiting connection still holds a total slot");
+    let _b = limit.admit(reservation, B).await.unwrap();
+
+    assert_eq!(
+        waiting.await.unwrap(),
+        Err(Rejected::TooManyConnectionsFrom(A))
+    );
+}
+
+#[tokio::test]
+async fn pause_admits_a_waiting_connection_once_its_ip_has_a_free_slot() {
+    let limit = limit(OverloadPolicy::Pause);
+    let a = limit.admit(limit.reserve().await, A).await.unwrap();
+
+    let reservation = limit.reserve().await;
+    let waiting = tokio::spawn({
+        let limit = limit.clone();
+        async move { limit.admit(reservation, A).await.map(drop) }
+    });
+    tokio::time::sleep(QUEUE_TIMEOUT / 3).await;
+    drop(a);
+
+    assert_eq!(waiting.await.unwrap(), Ok(()));
+}
+
+#[tokio::test]
+async fn reject_closes_connections_over_the_limits_right_away() {
+    let limit = limit(OverloadPolicy::Reject);
+    let _a = limit.admit(Reservation::default(), A).await.unwrap();
+    assert_eq!(
+        limit.admit(Reservation::default(), A).await.unwrap_err(),
+        Rejected::TooManyConnectionsFrom(A)
+    );
+
+    let _b = limit.admit(Reservation::default(), B).await.unwrap();
+    assert_eq!(
+        limit
+            .admit(Reservation::default(), IpAddr::V4(Ipv4Addr::LOCALHOST))
+            .await
+            .unwrap_err(),
+        Rejected::TooManyConnections
+    );
+}
+
+#[tokio::test]
+async fn queue_gives_the_ip_slot_back_when_there_is_no_total_slot() {
+    let limit = ConnectionLimit::new(Some(1), Some(1), OverloadPolicy::Queue, QUEUE_TIMEOUT);
+    let b = limit.admit(Reservation::default(), B).await.unwrap();
+
+    assert_eq!(
+        limit.admit(Reservation::default(), A).await.unwrap_err(),
+        Rejected::TooManyConnections
+    );
+    drop(b);
+    limit.admit(Reservation::default(), A).await.unwrap();
+}
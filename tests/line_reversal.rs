@@ -0,0 +1,38 @@
+mod common;
+
+use common::TestServer;
+use protohackers::problems::Problem;
+
+#[tokio::test]
+async fn reverses_lines_over_lrcp() {
+    let server = TestServer::start(Problem::LineReversal).await;
+    let client = server.udp_client().await;
+
+    client.send("/connect/12345/").await;
+    client.expect("/ack/12345/0/").await;
+
+    client.send("/data/12345/0/hello\n/").await;
+    client.expect("/ack/12345/6/").await;
+    client.expect("/data/12345/0/olleh\n/").await;
+    client.send("/ack/12345/6/").await;
+
+    // Lines can span several packets, slashes are escaped
+    client.send("/data/12345/6/a\\/b/").await;
+    client.expect("/ack/12345/9/").await;
+    client.send("/data/12345/9/c\n/").await;
+    client.expect("/ack/12345/11/").await;
+    client.expect("/data/12345/6/cb\\/a\n/").await;
+    client.send("/ack/12345/11/").await;
+
+    client.send("/close/12345/").await;
+    client.expect("/close/12345/").await;
+}
+
+#[tokio::test]
+async fn closes_unknown_sessions() {
+    let server = TestServer::start(Problem::LineReversal).await;
+    let client = server.udp_client().await;
+
+    client.send("/data/999/0/hello\n/").await;
+    client.expect("/close/999/").await;
+}
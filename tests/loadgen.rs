@@ -0,0 +1,37 @@
+mod common;
+
+use std::time::Duration;
+
+use common::TestServer;
+use protohackers::loadgen::{self, LoadOptions, PROBLEMS};
+use protohackers::problems::Problem;
+
+async fn generate_load(problem: Problem) {
+    let server = TestServer::start(problem).await;
+    let options = LoadOptions {
+        target: server.addr,
+        clients: 4,
+        duration: Duration::from_millis(300),
+        pause: Duration::from_millis(5),
+        seed: Some(1),
+        ..LoadOptions::default()
+    };
+
+    let report = loadgen::run(problem, &options).await.unwrap();
+    assert_eq!(report.errors, 0, "{report}");
+    assert!(report.answers() > 0, "{report}");
+    assert!(report.percentile(50.0) <= report.percentile(99.0));
+}
+
+#[tokio::test]
+async fn generates_load_for_every_scenario() {
+    for problem in PROBLEMS {
+        generate_load(problem).await;
+    }
+}
+
+#[tokio::test]
+async fn refuses_problems_without_a_scenario() {
+    let options = LoadOptions::default();
+    assert!(loadgen::run(Problem::SmokeTest, &options).await.is_err());
+}
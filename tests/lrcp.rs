@@ -0,0 +1,276 @@
+mod common;
+
+use common::{UdpClient, TIMEOUT};
+use protohackers::lrcp::{
+    chunks, escape, unescape, Listener, Message, Session, MAX_DATA_SIZE, MAX_PACKET_SIZE,
+};
+use tokio::io::AsyncReadExt;
+use tokio::time::timeout;
+
+/// A listener with one open session and the peer that opened it
+async fn connect(session: u32) -> (Listener, Session, UdpClient) {
+    let mut listener = Listener::bind("127.0.0.1:0").await.unwrap();
+    let peer = UdpClient::connect(listener.local_addr()).await;
+    peer.send(Message::Connect { session }.to_bytes()).await;
+    peer.expect(Message::Ack { session, length: 0 }.to_bytes())
+        .await;
+    let session = timeout(TIMEOUT, listener.accept())
+        .await
+        .expect("timed out waiting for the session")
+        .unwrap();
+    (listener, session, peer)
+}
+
+async fn recv(peer: &UdpClient) -> Message {
+    Message::from_bytes(&peer.recv().await).unwrap()
+}
+
+#[test]
+fn escapes_slashes_and_backslashes() {
+    assert_eq!(escape(b"a/b\\c"), b"a\\/b\\\\c");
+    assert_eq!(unescape(b"a\\/b\\\\c").unwrap(), b"a/b\\c");
+    assert_eq!(unescape(&escape(b"//\\\\")).unwrap(), b"//\\\\");
+    assert_eq!(unescape(b"").unwrap(), b"");
+
+    // Unescaped slash, unknown escape sequence, trailing backslash
+    assert_eq!(unescape(b"a/b"), None);
+    assert_eq!(unescape(b"a\\nb"), None);
+    assert_eq!(unescape(b"a\\"), None);
+}
+
+#[test]
+fn parses_messages() {
+    assert_eq!(
+        Message::from_bytes(b"/connect/12345/"),
+        Some(Message::Connect { session: 12345 })
+    );
+    assert_eq!(
+        Message::from_bytes(b"/data/12345/6/a\\/b\n/"),
+        Some(Message::Data {
+            session: 12345,
+            pos: 6,
+            data: b"a/b\n".to_vec(),
+        })
+    );
+    assert_eq!(
+        Message::from_bytes(b"/data/1/0//"),
+        Some(Message::Data {
+            session: 1,
+            pos: 0,
+            data: Vec::new(),
+        })
+    );
+    assert_eq!(
+        Message::from_bytes(b"/ack/12345/2147483647/"),
+        Some(Message::Ack {
+            session: 12345,
+            length: 2_147_483_647,
+        })
+    );
+    assert_eq!(
+        Message::from_bytes(b"/close/0/"),
+        Some(Message::Close { session: 0 })
+    );
+}
+
+#[test]
+fn ignores_invalid_messages() {
+    for packet in [
+        &b""[..],
+        b"/",
+        b"connect/1/",
+        b"/connect/1",
+        b"/connect/",
+        b"/connect/1/2/",
+        b"/connect/-1/",
+        b"/connect/+1/",
+        b"/connect/2147483648/",
+        b"/ack/1/",
+        b"/data/1/0/",
+        b"/data/1/0/a/b/",
+        b"/data/1/0/a\\/",
+        b"/close/x/",
+        b"/unknown/1/",
+    ] {
+        assert_eq!(
+            Message::from_bytes(packet),
+            None,
+            "{:?}",
+            String::from_utf8_lossy(packet)
+        );
+    }
+
+    let mut too_long = b"/data/1/0/".to_vec();
+    too_long.resize(MAX_PACKET_SIZE - 1, b'a');
+    too_long.push(b'/');
+    assert_eq!(Message::from_bytes(&too_long), None);
+}
+
+#[test]
+fn round_trips_messages() {
+    for message in [
+        Message::Connect { session: 7 },
+        Message::Data {
+            session: 7,
+            pos: 42,
+            data: b"/\\ and \n".to_vec(),
+        },
+        Message::Ack {
+            session: 7,
+            length: 42,
+        },
+        Message::Close { session: 7 },
+    ] {
+        assert_eq!(Message::from_bytes(&message.to_bytes()), Some(message));
+    }
+}
+
+#[test]
+fn chunks_fit_into_a_packet_once_escaped() {
+    assert_eq!(chunks(b"").count(), 0);
+    assert_eq!(chunks(b"hello").collect::<Vec<_>>(), [b"hello"]);
+
+    for data in [
+        vec![b'a'; 5000],
+        vec![b'/'; 5000],
+        b"a/\\".repeat(2000),
+        b"/".repeat(MAX_DATA_SIZE / 2 + 1),
+    ] {
+        let chunks: Vec<&[u8]> = chunks(&data).collect();
+        assert_eq!(chunks.concat(), data);
+
+        let mut pos = 0;
+        for chunk in chunks {
+            assert!(!chunk.is_empty());
+            assert!(escape(chunk).len() <= MAX_DATA_SIZE);
+            let message = Message::Data {
+                session: 2_147_483_647,
+                pos: pos as u32,
+                data: chunk.to_vec(),
+            };
+            assert!(message.to_bytes().len() < MAX_PACKET_SIZE);
+            pos += chunk.len();
+        }
+    }
+}
+
+#[tokio::test]
+async fn acks_data_the_application_has_not_read_yet() {
+    let (_listener, mut session, peer) = connect(1).await;
+
+    // More than fits into the pipe to the application
+    let data: Vec<u8> = (0..90_000).map(|idx| b'a' + (idx % 26) as u8).collect();
+    for (idx, chunk) in data.chunks(900).enumerate() {
+        let pos = (idx * 900) as u32;
+        peer.send(
+            Message::Data {
+                session: 1,
+                pos,
+                data: chunk.to_vec(),
+            }
+            .to_bytes(),
+        )
+        .await;
+        let length = pos + chunk.len() as u32;
+        assert_eq!(recv(&peer).await, Message::Ack { session: 1, length });
+    }
+
+    let mut received = vec![0_u8; data.len()];
+    timeout(TIMEOUT, session.stream.read_exact(&mut received))
+        .await
+        .expect("timed out reading the session")
+        .unwrap();
+    assert_eq!(received, data);
+}
+
+#[tokio::test]
+async fn acks_retransmissions_that_overlap_received_data() {
+    let (_listener, mut session, peer) = connect(1).await;
+
+    let data = |pos, data: &[u8]| {
+        Message::Data {
+            session: 1,
+            pos,
+            data: data.to_vec(),
+        }
+        .to_bytes()
+    };
+    peer.send(data(0, b"hello")).await;
+    assert_eq!(
+        recv(&peer).await,
+        Message::Ack {
+            session: 1,
+            length: 5
+        }
+    );
+    peer.send(data(3, b"lo world")).await;
+    assert_eq!(
+        recv(&peer).await,
+        Message::Ack {
+            session: 1,
+            length: 11
+        }
+    );
+    // Nothing new, everything is acked again
+    peer.send(data(0, b"hel")).await;
+    assert_eq!(
+        recv(&peer).await,
+        Message::Ack {
+            session: 1,
+            length: 11
+        }
+    );
+    // A gap is not accepted
+    peer.send(data(12, b"!")).await;
+    assert_eq!(
+        recv(&peer).await,
+        Message::Ack {
+            session: 1,
+            length: 11
+        }
+    );
+
+    let mut received = [0_u8; 11];
+    session.stream.read_exact(&mut received).await.unwrap();
+    assert_eq!(&received, b"hello world");
+}
+
+#[tokio::test]
+async fn keeps_other_peers_out_of_a_session() {
+    let (listener, mut session, peer) = connect(1).await;
+    let intruder = UdpClient::connect(listener.local_addr()).await;
+
+    // The session does not exist for anyone else
+    let injected = Message::Data {
+        session: 1,
+        pos: 0,
+        data: b"evil\n".to_vec(),
+    };
+    intruder.send(injected.to_bytes()).await;
+    assert_eq!(recv(&intruder).await, Message::Close { session: 1 });
+    intruder
+        .send(Message::Close { session: 1 }.to_bytes())
+        .await;
+    assert_eq!(recv(&intruder).await, Message::Close { session: 1 });
+
+    peer.send(
+        Message::Data {
+            session: 1,
+            pos: 0,
+            data: b"hello\n".to_vec(),
+        }
+        .to_bytes(),
+    )
+    .await;
+    assert_eq!(
+        recv(&peer).await,
+        Message::Ack {
+            session: 1,
+            length: 6
+        }
+    );
+
+    let mut received = [0_u8; 6];
+    session.stream.read_exact(&mut received).await.unwrap();
+    assert_eq!(&received, b"hello\n");
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::TestServer;
+use protohackers::problems::means_to_an_end::Message;
+use protohackers::problems::Problem;
+
+fn message(kind: u8, first: i32, second: i32) -> Vec<u8> {
+    let mut message = vec![kind];
+    message.extend(first.to_be_bytes());
+    message.extend(second.to_be_bytes());
+    message
+}
+
+#[test]
+fn parses_messages() {
+    assert_eq!(
+        Message::from_bytes(&message(b'I', 12345, 101)).unwrap(),
+        Message::Insert {
+            timestamp: 12345,
+            price: 101
+        }
+    );
+    assert_eq!(
+        Message::from_bytes(&message(b'Q', -5, 5)).unwrap(),
+        Message::Query {
+            mintime: -5,
+            maxtime: 5
+        }
+    );
+    assert!(Message::from_bytes(&message(b'X', 1, 2)).is_err());
+    assert!(Message::from_bytes(&message(b'I', 1, 2)[..8]).is_err());
+}
+
+#[tokio::test]
+async fn answers_queries_with_the_mean() {
+    let server = TestServer::start(Problem::MeansToAnEnd).await;
+    let mut client = server.connect().await;
+
+    client.send(message(b'I', 12345, 101)).await;
+    client.send(message(b'I', 12346, 102)).await;
+    client.send(message(b'I', 12347, 100)).await;
+    client.send(message(b'I', 40960, 5)).await;
+    client.send(message(b'Q', 12288, 16384)).await;
+    client.expect(101_i32.to_be_bytes()).await;
+
+    // No prices in range and an empty range
+    client.send(message(b'Q', 0, 100)).await;
+    client.expect(0_i32.to_be_bytes()).await;
+    client.send(message(b'Q', 16384, 12288)).await;
+    client.expect(0_i32.to_be_bytes()).await;
+}
+
+#[tokio::test]
+async fn keeps_prices_per_connection() {
+    let server = TestServer::start(Problem::MeansToAnEnd).await;
+    let mut first = server.connect().await;
+    let mut second = server.connect().await;
+
+    first.send(message(b'I', 1, 10)).await;
+    second.send(message(b'I', 1, 20)).await;
+    first.send(message(b'Q', 0, 2)).await;
+    first.expect(10_i32.to_be_bytes()).await;
+    second.send(message(b'Q', 0, 2)).await;
+    second.expect(20_i32.to_be_bytes()).await;
+}
+
+#[tokio::test]
+async fn accepts_messages_split_across_writes() {
+    let server = TestServer::start(Problem::MeansToAnEnd).await;
+    let mut client = server.connect().await;
+
+    let insert = message(b'I', 7, 70);
+    client.send(&insert[..3]).await;
+    client.send(&insert[3..]).await;
+    client.send(message(b'Q', 7, 7)).await;
+    client.expect(70_i32.to_be_bytes()).await;
+}
@@ -0,0 +1,70 @@
+mod common;
+
+use common::{TestServer, TIMEOUT};
+use protohackers::problems::Problem;
+use protohackers::Config;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+/// Sends a request to the metrics listener and returns the status line and the body
+async fn get(port: u16, path: &str) -> (String, String) {
+    let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+    let request = format!("GET {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    timeout(TIMEOUT, stream.read_to_string(&mut response))
+        .await
+        .expect("timed out waiting for the metrics")
+        .unwrap();
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.lines().next().unwrap().to_string();
+    (status, body.to_string())
+}
+
+#[tokio::test]
+async fn serves_the_stats_of_the_servers() {
+    let metrics_port = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let config = Config {
+        metrics_port: Some(metrics_port),
+        ..Config::default()
+    };
+    let server = TestServer::start_with(Problem::PrimeTime, config).await;
+
+    let mut client = server.connect().await;
+    client.send_line(r#"{"method":"isPrime","number":7}"#).await;
+    client
+        .expect_line(r#"{"method":"isPrime","prime":true}"#)
+        .await;
+    client.send_line(r#"{"method":"isPrime","number":8}"#).await;
+    client
+        .expect_line(r#"{"method":"isPrime","prime":false}"#)
+        .await;
+
+    let (status, body) = get(metrics_port, "/metrics").await;
+    assert_eq!(status, "HTTP/1.1 200 OK");
+    let label = format!("{{server=\"tcp://{}\"}}", server.addr);
+    for expected in [
+        "# TYPE protohackers_connections_active gauge".to_string(),
+        format!("protohackers_connections_active{label} 1"),
+        format!("protohackers_connections_accepted_total{label} 1"),
+        format!("protohackers_messages_received_total{label} 2"),
+        format!("protohackers_messages_sent_total{label} 2"),
+        "# TYPE protohackers_primes_checked_total counter".to_string(),
+        format!("protohackers_primes_checked_total{label} 2"),
+    ] {
+        assert!(
+            body.lines().any(|line| line == expected),
+            "{expected}\n{body}"
+        );
+    }
+
+    let (status, _) = get(metrics_port, "/").await;
+    assert_eq!(status, "HTTP/1.1 404 Not Found");
+}
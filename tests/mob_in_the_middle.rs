@@ -0,0 +1,94 @@
+mod common;
+
+use common::TestServer;
+use protohackers::problems::mob_in_the_middle::{do_the_boguscoin_rewrite, TONY};
+use protohackers::problems::Problem;
+use protohackers::Config;
+
+#[test]
+fn rewrites_boguscoin_addresses() {
+    let (rewritten, rewrites) =
+        do_the_boguscoin_rewrite("Send to 7F1u3wSD5RbOHQmupo9nx4TnhQ please\n");
+    assert_eq!(rewritten, format!("Send to {TONY} please"));
+    assert_eq!(rewrites, 1);
+
+    let (rewritten, rewrites) = do_the_boguscoin_rewrite(
+        "7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX 7LOrwbDlS8NujgjddyogWgIM93MV5N2VR",
+    );
+    assert_eq!(rewritten, format!("{TONY} {TONY}"));
+    assert_eq!(rewrites, 2);
+}
+
+#[test]
+fn leaves_everything_else_alone() {
+    for line in [
+        "Hi alice",
+        // Too short, too long, not alphanumeric
+        "7F1u3wSD5RbOHQmupo9nx4Tnh",
+        "7F1u3wSD5RbOHQmupo9nx4TnhQ7F1u3wSD5R",
+        "7F1u3wSD5RbOHQmupo9nx4TnhQ-123",
+        // Does not start with a 7
+        "8F1u3wSD5RbOHQmupo9nx4TnhQ",
+    ] {
+        assert_eq!(do_the_boguscoin_rewrite(line), (line.to_string(), 0));
+    }
+}
+
+/// The proxy in front of a Budget Chat server of this process
+async fn start(max_line_length: usize) -> (TestServer, TestServer) {
+    let chat = TestServer::start(Problem::BudgetChat).await;
+    let config = Config {
+        max_line_length,
+        mob_in_the_middle_upstream: chat.addr,
+        ..Config::default()
+    };
+    let proxy = TestServer::start_with(Problem::MobInTheMiddle, config).await;
+    (chat, proxy)
+}
+
+#[tokio::test]
+async fn rewrites_addresses_on_the_way_through() {
+    let (chat, proxy) = start(1024).await;
+
+    let mut alice = chat.connect().await;
+    alice.expect_line("name?").await;
+    alice.send_line("alice").await;
+    alice.expect_line("* LIST: ").await;
+
+    let mut bob = proxy.connect().await;
+    bob.expect_line("name?").await;
+    bob.send_line("bob").await;
+    bob.expect_line("* LIST: alice").await;
+    alice.expect_line("* JOIN: bob").await;
+
+    bob.send_line("Send to 7F1u3wSD5RbOHQmupo9nx4TnhQ please")
+        .await;
+    alice
+        .expect_line(&format!("[bob] Send to {TONY} please"))
+        .await;
+    alice
+        .send_line("Mine is 7iKDZEwPZSqIvDnHvVN2r0hUWXD5rHX")
+        .await;
+    bob.expect_line(&format!("[alice] Mine is {TONY}")).await;
+}
+
+#[tokio::test]
+async fn closes_connections_that_send_a_line_that_is_too_long() {
+    let (chat, proxy) = start(64).await;
+
+    let mut alice = chat.connect().await;
+    alice.expect_line("name?").await;
+    alice.send_line("alice").await;
+    alice.expect_line("* LIST: ").await;
+
+    let mut bob = proxy.connect().await;
+    bob.expect_line("name?").await;
+    bob.send_line("bob").await;
+    bob.expect_line("* LIST: alice").await;
+    alice.expect_line("* JOIN: bob").await;
+
+    bob.send("x".repeat(4096)).await;
+    bob.expect_closed().await;
+    // Nothing of it reached the chat
+    alice.expect_line("* PART: bob").await;
+}
@@ -0,0 +1,168 @@
+mod common;
+
+use common::{TestServer, TIMEOUT};
+use protohackers::problems::pest_control::types::{Action, Message, Population, TargetPopulation};
+use protohackers::problems::Problem;
+use protohackers::Config;
+use tokio::io::BufReader;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::timeout;
+
+/// One side of a connection that speaks the binary protocol
+struct Peer {
+    reader: BufReader<OwnedReadHalf>,
+    writer: OwnedWriteHalf,
+}
+
+impl Peer {
+    fn new(stream: TcpStream) -> Self {
+        let (reader, writer) = stream.into_split();
+        Self {
+            reader: BufReader::new(reader),
+            writer,
+        }
+    }
+
+    async fn send(&mut self, message: Message) {
+        message.to_bytes(&mut self.writer).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> Message {
+        timeout(TIMEOUT, Message::from_bytes(&mut self.reader))
+            .await
+            .expect("timed out waiting for a message")
+            .unwrap()
+    }
+
+    async fn expect(&mut self, expected: Message) {
+        assert_eq!(self.recv().await, expected);
+    }
+}
+
+/// The server and a fake authority server it is configured to dial
+async fn start() -> (TestServer, TcpListener) {
+    let authority = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let config = Config {
+        pest_control_authority: authority.local_addr().unwrap().to_string(),
+        ..Config::default()
+    };
+    let server = TestServer::start_with(Problem::PestControl, config).await;
+    (server, authority)
+}
+
+async fn connect(server: &TestServer) -> Peer {
+    let mut client = Peer::new(TcpStream::connect(server.addr).await.unwrap());
+    client.expect(Message::hello()).await;
+    client.send(Message::hello()).await;
+    client
+}
+
+async fn accept(authority: &TcpListener) -> Peer {
+    let (stream, _) = timeout(TIMEOUT, authority.accept())
+        .await
+        .expect("timed out waiting for the server to dial the authority")
+        .unwrap();
+    let mut authority = Peer::new(stream);
+    authority.send(Message::hello()).await;
+    authority.expect(Message::hello()).await;
+    authority
+}
+
+fn visit(site: u32, populations: &[(&str, u32)]) -> Message {
+    Message::SiteVisit {
+        site,
+        populations: populations
+            .iter()
+            .map(|&(species, count)| Population {
+                species: species.to_string(),
+                count,
+            })
+            .collect(),
+    }
+}
+
+#[tokio::test]
+async fn keeps_the_policies_of_the_authority_in_line_with_the_visits() {
+    let (server, authority) = start().await;
+    let mut client = connect(&server).await;
+
+    client.send(visit(12345, &[("dog", 5), ("rat", 100)])).await;
+
+    let mut authority = accept(&authority).await;
+    authority
+        .expect(Message::DialAuthority { site: 12345 })
+        .await;
+    authority
+        .send(Message::TargetPopulations {
+            site: 12345,
+            populations: vec![
+                TargetPopulation {
+                    species: "dog".to_string(),
+                    min: 1,
+                    max: 3,
+                },
+                TargetPopulation {
+                    species: "cat".to_string(),
+                    min: 2,
+                    max: 4,
+                },
+            ],
+        })
+        .await;
+
+    // Species without a target are ignored, missing ones count as 0
+    let mut created = Vec::new();
+    for policy in [1, 2] {
+        match authority.recv().await {
+            Message::CreatePolicy { species, action } => created.push((species, action)),
+            other => panic!("expected a new policy, got {other:?}"),
+        }
+        authority.send(Message::PolicyResult { policy }).await;
+    }
+    assert_eq!(
+        created,
+        [
+            ("dog".to_string(), Action::Cull),
+            ("cat".to_string(), Action::Conserve)
+        ]
+    );
+
+    // The dog population is fine now, cats still need to be conserved
+    client.send(visit(12345, &[("dog", 2), ("cat", 1)])).await;
+    authority.expect(Message::DeletePolicy { policy: 1 }).await;
+    authority.send(Message::Ok).await;
+
+    client.send(visit(12345, &[("dog", 0), ("cat", 3)])).await;
+    authority
+        .expect(Message::CreatePolicy {
+            species: "dog".to_string(),
+            action: Action::Conserve,
+        })
+        .await;
+    authority.send(Message::PolicyResult { policy: 3 }).await;
+    authority.expect(Message::DeletePolicy { policy: 2 }).await;
+    authority.send(Message::Ok).await;
+}
+
+#[tokio::test]
+async fn rejects_visits_with_conflicting_counts() {
+    let (server, _authority) = start().await;
+    let mut client = connect(&server).await;
+
+    client.send(visit(1, &[("dog", 1), ("dog", 2)])).await;
+    match client.recv().await {
+        Message::Error { message } => assert!(message.contains("dog"), "{message}"),
+        other => panic!("expected an error, got {other:?}"),
+    }
+}
+
+#[tokio::test]
+async fn rejects_clients_that_do_not_say_hello() {
+    let (server, _authority) = start().await;
+    let mut client = Peer::new(TcpStream::connect(server.addr).await.unwrap());
+    client.expect(Message::hello()).await;
+
+    client.send(visit(1, &[("dog", 1)])).await;
+    assert!(matches!(client.recv().await, Message::Error { .. }));
+}
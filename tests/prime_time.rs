@@ -0,0 +1,500 @@
+mod common;
+
+use common::TestServer;
+use num_bigint::{BigInt, BigUint};
+use protohackers::problems::prime_time::oracle::{
+    OracleStats, PrimeOracle, Source, MAX_CACHED_BITS,
+};
+use protohackers::problems::prime_time::primality::{self, factorize, next_prime, prime_count};
+use protohackers::problems::prime_time::{Protocol, Request};
+use protohackers::problems::Problem;
+use protohackers::Config;
+use serde_json::{json, Value};
+
+fn is_prime(json: &str) -> bool {
+    serde_json::from_str::<Request>(json).unwrap().is_prime()
+}
+
+#[test]
+fn checks_numbers() {
+    assert!(is_prime(r#"{"method":"isPrime","number":7}"#));
+    assert!(is_prime(r#"{"method":"isPrime","number":7.0}"#));
+    assert!(!is_prime(r#"{"method":"isPrime","number":8}"#));
+    assert!(!is_prime(r#"{"method":"isPrime","number":1}"#));
+    assert!(!is_prime(r#"{"method":"isPrime","number":0}"#));
+    assert!(!is_prime(r#"{"method":"isPrime","number":-7}"#));
+    assert!(!is_prime(r#"{"method":"isPrime","number":7.5}"#));
+}
+
+fn number_is_prime(number: &str) -> bool {
+    is_prime(&format!(r#"{{"method":"isPrime","number":{number}}}"#))
+}
+
+#[test]
+fn checks_numbers_exactly() {
+    // Both are 2^53 as an f64
+    assert!(!number_is_prime("9007199254740993"));
+    assert!(number_is_prime("9007199254740997"));
+    // The largest prime below 2^64, and 2^64 + 1
+    assert!(number_is_prime("18446744073709551557"));
+    assert!(!number_is_prime("18446744073709551617"));
+    // Mersenne primes
+    assert!(number_is_prime("618970019642690137449562111"));
+    assert!(number_is_prime("170141183460469231731687303715884105727"));
+    // (2^61 - 1) * (2^89 - 1)
+    assert!(!number_is_prime(
+        "1427247692705959880439315947500961989719490561"
+    ));
+}
+
+#[test]
+fn checks_miller_rabin_pseudoprimes() {
+    // Passes the bases up to 23
+    assert!(!number_is_prime("3825123056546413051"));
+    // 399165290221 * 798330580441, passes the bases up to 37
+    assert!(!number_is_prime("318665857834031151167461"));
+    // Passes the bases up to 41
+    assert!(!number_is_prime("3317044064679887385961981"));
+}
+
+#[test]
+fn checks_every_notation_of_a_number() {
+    for prime in ["7", "7.0", "7.000", "0.7e1", "70e-1", "700E-2", "7e0"] {
+        assert!(number_is_prime(prime), "{prime}");
+    }
+    for not_prime in [
+        "70", "7e1", "7e-1", "0.07e1", "-7", "-7.0", "0", "-0", "0.0", "1e400",
+    ] {
+        assert!(!number_is_prime(not_prime), "{not_prime}");
+    }
+    // Exponents too large for any integer type
+    assert!(!number_is_prime("7e99999999999999999999"));
+    assert!(!number_is_prime("7e-99999999999999999999"));
+}
+
+#[tokio::test]
+async fn answers_every_request() {
+    let server = TestServer::start(Problem::PrimeTime).await;
+    let mut client = server.connect().await;
+
+    client
+        .send(concat!(
+            r#"{"method":"isPrime","number":13}"#,
+            "\n",
+            r#"{"number":12,"method":"isPrime","extra":true}"#,
+            "\n",
+            r#"{"method":"isPrime","number":170141183460469231731687303715884105727}"#,
+            "\n",
+        ))
+        .await;
+    client
+        .expect_line(r#"{"method":"isPrime","prime":true}"#)
+        .await;
+    client
+        .expect_line(r#"{"method":"isPrime","prime":false}"#)
+        .await;
+    client
+        .expect_line(r#"{"method":"isPrime","prime":true}"#)
+        .await;
+}
+
+#[tokio::test]
+async fn disconnects_after_a_malformed_request() {
+    let server = TestServer::start(Problem::PrimeTime).await;
+
+    for malformed in [
+        r#"{"method":"isPrime"}"#,
+        r#"{"method":"isComposite","number":4}"#,
+        r#"{"method":"isPrime","number":"4"}"#,
+        "not json",
+    ] {
+        let mut client = server.connect().await;
+        client.send_line(malformed).await;
+        client.expect("malformed").await;
+        client.expect_closed().await;
+    }
+}
+
+#[test]
+fn factorizes() {
+    assert_eq!(factorize(1), Vec::<u64>::new());
+    assert_eq!(factorize(2), [2]);
+    assert_eq!(factorize(600851475143), [71, 839, 1471, 6857]);
+    assert_eq!(factorize(u64::MAX), [3, 5, 17, 257, 641, 65537, 6700417]);
+    assert_eq!(factorize(18446743979220271189), [4294967279, 4294967291]);
+    assert_eq!(factorize(4294967291 * 4294967291), [4294967291, 4294967291]);
+}
+
+#[test]
+fn finds_the_next_prime() {
+    let next = |n: BigInt| next_prime(&n);
+    assert_eq!(next(BigInt::from(-5)), BigUint::from(2_u32));
+    assert_eq!(next(BigInt::from(2)), BigUint::from(3_u32));
+    assert_eq!(next(BigInt::from(13)), BigUint::from(17_u32));
+    assert_eq!(
+        next(BigInt::from(u64::MAX)),
+        BigUint::from(u64::MAX) + 14_u32
+    );
+    let googol = BigInt::from(10).pow(100);
+    assert_eq!(
+        next(googol.clone()),
+        (googol + BigInt::from(267)).to_biguint().unwrap()
+    );
+}
+
+#[test]
+fn counts_primes() {
+    assert_eq!(prime_count(0), 0);
+    assert_eq!(prime_count(2), 1);
+    assert_eq!(prime_count(10), 4);
+    assert_eq!(prime_count(1000), 168);
+    assert_eq!(prime_count(10_000_000), 664579);
+    assert_eq!(prime_count(10_000_000_000), 455052511);
+}
+
+async fn start(protocol: Protocol) -> TestServer {
+    let config = Config {
+        prime_time_protocol: protocol,
+        ..Config::default()
+    };
+    TestServer::start_with(Problem::PrimeTime, config).await
+}
+
+#[tokio::test]
+async fn answers_extended_methods() {
+    let server = start(Protocol::Extended).await;
+    let mut client = server.connect().await;
+
+    for (request, response) in [
+        (
+            json!({"method": "isPrime", "number": 7}),
+            json!({"method": "isPrime", "prime": true}),
+        ),
+        (
+            json!({"method": "factorize", "number": 12}),
+            json!({"method": "factorize", "factors": [2, 2, 3]}),
+        ),
+        (
+            json!({"method": "nextPrime", "number": 13}),
+            json!({"method": "nextPrime", "prime": 17}),
+        ),
+        (
+            json!({"method": "primeCount", "number": 100}),
+            json!({"method": "primeCount", "count": 25}),
+        ),
+        (
+            json!({"method": "isPrimeBatch", "numbers": [2, 4, 7.5, -3, 11]}),
+            json!({"method": "isPrimeBatch", "primes": [true, false, false, false, true]}),
+        ),
+    ] {
+        client.send_line(&request.to_string()).await;
+        let received: Value = serde_json::from_str(&client.recv_line().await).unwrap();
+        assert_eq!(received, response);
+    }
+
+    client
+        .send_line(r#"{"method":"factorize","number":0}"#)
+        .await;
+    client.expect("malformed").await;
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn keeps_the_strict_protocol_by_default() {
+    let server = TestServer::start(Problem::PrimeTime).await;
+    let mut client = server.connect().await;
+
+    client
+        .send_line(r#"{"method":"factorize","number":12}"#)
+        .await;
+    client.expect("malformed").await;
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn answers_json_rpc() {
+    let server = start(Protocol::JsonRpc).await;
+    let mut client = server.connect().await;
+    let mut call = async |request: Value| -> Value {
+        client.send_line(&request.to_string()).await;
+        serde_json::from_str(&client.recv_line().await).unwrap()
+    };
+
+    assert_eq!(
+        call(json!({"jsonrpc": "2.0", "method": "isPrime", "params": {"number": 7}, "id": 1}))
+            .await,
+        json!({"jsonrpc": "2.0", "result": true, "id": 1})
+    );
+    assert_eq!(
+        call(json!({"jsonrpc": "2.0", "method": "factorize", "params": [12], "id": "a"})).await,
+        json!({"jsonrpc": "2.0", "result": [2, 2, 3], "id": "a"})
+    );
+
+    let error = call(json!({"jsonrpc": "2.0", "method": "nope", "id": 2})).await;
+    assert_eq!(error["error"]["code"], -32601);
+    assert_eq!(error["id"], 2);
+    let error =
+        call(json!({"jsonrpc": "2.0", "method": "factorize", "params": [0.5], "id": 3})).await;
+    assert_eq!(error["error"]["code"], -32602);
+    let error = call(json!({"method": "isPrime", "params": [7], "id": 4})).await;
+    assert_eq!(error["error"]["code"], -32600);
+    let error = call(json!([])).await;
+    assert_eq!(error["error"]["code"], -32600);
+
+    // Notifications are not answered, not even in a batch
+    let batch = call(json!([
+        {"jsonrpc": "2.0", "method": "isPrime", "params": [4]},
+        {"jsonrpc": "2.0", "method": "nextPrime", "params": {"number": 100}, "id": 5},
+        {"jsonrpc": "2.0", "method": "primeCount", "params": [10], "id": 6},
+    ]))
+    .await;
+    assert_eq!(
+        batch,
+        json!([
+            {"jsonrpc": "2.0", "result": 101, "id": 5},
+            {"jsonrpc": "2.0", "result": 4, "id": 6},
+        ])
+    );
+
+    // Parse errors do not close the connection either
+    client.send_line("not json").await;
+    let error: Value = serde_json::from_str(&client.recv_line().await).unwrap();
+    assert_eq!(error["error"]["code"], -32700);
+    assert_eq!(error["id"], Value::Null);
+    client
+        .send_line(r#"{"jsonrpc":"2.0","method":"isPrime","params":[4]}"#)
+        .await;
+    client
+        .expect_silence(std::time::Duration::from_millis(100))
+        .await;
+}
+
+async fn expect_in_order(server: &TestServer) {
+    let mut client = server.connect().await;
+
+    // The slow count comes first and is answered first
+    let mut requests = String::new();
+    requests.push_str(r#"{"method":"primeCount","number":10000000000}"#);
+    requests.push('\n');
+    for number in 0..50 {
+        requests.push_str(&format!(r#"{{"method":"isPrime","number":{number}}}"#));
+        requests.push('\n');
+    }
+    client.send(requests).await;
+
+    client
+        .expect_line(r#"{"count":455052511,"method":"primeCount"}"#)
+        .await;
+    for number in 0..50 {
+        let prime = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47].contains(&number);
+        client
+            .expect_line(&format!(r#"{{"method":"isPrime","prime":{prime}}}"#))
+            .await;
+    }
+}
+
+#[tokio::test]
+async fn answers_pipelined_requests_in_order() {
+    expect_in_order(&start(Protocol::Extended).await).await;
+}
+
+#[tokio::test]
+async fn answers_in_order_with_a_window_of_one() {
+    let config = Config {
+        prime_time_protocol: Protocol::Extended,
+        prime_time_window: 1,
+        ..Config::default()
+    };
+    expect_in_order(&TestServer::start_with(Problem::PrimeTime, config).await).await;
+}
+
+#[tokio::test]
+async fn answers_the_requests_before_a_malformed_one() {
+    let server = TestServer::start(Problem::PrimeTime).await;
+    let mut client = server.connect().await;
+
+    client
+        .send(concat!(
+            r#"{"method":"isPrime","number":170141183460469231731687303715884105727}"#,
+            "\n",
+            r#"{"method":"isPrime","number":4}"#,
+            "\n",
+            "not json\n",
+            r#"{"method":"isPrime","number":7}"#,
+            "\n",
+        ))
+        .await;
+    client
+        .expect_line(r#"{"method":"isPrime","prime":true}"#)
+        .await;
+    client
+        .expect_line(r#"{"method":"isPrime","prime":false}"#)
+        .await;
+    client.expect("malformed").await;
+    client.expect_closed().await;
+}
+
+#[test]
+fn sieve_agrees_with_miller_rabin() {
+    let oracle = PrimeOracle::new(100_000, 0);
+    for n in 0..=100_001_u32 {
+        let n = BigUint::from(n);
+        assert_eq!(oracle.is_prime(&n), primality::is_prime(&n), "{n}");
+    }
+    assert_eq!(oracle.stats().sieve_hits, 100_001);
+}
+
+#[test]
+fn caches_the_most_recently_used_numbers() {
+    let oracle = PrimeOracle::new(100, 2);
+    let lookup = |n: u64| oracle.lookup(&BigUint::from(n));
+
+    assert_eq!(lookup(97), (true, Source::Sieve));
+    assert_eq!(lookup(101), (true, Source::Computed));
+    assert_eq!(lookup(102), (false, Source::Computed));
+    assert_eq!(lookup(101), (true, Source::Cache));
+    // Evicts 102, 101 was used more recently
+    assert_eq!(lookup(103), (true, Source::Computed));
+    assert_eq!(lookup(101), (true, Source::Cache));
+    assert_eq!(lookup(102), (false, Source::Computed));
+
+    assert_eq!(
+        oracle.stats(),
+        OracleStats {
+            sieve_hits: 1,
+            cache_hits: 2,
+            cache_misses: 4,
+            cached: 2,
+        }
+    );
+}
+
+#[test]
+fn does_not_cache_huge_numbers() {
+    let oracle = PrimeOracle::new(100, 10);
+    let largest = (BigUint::from(1_u32) << MAX_CACHED_BITS) - 1_u32;
+    let too_large = BigUint::from(1_u32) << MAX_CACHED_BITS;
+
+    assert_eq!(oracle.lookup(&too_large), (false, Source::Computed));
+    assert_eq!(oracle.lookup(&too_large), (false, Source::Computed));
+    assert_eq!(oracle.stats().cached, 0);
+
+    // Divisible by 3, so it is answered quickly
+    assert_eq!(oracle.lookup(&largest), (false, Source::Computed));
+    assert_eq!(oracle.lookup(&largest), (false, Source::Cache));
+    assert_eq!(oracle.stats().cached, 1);
+}
+
+#[test]
+fn does_not_look_up_numbers_that_cannot_be_primes() {
+    let oracle = PrimeOracle::new(100, 10);
+    let number = |json: &str| serde_json::from_str(json).unwrap();
+
+    assert_eq!(
+        oracle.lookup_number(&number("7.0")),
+        (true, Some(Source::Sieve))
+    );
+    assert_eq!(oracle.lookup_number(&number("-7")), (false, None));
+    assert_eq!(oracle.lookup_number(&number("7.5")), (false, None));
+    assert_eq!(oracle.lookup_number(&number("1e400")), (false, None));
+    assert_eq!(
+        oracle.lookup_number(&number("170141183460469231731687303715884105727")),
+        (true, Some(Source::Computed))
+    );
+}
+
+async fn start_with_max_line_length(protocol: Protocol, max_line_length: usize) -> TestServer {
+    let config = Config {
+        prime_time_protocol: protocol,
+        max_line_length,
+        ..Config::default()
+    };
+    TestServer::start_with(Problem::PrimeTime, config).await
+}
+
+#[tokio::test]
+async fn answers_a_line_that_is_too_long_with_malformed() {
+    let server = start_with_max_line_length(Protocol::Strict, 1024).await;
+    let mut client = server.connect().await;
+
+    // Exactly at the limit
+    let number = "1".repeat(1024 - r#"{"method":"isPrime","number":}"#.len());
+    client
+        .send_line(&format!(r#"{{"method":"isPrime","number":{number}}}"#))
+        .await;
+    client
+        .expect_line(r#"{"method":"isPrime","prime":false}"#)
+        .await;
+
+    // Never ends, the server does not wait for the newline
+    client.send("1".repeat(4096)).await;
+    client.expect("malformed").await;
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn answers_a_line_that_is_too_long_with_a_json_rpc_error() {
+    let server = start_with_max_line_length(Protocol::JsonRpc, 1024).await;
+    let mut client = server.connect().await;
+
+    client
+        .send_line(&format!(
+            r#"{{"jsonrpc":"2.0","method":"isPrime","params":[{}],"id":1}}"#,
+            "1".repeat(2048)
+        ))
+        .await;
+    let response: Value = serde_json::from_str(&client.recv_line().await).unwrap();
+    assert_eq!(response["error"]["code"], -32600);
+    assert_eq!(response["id"], Value::Null);
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn refuses_integers_with_too_many_digits() {
+    let config = Config {
+        prime_time_max_digits: 40,
+        ..Config::default()
+    };
+    let server = TestServer::start_with(Problem::PrimeTime, config).await;
+    let mut client = server.connect().await;
+
+    // 2^127 - 1 has 39 digits. The others are longer but cannot be primes anyway.
+    let large = "1".repeat(41);
+    for (number, prime) in [
+        ("170141183460469231731687303715884105727.0", true),
+        ("1e40", false),
+        (&format!("-{large}"), false),
+        (&format!("{large}.5"), false),
+    ] {
+        client
+            .send_line(&format!(r#"{{"method":"isPrime","number":{number}}}"#))
+            .await;
+        client
+            .expect_line(&format!(r#"{{"method":"isPrime","prime":{prime}}}"#))
+            .await;
+    }
+    client
+        .send_line(&format!(r#"{{"method":"isPrime","number":{large}}}"#))
+        .await;
+    client.expect("malformed").await;
+    client.expect_closed().await;
+
+    let config = Config {
+        prime_time_protocol: Protocol::JsonRpc,
+        prime_time_max_digits: 40,
+        ..Config::default()
+    };
+    let server = TestServer::start_with(Problem::PrimeTime, config).await;
+    let mut client = server.connect().await;
+    client
+        .send_line(&format!(
+            r#"{{"jsonrpc":"2.0","method":"isPrimeBatch","params":[[7,1e40,{large}]],"id":1}}"#
+        ))
+        .await;
+    let response: Value = serde_json::from_str(&client.recv_line().await).unwrap();
+    assert_eq!(response["error"]["code"], -32602);
+    assert_eq!(
+        response["error"]["data"],
+        "integers must have at most 40 digits"
+    );
+}
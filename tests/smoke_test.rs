@@ -0,0 +1,71 @@
+mod common;
+
+use std::time::Duration;
+
+use common::TestServer;
+use protohackers::problems::Problem;
+use protohackers::ShutdownSummary;
+
+#[tokio::test]
+async fn echoes_until_the_client_closes() {
+    let server = TestServer::start(Problem::SmokeTest).await;
+    let mut client = server.connect().await;
+
+    client.send(b"hello\x00\xff").await;
+    client.expect(b"hello\x00\xff").await;
+    client.send(b" world").await;
+    client.expect(b" world").await;
+
+    drop(client);
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn handles_clients_at_the_same_time() {
+    let server = TestServer::start(Problem::SmokeTest).await;
+    let mut first = server.connect().await;
+    let mut second = server.connect().await;
+
+    second.send("second").await;
+    first.send("first").await;
+    first.expect("first").await;
+    second.expect("second").await;
+}
+
+#[tokio::test]
+async fn drains_connections_that_finish_before_the_shutdown_timeout() {
+    let server = TestServer::start(Problem::SmokeTest).await;
+    let mut client = server.connect().await;
+    client.send("hello").await;
+    client.expect("hello").await;
+
+    // The test server waits one second
+    let (summary, ()) = tokio::join!(server.stop(), async {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(client);
+    });
+    assert_eq!(
+        summary,
+        ShutdownSummary {
+            drained: 1,
+            aborted: 0
+        }
+    );
+}
+
+#[tokio::test]
+async fn aborts_connections_that_are_still_open_after_the_shutdown_timeout() {
+    let server = TestServer::start(Problem::SmokeTest).await;
+    let mut client = server.connect().await;
+    client.send("hello").await;
+    client.expect("hello").await;
+
+    assert_eq!(
+        server.stop().await,
+        ShutdownSummary {
+            drained: 0,
+            aborted: 1
+        }
+    );
+    client.expect_closed().await;
+}
@@ -0,0 +1,175 @@
+mod common;
+
+use std::time::Duration;
+
+use common::TestServer;
+use protohackers::problems::speed_daemon::types::{ClientMessage, Error, ServerMessage, Ticket};
+use protohackers::problems::Problem;
+
+fn camera(road: u16, mile: u16, limit: u16) -> Vec<u8> {
+    let mut message = vec![0x80];
+    for field in [road, mile, limit] {
+        message.extend(field.to_be_bytes());
+    }
+    message
+}
+
+fn plate(plate: &str, timestamp: u32) -> Vec<u8> {
+    let mut message = vec![0x20, plate.len() as u8];
+    message.extend(plate.as_bytes());
+    message.extend(timestamp.to_be_bytes());
+    message
+}
+
+fn dispatcher(roads: &[u16]) -> Vec<u8> {
+    let mut message = vec![0x81, roads.len() as u8];
+    for road in roads {
+        message.extend(road.to_be_bytes());
+    }
+    message
+}
+
+async fn encode(message: ServerMessage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    message.to_bytes(&mut bytes).await.unwrap();
+    bytes
+}
+
+#[tokio::test]
+async fn parses_client_messages() {
+    let message = ClientMessage::from_bytes(&mut &plate("UN1X", 1000)[..])
+        .await
+        .unwrap();
+    assert!(
+        matches!(message, ClientMessage::Plate(plate) if plate.plate == "UN1X" && plate.timestamp == 1000)
+    );
+
+    let message = ClientMessage::from_bytes(&mut &dispatcher(&[66, 368, 5000])[..])
+        .await
+        .unwrap();
+    assert!(
+        matches!(message, ClientMessage::IAmDispatcher(dispatcher) if dispatcher.roads == [66, 368, 5000])
+    );
+
+    assert!(ClientMessage::from_bytes(&mut &[0x99_u8][..])
+        .await
+        .is_err());
+    assert!(ClientMessage::from_bytes(&mut &camera(1, 2, 3)[..5])
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn encodes_server_messages() {
+    assert_eq!(
+        encode(ServerMessage::Error(Error::with_msg("bad"))).await,
+        b"\x10\x03bad"
+    );
+    assert!(ServerMessage::Error(Error::with_msg("x".repeat(256)))
+        .to_bytes(&mut Vec::new())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn tickets_cars_that_are_too_fast() {
+    let server = TestServer::start(Problem::SpeedDaemon).await;
+
+    let mut first = server.connect().await;
+    first.send(camera(123, 8, 60)).await;
+    first.send(plate("UN1X", 0)).await;
+    let mut second = server.connect().await;
+    second.send(camera(123, 9, 60)).await;
+    second.send(plate("UN1X", 45)).await;
+
+    let mut dispatcher_client = server.connect().await;
+    dispatcher_client.send(dispatcher(&[123])).await;
+    let ticket = Ticket {
+        plate: "UN1X".to_string(),
+        road: 123,
+        mile1: 8,
+        timestamp1: 0,
+        mile2: 9,
+        timestamp2: 45,
+        speed: 8000,
+    };
+    dispatcher_client
+        .expect(encode(ServerMessage::Ticket(ticket)).await)
+        .await;
+}
+
+#[tokio::test]
+async fn keeps_the_first_observation_of_a_timestamp() {
+    let server = TestServer::start(Problem::SpeedDaemon).await;
+
+    let mut first = server.connect().await;
+    first.send(camera(123, 8, 60)).await;
+    first.send(plate("UN1X", 0)).await;
+    // Cameras are separate connections, nothing orders their observations
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    // Would be 84 mph from mile 100 to mile 16 if it replaced the first one
+    let mut second = server.connect().await;
+    second.send(camera(123, 100, 60)).await;
+    second.send(plate("UN1X", 0)).await;
+    let mut third = server.connect().await;
+    third.send(camera(123, 16, 60)).await;
+    third.send(plate("UN1X", 3600)).await;
+
+    let mut dispatcher_client = server.connect().await;
+    dispatcher_client.send(dispatcher(&[123])).await;
+    dispatcher_client
+        .expect_silence(Duration::from_millis(200))
+        .await;
+}
+
+#[tokio::test]
+async fn sends_heartbeats() {
+    let server = TestServer::start(Problem::SpeedDaemon).await;
+    let mut client = server.connect().await;
+
+    // Every 0.1 seconds
+    client.send([0x40, 0, 0, 0, 1]).await;
+    client.expect([0x41]).await;
+    client.expect([0x41]).await;
+}
+
+#[tokio::test]
+async fn rejects_illegal_messages() {
+    let server = TestServer::start(Problem::SpeedDaemon).await;
+
+    let mut client = server.connect().await;
+    client.send([0x99]).await;
+    client
+        .expect(encode(ServerMessage::Error(Error::with_msg("illegal msg"))).await)
+        .await;
+    client.expect_closed().await;
+
+    // Plates only come from cameras
+    let mut client = server.connect().await;
+    client.send(plate("UN1X", 0)).await;
+    client
+        .expect(
+            encode(ServerMessage::Error(Error::with_msg(
+                "You are not a camera",
+            )))
+            .await,
+        )
+        .await;
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn closes_idle_connections_with_an_error() {
+    let config = protohackers::Config {
+        idle_timeout: Some(1),
+        ..Default::default()
+    };
+    let server = TestServer::start_with(Problem::SpeedDaemon, config).await;
+    let mut client = server.connect().await;
+
+    client.expect_silence(Duration::from_millis(500)).await;
+    client
+        .expect(encode(ServerMessage::Error(Error::with_msg("idle timeout"))).await)
+        .await;
+    client.expect_closed().await;
+}
@@ -0,0 +1,146 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use protohackers::stats::{ConnectionStats, ServerStats};
+use protohackers::stream::{TimedOut, Timeouts};
+use protohackers::Stream;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::Instant;
+
+const SECOND: Duration = Duration::from_secs(1);
+
+/// The server side of a fresh connection and the client on the other end
+async fn connect(timeouts: Timeouts) -> (Stream, TcpStream, Arc<ConnectionStats>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let client = TcpStream::connect(listener.local_addr().unwrap())
+        .await
+        .unwrap();
+    let (server, _) = listener.accept().await.unwrap();
+    let stats = ServerStats::register("stream-test").connection();
+    (Stream::new(server, timeouts, stats.clone()), client, stats)
+}
+
+async fn read_error(stream: &mut Stream) -> io::Error {
+    let mut buffer = [0_u8; 16];
+    let err = stream.read(&mut buffer).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    err
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_reads_without_a_first_byte() {
+    let timeouts = Timeouts {
+        first_byte: Some(10 * SECOND),
+        idle: Some(60 * SECOND),
+        ..Timeouts::default()
+    };
+    let (mut stream, _client, _) = connect(timeouts).await;
+    let start = Instant::now();
+
+    let err = read_error(&mut stream).await;
+    assert_eq!(TimedOut::find(&err), Some(TimedOut::FirstByte));
+    assert_eq!(start.elapsed().as_secs(), 10);
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_reads_after_being_idle() {
+    let timeouts = Timeouts {
+        idle: Some(5 * SECOND),
+        session: Some(60 * SECOND),
+        ..Timeouts::default()
+    };
+    let (mut stream, mut client, _) = connect(timeouts).await;
+
+    tokio::time::sleep(3 * SECOND).await;
+    client.write_all(b"hi").await.unwrap();
+    let mut buffer = [0_u8; 2];
+    stream.read_exact(&mut buffer).await.unwrap();
+    let heard = Instant::now();
+
+    // Counted from the last activity, not from connecting
+    let err = read_error(&mut stream).await;
+    assert_eq!(TimedOut::find(&err), Some(TimedOut::Idle));
+    assert_eq!(heard.elapsed().as_secs(), 5);
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_reads_at_the_end_of_the_session_even_if_active() {
+    let timeouts = Timeouts {
+        idle: Some(2 * SECOND),
+        session: Some(5 * SECOND),
+        ..Timeouts::default()
+    };
+    let (mut stream, mut client, _) = connect(timeouts).await;
+    let start = Instant::now();
+
+    let mut buffer = [0_u8; 1];
+    for _ in 0..4 {
+        tokio::time::sleep(SECOND).await;
+        client.write_all(b"x").await.unwrap();
+        stream.read_exact(&mut buffer).await.unwrap();
+    }
+
+    // The idle timeout would only pass after 6 seconds
+    let err = read_error(&mut stream).await;
+    assert_eq!(TimedOut::find(&err), Some(TimedOut::Session));
+    assert_eq!(start.elapsed().as_secs(), 5);
+}
+
+#[tokio::test(start_paused = true)]
+async fn writes_a_goodbye_after_a_timeout() {
+    let timeouts = Timeouts {
+        idle: Some(5 * SECOND),
+        ..Timeouts::default()
+    };
+    let (mut stream, mut client, _) = connect(timeouts).await;
+
+    read_error(&mut stream).await;
+    stream.write_all(b"bye\n").await.unwrap();
+    drop(stream);
+
+    let mut goodbye = String::new();
+    client.read_to_string(&mut goodbye).await.unwrap();
+    assert_eq!(goodbye, "bye\n");
+}
+
+#[tokio::test(start_paused = true)]
+async fn fails_writes_that_wait_for_a_client_that_does_not_read() {
+    let timeouts = Timeouts {
+        idle: Some(5 * SECOND),
+        ..Timeouts::default()
+    };
+    let (mut stream, _client, stats) = connect(timeouts).await;
+
+    // Far more than the socket buffers take
+    let data = vec![0_u8; 64 * 1024 * 1024];
+    let err = stream.write_all(&data).await.unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+    assert_eq!(TimedOut::find(&err), Some(TimedOut::Idle));
+
+    let written = stats.summary().bytes_out;
+    assert!(written > 0 && written < data.len() as u64, "{written}");
+}
+
+#[tokio::test]
+async fn counts_the_bytes_in_both_directions() {
+    let (mut stream, mut client, stats) = connect(Timeouts::default()).await;
+
+    client.write_all(b"hello").await.unwrap();
+    let mut buffer = [0_u8; 5];
+    stream.read_exact(&mut buffer).await.unwrap();
+
+    let (mut reader, mut writer) = stream.split();
+    writer.write_all(b"hello world").await.unwrap();
+    client.shutdown().await.unwrap();
+    assert_eq!(reader.read(&mut buffer).await.unwrap(), 0);
+    drop((reader, writer));
+
+    let summary = stats.summary();
+    assert_eq!((summary.bytes_in, summary.bytes_out), (5, 11));
+
+    let mut received = [0_u8; 11];
+    client.read_exact(&mut received).await.unwrap();
+    assert_eq!(&received, b"hello world");
+}
@@ -0,0 +1,150 @@
+mod common;
+
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common::{UdpClient, TIMEOUT};
+use protohackers::udp::{self, Datagram, UdpOptions};
+use protohackers::{stats, Config, Error, ShutdownSummary};
+use tokio::net::UdpSocket;
+use tokio::sync::{oneshot, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+
+struct Server {
+    addr: SocketAddr,
+    shutdown: oneshot::Sender<()>,
+    task: JoinHandle<Result<ShutdownSummary, Error>>,
+}
+
+impl Server {
+    async fn start<H, Fut>(options: UdpOptions, handler: H) -> Self
+    where
+        H: FnOnce(Datagram) -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = Result<(), Error>> + Send,
+    {
+        let addr = UdpSocket::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap();
+        let config = Config {
+            bind: addr.ip().to_string(),
+            port: addr.port(),
+            shutdown_timeout: 1,
+            ..Config::default()
+        };
+
+        let (shutdown, stopped) = oneshot::channel();
+        let task = tokio::spawn(async move {
+            udp::serve_until(&config, options, handler, async {
+                let _ = stopped.await;
+            })
+            .await
+        });
+        // Servers register their stats right after binding
+        let name = format!("udp://{addr}");
+        timeout(TIMEOUT, async {
+            while !stats::snapshot().iter().any(|server| server.name == name) {
+                tokio::time::sleep(Duration::from_millis(5)).await;
+            }
+        })
+        .await
+        .expect("the server did not start");
+
+        Self {
+            addr,
+            shutdown,
+            task,
+        }
+    }
+
+    async fn stop(self) -> ShutdownSummary {
+        let _ = self.shutdown.send(());
+        self.task.await.unwrap().unwrap()
+    }
+}
+
+#[tokio::test]
+async fn per_peer_tasks_keep_the_order_of_a_peer_but_not_across_peers() {
+    let release = Arc::new(Notify::new());
+    let options = UdpOptions {
+        per_peer_tasks: true,
+        ..UdpOptions::default()
+    };
+    let server = Server::start(options, {
+        let release = release.clone();
+        move |datagram: Datagram| async move {
+            if datagram.data == b"wait" {
+                release.notified().await;
+            }
+            datagram.reply.send(&datagram.data).await
+        }
+    })
+    .await;
+
+    let slow = UdpClient::connect(server.addr).await;
+    let fast = UdpClient::connect(server.addr).await;
+    slow.send("wait").await;
+    slow.send("after").await;
+
+    // Would wait for the slow peer forever if both were handled on the same task
+    fast.send("hello").await;
+    fast.expect("hello").await;
+    slow.expect_silence(Duration::from_millis(100)).await;
+
+    release.notify_one();
+    slow.expect("wait").await;
+    slow.expect("after").await;
+
+    // Both peers are still waiting for their next datagram
+    assert_eq!(
+        server.stop().await,
+        ShutdownSummary {
+            drained: 2,
+            aborted: 0
+        }
+    );
+}
+
+#[tokio::test]
+async fn forgets_peers_after_the_peer_timeout() {
+    for per_peer_tasks in [false, true] {
+        let options = UdpOptions {
+            per_peer_tasks,
+            peer_timeout: Duration::from_millis(200),
+            ..UdpOptions::default()
+        };
+        let server = Server::start(options, |datagram: Datagram| async move {
+            datagram
+                .reply
+                .send(datagram.id.to_string().as_bytes())
+                .await
+        })
+        .await;
+        let client = UdpClient::connect(server.addr).await;
+
+        client.send("").await;
+        client.expect("0").await;
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        client.send("").await;
+        client.expect("0").await;
+
+        // A new peer as far as the server is concerned
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        client.send("").await;
+        client.expect("1").await;
+
+        // The task of the first peer ended with it
+        let running = usize::from(per_peer_tasks);
+        assert_eq!(
+            server.stop().await,
+            ShutdownSummary {
+                drained: running,
+                aborted: 0
+            }
+        );
+    }
+}
@@ -0,0 +1,41 @@
+mod common;
+
+use common::TestServer;
+use protohackers::problems::Problem;
+
+#[tokio::test]
+async fn stores_and_retrieves_values() {
+    let server = TestServer::start(Problem::UnusualDatabaseProgram).await;
+    let client = server.udp_client().await;
+
+    client.send("foo=bar").await;
+    client.send("foo").await;
+    client.expect("foo=bar").await;
+
+    // Only the first `=` separates key and value
+    client.send("foo=bar=baz").await;
+    client.send("foo").await;
+    client.expect("foo=bar=baz").await;
+
+    client.send("=empty key").await;
+    client.send("").await;
+    client.expect("=empty key").await;
+
+    // Missing keys are answered with an empty value
+    client.send("missing").await;
+    client.expect("missing=").await;
+}
+
+#[tokio::test]
+async fn does_not_let_clients_change_the_version() {
+    let server = TestServer::start(Problem::UnusualDatabaseProgram).await;
+    let client = server.udp_client().await;
+
+    client.send("version").await;
+    let version = client.recv().await;
+    assert!(version.starts_with(b"version="));
+
+    client.send("version=mine").await;
+    client.send("version").await;
+    client.expect(version).await;
+}
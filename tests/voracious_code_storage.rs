@@ -0,0 +1,111 @@
+mod common;
+
+use common::TestServer;
+use protohackers::problems::voracious_code_storage::{Command, Invalid, MAX_FILE_SIZE};
+use protohackers::problems::Problem;
+use protohackers::Config;
+
+#[test]
+fn parses_commands() {
+    assert_eq!(Command::parse("help"), Ok(Command::Help));
+    assert_eq!(
+        Command::parse("GET /a/b.txt r2"),
+        Ok(Command::Get {
+            file: "/a/b.txt".to_string(),
+            revision: Some(2)
+        })
+    );
+    assert_eq!(
+        Command::parse("PUT /a.txt 5"),
+        Ok(Command::Put {
+            file: "/a.txt".to_string(),
+            length: 5
+        })
+    );
+    assert_eq!(Command::parse("PUT a.txt 5"), Err(Invalid::IllegalFileName));
+    assert_eq!(Command::parse("LIST /a//b"), Err(Invalid::IllegalDirName));
+    assert_eq!(
+        Command::parse("DELETE /a.txt"),
+        Err(Invalid::IllegalMethod("DELETE".to_string()))
+    );
+}
+
+#[tokio::test]
+async fn stores_revisions() {
+    let server = TestServer::start(Problem::VoraciousCodeStorage).await;
+    let mut client = server.connect().await;
+    client.expect_line("READY").await;
+
+    client.send("PUT /dir/test.txt 6\nhello\n").await;
+    client.expect_line("OK r1").await;
+    client.expect_line("READY").await;
+    client.send("PUT /dir/test.txt 6\nworld\n").await;
+    client.expect_line("OK r2").await;
+    client.expect_line("READY").await;
+
+    client.send_line("GET /dir/test.txt r1").await;
+    client.expect_line("OK 6").await;
+    client.expect("hello\n").await;
+    client.expect_line("READY").await;
+
+    client.send_line("LIST /").await;
+    client.expect_line("OK 1").await;
+    client.expect_line("dir/ DIR").await;
+    client.expect_line("READY").await;
+    client.send_line("LIST /dir").await;
+    client.expect_line("OK 1").await;
+    client.expect_line("test.txt r2").await;
+    client.expect_line("READY").await;
+}
+
+#[tokio::test]
+async fn refuses_binary_files_and_unknown_methods() {
+    let server = TestServer::start(Problem::VoraciousCodeStorage).await;
+    let mut client = server.connect().await;
+    client.expect_line("READY").await;
+
+    client.send(b"PUT /binary 2\n\x00\x01").await;
+    client.expect_line("ERR text files only").await;
+    client.expect_line("READY").await;
+
+    client.send_line("GET /binary").await;
+    client.expect_line("ERR no such file").await;
+    client.expect_line("READY").await;
+
+    client.send_line("FROB").await;
+    client.expect_line("ERR illegal method: FROB").await;
+    client.expect_closed().await;
+}
+
+#[tokio::test]
+async fn refuses_files_that_are_too_large() {
+    let server = TestServer::start(Problem::VoraciousCodeStorage).await;
+
+    for length in [u64::MAX, MAX_FILE_SIZE as u64 + 1] {
+        let mut client = server.connect().await;
+        client.expect_line("READY").await;
+        client.send_line(&format!("PUT /big {length}")).await;
+        client.expect_line("ERR file too large").await;
+        client.expect_closed().await;
+    }
+
+    let mut client = server.connect().await;
+    client.expect_line("READY").await;
+    client.send_line("GET /big").await;
+    client.expect_line("ERR no such file").await;
+}
+
+#[tokio::test]
+async fn refuses_lines_that_are_too_long() {
+    let config = Config {
+        max_line_length: 64,
+        ..Config::default()
+    };
+    let server = TestServer::start_with(Problem::VoraciousCodeStorage, config).await;
+    let mut client = server.connect().await;
+    client.expect_line("READY").await;
+
+    client.send("LIST /".repeat(1000)).await;
+    client.expect_line("ERR line too long").await;
+    client.expect_closed().await;
+}